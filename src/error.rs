@@ -11,6 +11,11 @@ pub enum Error {
     OscarIo(oscar_io::error::Error),
     StripPrefixError(std::path::StripPrefixError),
     Custom(String),
+    Warc(warc::Error),
+    #[cfg(feature = "parquet")]
+    Arrow(arrow::error::ArrowError),
+    #[cfg(feature = "parquet")]
+    Parquet(parquet::errors::ParquetError),
 }
 
 #[cfg(not(tarpaulin_include))]
@@ -32,8 +37,28 @@ impl From<serde_json::Error> for Error {
     }
 }
 
+impl From<warc::Error> for Error {
+    fn from(v: warc::Error) -> Self {
+        Self::Warc(v)
+    }
+}
+
 impl From<std::io::Error> for Error {
     fn from(err: std::io::Error) -> Error {
         Error::Io(err)
     }
 }
+
+#[cfg(feature = "parquet")]
+impl From<arrow::error::ArrowError> for Error {
+    fn from(v: arrow::error::ArrowError) -> Self {
+        Self::Arrow(v)
+    }
+}
+
+#[cfg(feature = "parquet")]
+impl From<parquet::errors::ParquetError> for Error {
+    fn from(v: parquet::errors::ParquetError) -> Self {
+        Self::Parquet(v)
+    }
+}
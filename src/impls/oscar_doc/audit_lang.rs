@@ -0,0 +1,49 @@
+//! Language identification audit of OSCAR v2 corpora (see [crate::ops::AuditLang]).
+use std::path::PathBuf;
+
+use clap::{arg, ArgMatches};
+
+use crate::{cli::Command, error::Error, ops::AuditLang};
+
+pub(crate) struct AuditLangDoc;
+impl AuditLang for AuditLangDoc {}
+
+impl Command for AuditLangDoc {
+    fn subcommand() -> clap::App<'static>
+    where
+        Self: Sized,
+    {
+        clap::App::new("audit-lang")
+            .about("Audit the consistency of document and line language identifications.")
+            .long_about("Audit the consistency of document and line language identifications.
+For each document label, the report holds the line labels found in its documents (the confusion),
+and the number of flagged documents, whose majority line label differs from the document label.
+Unidentified lines are reported but do not count towards the majority.")
+            .arg(arg!([SOURCE] "Corpus source file/folder.").required(true))
+            .arg(arg!([REPORT] "JSON report file.").required(true))
+            .arg(arg!(--flagged <FILE> "Write flagged documents into FILE.").required(false))
+            .arg(arg!(-J --num_threads <NUM_THREADS> "Number of threads to use (iif source is a folder). If 0, take all available").default_value("0").required(false))
+    }
+
+    fn run(matches: &ArgMatches) -> Result<(), Error>
+    where
+        Self: Sized,
+    {
+        let src: PathBuf = matches
+            .value_of("SOURCE")
+            .expect("Value of 'SOURCE' is required.")
+            .into();
+        let report: PathBuf = matches
+            .value_of("REPORT")
+            .expect("Value of 'REPORT' is required.")
+            .into();
+        let flagged: Option<PathBuf> = matches.value_of("flagged").map(PathBuf::from);
+        let num_threads: usize = matches
+            .value_of("num_threads")
+            .unwrap()
+            .parse()
+            .expect("'num_threads' has to be a number.");
+
+        Self::audit(&src, &report, flagged.as_deref(), num_threads)
+    }
+}
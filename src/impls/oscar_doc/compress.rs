@@ -29,6 +29,7 @@ Only provide a folder (resp. file) as a destination if a folder (resp. file) has
             .arg(arg!([DESTINATION] "File/folder to write to.").required(true))
             .arg(arg!(--del_src "If set, deletes source files as they are being compressed.").required(false))
             .arg(arg!(--compression <COMP> "Compression to use (gzip, zstd)").required(false).default_value("zstd"))
+            .arg(arg!(--resume "If set, skips files compressed by a previous run into DESTINATION (iif source is a folder).").required(false))
             .arg(arg!(-J --num_threads <NUM_THREADS> "Number of threads to use (iif source is a folder). If 0, take all available").default_value("0").required(false))
     }
 
@@ -45,6 +46,7 @@ Only provide a folder (resp. file) as a destination if a folder (resp. file) has
             .expect("Value of 'DESTINATION' is required.")
             .into();
         let del_src = matches.is_present("del_src");
+        let resume = matches.is_present("resume");
         let compression = matches.value_of("compression").unwrap();
         let num_threads: usize = matches
             .value_of("num_threads")
@@ -54,7 +56,7 @@ Only provide a folder (resp. file) as a destination if a folder (resp. file) has
         if src.is_file() {
             CompressDoc::compress_file(&src, &dst, del_src, compression)?;
         } else if src.is_dir() {
-            CompressDoc::compress_folder(&src, &dst, del_src, compression, num_threads)?;
+            CompressDoc::compress_folder(&src, &dst, del_src, compression, num_threads, resume)?;
         } else {
             return Err(
                 std::io::Error::new(std::io::ErrorKind::NotFound, format!("{:?}", src)).into(),
@@ -0,0 +1,223 @@
+/*! Removal (or flagging) of documents matching a reference set (see [crate::ops::Decontaminate]) !*/
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+use clap::{arg, ArgMatches};
+use oscar_io::oscar_doc::{Document, Writer};
+
+use crate::{
+    cli::Command,
+    error::Error,
+    ops::{CorpusReader, Decontaminate, Normalization, ReferenceSet, TextUnit},
+};
+
+/// Annotation added to flagged documents.
+const CONTAMINATION_ANNOTATION: &str = "contamination";
+
+pub struct DecontaminateDoc;
+
+impl DecontaminateDoc {
+    /// Will read documents from a Reader and write documents that don't match `references` into a Writer.
+    /// If `flag` is set, matching documents are written too, with an additional `contamination` annotation.
+    fn decontaminate_write<T, U>(
+        src: T,
+        dst: &mut Writer<U>,
+        references: &mut ReferenceSet,
+        flag: bool,
+    ) -> Result<(), Error>
+    where
+        T: Iterator<Item = Result<Document, oscar_io::error::Error>>,
+        U: std::io::Write,
+    {
+        for doc in src {
+            let mut doc = doc?;
+            if references.check(doc.content()) {
+                if flag {
+                    doc.metadata_mut()
+                        .add_annotation(CONTAMINATION_ANNOTATION.to_string());
+                    dst.write(&doc)?;
+                }
+            } else {
+                dst.write(&doc)?;
+            }
+        }
+        dst.flush()?;
+        Ok(())
+    }
+}
+
+impl Decontaminate for DecontaminateDoc {
+    fn decontaminate(
+        src: &Path,
+        dst: &Path,
+        references: &mut ReferenceSet,
+        flag: bool,
+    ) -> Result<(), Error> {
+        let dst_file = File::create(dst)?;
+        let dst_buf = BufWriter::new(dst_file);
+
+        let cr = CorpusReader::new(src)?;
+        let mut wr = Writer::new(dst_buf);
+        Self::decontaminate_write(cr, &mut wr, references, flag)
+    }
+}
+
+impl Command for DecontaminateDoc {
+    fn subcommand() -> clap::App<'static>
+    where
+        Self: Sized,
+    {
+        clap::App::new("decontaminate")
+            .about("Remove documents matching reference (benchmark) items, by line or by word n-gram. Use --flag to annotate them instead.")
+            .arg(arg!([SOURCE] "Corpus source file/folder. If folder, reads all corpus files in provided folder.").required(true))
+            .arg(arg!([DESTINATION] "Corpus destination file.").required(true))
+            .arg(arg!(-r --references <FILES> "Comma separated reference files (can be repeated).").use_value_delimiter(true).require_value_delimiter(true).multiple_occurrences(true).required(true))
+            .arg(arg!(--unit <UNIT> "Reference items are lines (line) or blank-line separated blocks (doc).").default_value("line").required(false))
+            .arg(arg!(--ngram <N> "Also match word n-grams of size N.").required(false))
+            .arg(arg!(--flag "Keep matching documents, adding a 'contamination' annotation.").required(false))
+            .arg(arg!(--report <REPORT> "Path of the JSON report of hit reference items.").required(false))
+            .args(Normalization::args())
+    }
+
+    fn run(matches: &ArgMatches) -> Result<(), Error>
+    where
+        Self: Sized,
+    {
+        let src: PathBuf = matches.value_of("SOURCE").unwrap().into();
+        let dst: PathBuf = matches.value_of("DESTINATION").unwrap().into();
+        let reference_paths: Vec<PathBuf> = matches
+            .values_of("references")
+            .unwrap()
+            .map(PathBuf::from)
+            .collect();
+        let unit: TextUnit = matches.value_of("unit").unwrap().parse()?;
+        let ngram_size: Option<usize> = matches
+            .value_of("ngram")
+            .map(|n| n.parse().expect("'ngram' has to be a number."));
+        let flag = matches.is_present("flag");
+
+        let normalization = Normalization::from_matches(matches);
+
+        let mut references =
+            ReferenceSet::from_paths(&reference_paths, unit, ngram_size, normalization)?;
+        Self::decontaminate(&src, &dst, &mut references, flag)?;
+
+        let (checked, matched) = references.stats();
+        if flag {
+            info!("flagged {matched} documents out of {checked}");
+        } else {
+            info!("removed {matched} documents out of {checked}");
+        }
+        if let Some(report) = matches.value_of("report") {
+            let mut w = BufWriter::new(File::create(report)?);
+            references.write_report(&mut w)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, io::Cursor};
+
+    use oscar_io::oscar_doc::{Document, Metadata, Reader, Writer};
+
+    use crate::{
+        cli::Command,
+        ops::{Normalization, ReferenceSet},
+    };
+
+    use super::DecontaminateDoc;
+
+    fn get_documents() -> Vec<Result<Document, oscar_io::error::Error>> {
+        [
+            "nothing to see here",
+            "some text\nWho wrote Hamlet?\nmore text",
+            "clean document",
+        ]
+        .into_iter()
+        .map(|content| {
+            Ok(Document::new(
+                content.to_string(),
+                HashMap::new(),
+                Metadata::default(),
+            ))
+        })
+        .collect()
+    }
+
+    fn get_references() -> ReferenceSet {
+        let mut references = ReferenceSet::new(None, Normalization::default());
+        references.add_item("bench.txt", 1, "Who wrote Hamlet?");
+        references
+    }
+
+    #[test]
+    fn test_decontaminate_drop() {
+        let mut references = get_references();
+        let mut dst = vec![];
+        let mut wr = Writer::new(&mut dst);
+        DecontaminateDoc::decontaminate_write(
+            get_documents().into_iter(),
+            &mut wr,
+            &mut references,
+            false,
+        )
+        .unwrap();
+
+        let docs: Vec<Document> = Reader::new(Cursor::new(dst))
+            .map(|doc| doc.unwrap())
+            .collect();
+        let contents: Vec<&str> = docs.iter().map(|doc| doc.content().as_str()).collect();
+        assert_eq!(contents, ["nothing to see here", "clean document"]);
+        assert_eq!(references.stats(), (3, 1));
+    }
+
+    #[test]
+    fn test_decontaminate_flag() {
+        let mut references = get_references();
+        let mut dst = vec![];
+        let mut wr = Writer::new(&mut dst);
+        DecontaminateDoc::decontaminate_write(
+            get_documents().into_iter(),
+            &mut wr,
+            &mut references,
+            true,
+        )
+        .unwrap();
+
+        let docs: Vec<Document> = Reader::new(Cursor::new(dst))
+            .map(|doc| doc.unwrap())
+            .collect();
+        assert_eq!(docs.len(), 3);
+        assert_eq!(docs[0].metadata().annotation(), None);
+        assert_eq!(
+            docs[1].metadata().annotation(),
+            Some(&vec!["contamination".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_references_before_positionals() {
+        let matches = DecontaminateDoc::subcommand()
+            .try_get_matches_from([
+                "decontaminate",
+                "-r",
+                "a.txt,b.txt",
+                "-r",
+                "c.txt",
+                "in",
+                "out",
+            ])
+            .unwrap();
+        assert_eq!(
+            matches.values_of("references").unwrap().collect::<Vec<_>>(),
+            ["a.txt", "b.txt", "c.txt"]
+        );
+        assert_eq!(matches.value_of("SOURCE"), Some("in"));
+        assert_eq!(matches.value_of("DESTINATION"), Some("out"));
+    }
+}
@@ -0,0 +1,61 @@
+//! Export of OSCAR v2 corpora (see [crate::ops::Export]).
+use std::path::PathBuf;
+
+use clap::{arg, ArgMatches};
+
+use crate::{
+    cli::Command,
+    error::Error,
+    ops::{Export, ExportFormat},
+};
+
+pub(crate) struct ExportDoc;
+impl Export for ExportDoc {}
+
+impl Command for ExportDoc {
+    fn subcommand() -> clap::App<'static>
+    where
+        Self: Sized,
+    {
+        clap::App::new("export")
+            .about("Export a corpus file/folder to another format, keeping the folder layout.")
+            .long_about("Export a corpus file/folder to another format, keeping the folder layout.
+Each corpus file <lang>/<lang>_part_<n>.jsonl[.gz] is exported into DESTINATION/<lang>/<lang>_part_<n>.<format>.
+parquet: one row per document, with content, warc_headers (map) and metadata (struct, with lists of annotations and sentence identifications) columns. Needs the parquet feature.")
+            .arg(arg!([SOURCE] "Corpus source file/folder.").required(true))
+            .arg(arg!([DESTINATION] "Destination file/folder.").required(true))
+            .arg(arg!(-f --format <FORMAT> "Export format.").possible_values(ExportFormat::NAMES).required(true))
+            .arg(arg!(--row_group_size <NB_DOCS> "Maximum number of documents per Parquet row group.").default_value("10000").required(false))
+            .arg(arg!(-J --num_threads <NUM_THREADS> "Number of threads to use (iif source is a folder). If 0, take all available").default_value("0").required(false))
+    }
+
+    fn run(matches: &ArgMatches) -> Result<(), Error>
+    where
+        Self: Sized,
+    {
+        let src: PathBuf = matches
+            .value_of("SOURCE")
+            .expect("Value of 'SOURCE' is required.")
+            .into();
+        let dst: PathBuf = matches
+            .value_of("DESTINATION")
+            .expect("Value of 'DESTINATION' is required.")
+            .into();
+        let format: ExportFormat = matches
+            .value_of("format")
+            .expect("Value of 'format' is required.")
+            .parse()?;
+        let row_group_size: usize = matches
+            .value_of("row_group_size")
+            .unwrap()
+            .parse()
+            .expect("'row_group_size' has to be a number.");
+        let num_threads: usize = matches
+            .value_of("num_threads")
+            .unwrap()
+            .parse()
+            .expect("'num_threads' has to be a number.");
+
+        Self::export(&src, &dst, format, row_group_size, num_threads)
+    }
+}
@@ -0,0 +1,74 @@
+//! Extraction of document fields into side tables (see [crate::ops::ExtractFields]).
+use std::path::PathBuf;
+
+use clap::{arg, ArgMatches};
+
+use crate::{
+    cli::Command,
+    error::Error,
+    ops::{ExtractFields, FieldsFormat},
+};
+
+pub(crate) struct ExtractFieldsDoc;
+impl ExtractFields for ExtractFieldsDoc {}
+
+impl Command for ExtractFieldsDoc {
+    fn subcommand() -> clap::App<'static>
+    where
+        Self: Sized,
+    {
+        clap::App::new("extract-fields")
+            .about("Extract document fields (without content) into TSV, CSV or JSONL tables.")
+            .long_about("Extract document fields (without content) into TSV, CSV or JSONL tables.
+Fields are JSON pointers into documents, such as /warc_headers/warc-record-id, /warc_headers/warc-target-uri,
+/warc_headers/content-length, /metadata/identification/label, /metadata/identification/prob or /metadata/annotation.
+Missing fields are left empty. TSV/CSV files start with a header row holding the pointers.
+Folders keep their layout: fr/fr_part_1.jsonl.gz is extracted into DESTINATION/fr/fr_part_1.tsv.")
+            .arg(arg!([SOURCE] "Corpus source file/folder.").required(true))
+            .arg(arg!([DESTINATION] "Destination file/folder.").required(true))
+            .arg(
+                arg!(-F --fields <FIELDS> "Comma separated JSON pointers of the fields to extract (can be repeated).")
+                    .use_value_delimiter(true)
+                    .require_value_delimiter(true)
+                    .multiple_occurrences(true)
+                    .required(true),
+            )
+            .arg(
+                arg!(-f --format <FORMAT> "Table format.")
+                    .possible_values(FieldsFormat::NAMES)
+                    .default_value("tsv")
+                    .required(false),
+            )
+            .arg(arg!(-J --num_threads <NUM_THREADS> "Number of threads to use (iif source is a folder). If 0, take all available").default_value("0").required(false))
+    }
+
+    fn run(matches: &ArgMatches) -> Result<(), Error>
+    where
+        Self: Sized,
+    {
+        let src: PathBuf = matches
+            .value_of("SOURCE")
+            .expect("Value of 'SOURCE' is required.")
+            .into();
+        let dst: PathBuf = matches
+            .value_of("DESTINATION")
+            .expect("Value of 'DESTINATION' is required.")
+            .into();
+        let fields: Vec<String> = matches
+            .values_of("fields")
+            .expect("'fields' is required.")
+            .map(String::from)
+            .collect();
+        let format: FieldsFormat = matches
+            .value_of("format")
+            .expect("'format' has a default value.")
+            .parse()?;
+        let num_threads: usize = matches
+            .value_of("num_threads")
+            .unwrap()
+            .parse()
+            .expect("'num_threads' has to be a number.");
+
+        Self::extract_fields(&src, &dst, &fields, format, num_threads)
+    }
+}
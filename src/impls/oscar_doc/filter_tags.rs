@@ -3,11 +3,11 @@
  */
 use std::{borrow::Cow, collections::HashSet, fs::File, io::BufWriter};
 
-use oscar_io::oscar_doc::{Document, SplitFolderReader, Writer};
+use oscar_io::oscar_doc::{Document, Writer};
 
 use crate::error::Error;
 
-use crate::ops::FilterTags;
+use crate::ops::{CorpusReader, FilterTags};
 
 pub struct FilterTagDoc;
 impl FilterTags for FilterTagDoc {
@@ -21,7 +21,7 @@ impl FilterTags for FilterTagDoc {
         let dst_file = File::create(dst)?;
         let dst_buf = BufWriter::new(dst_file);
 
-        let mut cr = SplitFolderReader::new(src)?;
+        let mut cr = CorpusReader::new(src)?;
         let mut wr = Writer::new(dst_buf);
         Self::filter_write(&mut cr, &mut wr, clean, include, exclude)?;
         Ok(())
@@ -0,0 +1,180 @@
+//! Random document access for OSCAR v2 corpora.
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+use clap::{arg, ArgGroup, ArgMatches};
+
+use crate::{
+    cli::Command,
+    error::Error,
+    ops::{DocKey, DocLookup},
+};
+
+/// Get documents by ordinal, record id or URL, writing them as JSON lines.
+pub(crate) struct GetDoc;
+
+impl GetDoc {
+    /// Keys requested on the command line, in argument order within each kind.
+    fn keys(matches: &ArgMatches) -> Result<Vec<DocKey>, Error> {
+        let mut keys = Vec::new();
+        if let Some(ordinals) = matches.values_of("ordinal") {
+            for ordinal in ordinals {
+                let ordinal = ordinal.parse().map_err(|_| {
+                    Error::Custom(format!("'ordinal' has to be a number, got {ordinal:?}"))
+                })?;
+                keys.push(DocKey::Ordinal(ordinal));
+            }
+        }
+        if let Some(record_ids) = matches.values_of("record_id") {
+            keys.extend(record_ids.map(|id| DocKey::RecordId(id.to_string())));
+        }
+        if let Some(urls) = matches.values_of("url") {
+            keys.extend(urls.map(|url| DocKey::Url(url.to_string())));
+        }
+        Ok(keys)
+    }
+
+    /// Write the documents matching `keys` in `w`. Returns the number of keys that matched no document.
+    fn get<W: Write>(lookup: &mut DocLookup, keys: &[DocKey], w: &mut W) -> Result<usize, Error> {
+        let mut nb_missing = 0;
+        for key in keys {
+            let docs = lookup.get_raw(key)?;
+            if docs.is_empty() {
+                warn!("no document found for {:?}", key);
+                nb_missing += 1;
+            }
+            for doc in docs {
+                writeln!(w, "{doc}")?;
+            }
+        }
+        w.flush()?;
+        Ok(nb_missing)
+    }
+}
+
+impl Command for GetDoc {
+    fn subcommand() -> clap::App<'static>
+    where
+        Self: Sized,
+    {
+        clap::App::new("get")
+            .about("Get documents by ordinal (starting at 0), WARC record id or URL.")
+            .long_about("Get documents by ordinal (starting at 0), WARC record id or URL.
+SOURCE can be an uncompressed corpus file or a folder of corpus parts, ordinals spanning over parts in part order.
+Indices written by 'index' (with --keys for record ids and URLs) are used when fresh, and built in memory otherwise.")
+            .arg(arg!([SOURCE] "Corpus source file/folder.").required(true))
+            .arg(arg!(--ordinal <ORDINAL> "Comma separated document ordinal(s) (can be repeated).").required(false).use_value_delimiter(true).require_value_delimiter(true).multiple_occurrences(true))
+            .arg(arg!(--record_id <RECORD_ID> "Document WARC record id (e.g. '<urn:uuid:...>', can be repeated).").required(false).multiple_occurrences(true))
+            .arg(arg!(--url <URL> "Document URL (can be repeated). Several documents can share a URL.").required(false).multiple_occurrences(true))
+            .group(ArgGroup::new("key").args(&["ordinal", "record_id", "url"]).multiple(true).required(true))
+            .arg(arg!(-o --output <OUTPUT> "File to write documents to. Defaults to stdout.").required(false))
+    }
+
+    fn run(matches: &ArgMatches) -> Result<(), Error>
+    where
+        Self: Sized,
+    {
+        let src: PathBuf = matches
+            .value_of("SOURCE")
+            .expect("Value of 'SOURCE' is required.")
+            .into();
+        let keys = Self::keys(matches)?;
+        let mut lookup = DocLookup::open(&src)?;
+        debug!("{:?} holds {} documents", src, lookup.len());
+
+        let nb_missing = match matches.value_of("output") {
+            Some(dst) => {
+                let mut w = BufWriter::new(File::create(dst)?);
+                Self::get(&mut lookup, &keys, &mut w)?
+            }
+            None => Self::get(&mut lookup, &keys, &mut std::io::stdout().lock())?,
+        };
+        if nb_missing > 0 {
+            return Err(Error::Custom(format!(
+                "{nb_missing} key(s) matched no document. Check logs."
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use oscar_io::oscar_doc::{Document, Metadata, Writer};
+    use tempfile::tempdir;
+
+    use crate::{
+        cli::Command,
+        ops::{DocKey, DocLookup},
+    };
+
+    use super::GetDoc;
+
+    #[test]
+    fn test_get() {
+        let dir = tempdir().unwrap();
+        let corpus = dir.path().join("fr_meta.jsonl");
+        let docs: Vec<Document> = (0..3)
+            .map(|i| {
+                let headers = HashMap::from([(
+                    "warc-target-uri".to_string(),
+                    format!("https://example.com/{i}"),
+                )]);
+                Document::new(format!("document {i}"), headers, Metadata::default())
+            })
+            .collect();
+        let mut wr = Writer::new(std::fs::File::create(&corpus).unwrap());
+        wr.write_multiple(&docs).unwrap();
+        wr.flush().unwrap();
+
+        let mut lookup = DocLookup::open(&corpus).unwrap();
+        let keys = [
+            DocKey::Url("https://example.com/2".to_string()),
+            DocKey::Ordinal(0),
+            DocKey::Ordinal(3),
+        ];
+        let mut w = Vec::new();
+        let nb_missing = GetDoc::get(&mut lookup, &keys, &mut w).unwrap();
+        assert_eq!(nb_missing, 1);
+
+        let found: Vec<String> = String::from_utf8(w)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                let doc: serde_json::Value = serde_json::from_str(line).unwrap();
+                doc["content"].as_str().unwrap().to_string()
+            })
+            .collect();
+        assert_eq!(found, ["document 2", "document 0"]);
+    }
+
+    #[test]
+    fn test_keys_before_source() {
+        let matches = GetDoc::subcommand()
+            .try_get_matches_from([
+                "get",
+                "--ordinal",
+                "0,2",
+                "--url",
+                "https://example.com/a,b",
+                "--url",
+                "https://example.com/c",
+                "corpus.jsonl",
+            ])
+            .unwrap();
+        assert_eq!(
+            matches.values_of("ordinal").unwrap().collect::<Vec<_>>(),
+            ["0", "2"]
+        );
+        assert_eq!(
+            matches.values_of("url").unwrap().collect::<Vec<_>>(),
+            ["https://example.com/a,b", "https://example.com/c"]
+        );
+        assert_eq!(matches.value_of("SOURCE"), Some("corpus.jsonl"));
+    }
+}
@@ -0,0 +1,67 @@
+//! Export of OSCAR v2 corpora into the Hugging Face datasets layout (see [crate::ops::HfDataset]).
+use std::path::PathBuf;
+
+use clap::{arg, ArgMatches};
+
+use crate::{
+    cli::Command,
+    error::Error,
+    ops::{Checksum, Compress, Export, HfDataset, Rotation, ShardFormat},
+};
+
+pub(crate) struct HfDatasetDoc;
+impl Checksum for HfDatasetDoc {}
+impl Compress for HfDatasetDoc {}
+impl Export for HfDatasetDoc {}
+impl HfDataset for HfDatasetDoc {}
+
+impl Command for HfDatasetDoc {
+    fn subcommand() -> clap::App<'static>
+    where
+        Self: Sized,
+    {
+        clap::App::new("export-hf")
+            .about("Export a corpus folder into the Hugging Face datasets layout.")
+            .long_about("Export a corpus folder into the Hugging Face datasets layout.
+Each language becomes a config, held in DESTINATION/<lang>/ as shards of the chosen format.
+DESTINATION also gets a dataset_infos.json file with per-config sizes, shard sizes and SHA-384 checksums,
+and a README.md dataset card declaring the configs.
+Parquet shards need the parquet feature.")
+            .arg(arg!([SOURCE] "Corpus source folder.").required(true))
+            .arg(arg!([DESTINATION] "Dataset folder. Has to be empty or not to exist.").required(true))
+            .arg(arg!(--name <NAME> "Dataset name, used in the dataset card. Defaults to the name of DESTINATION.").required(false))
+            .arg(arg!(-f --format <FORMAT> "Shard format.").possible_values(ShardFormat::NAMES).default_value("jsonl.gz").required(false))
+            .args(Rotation::args(Some("500")))
+            .arg(arg!(-J --num_threads <NUM_THREADS> "Number of threads to use. If 0, take all available").default_value("0").required(false))
+    }
+
+    fn run(matches: &ArgMatches) -> Result<(), Error>
+    where
+        Self: Sized,
+    {
+        let src: PathBuf = matches
+            .value_of("SOURCE")
+            .expect("Value of 'SOURCE' is required.")
+            .into();
+        let dst: PathBuf = matches
+            .value_of("DESTINATION")
+            .expect("Value of 'DESTINATION' is required.")
+            .into();
+        let name = match matches.value_of("name") {
+            Some(name) => name.to_string(),
+            None => dst
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "OSCAR".to_string()),
+        };
+        let format: ShardFormat = matches.value_of("format").unwrap().parse()?;
+        let rotation = Rotation::from_matches(matches)?.expect("'size' has a default value.");
+        let num_threads: usize = matches
+            .value_of("num_threads")
+            .unwrap()
+            .parse()
+            .expect("'num_threads' has to be a number.");
+
+        Self::hf_dataset(&src, &dst, &name, format, rotation, num_threads)
+    }
+}
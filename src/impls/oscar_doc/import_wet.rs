@@ -0,0 +1,45 @@
+//! Import of WARC/WET files into OSCAR v2 documents (see [crate::ops::ImportWet]).
+use std::path::PathBuf;
+
+use clap::{arg, ArgMatches};
+
+use crate::{cli::Command, error::Error, ops::ImportWet};
+
+pub(crate) struct ImportWetDoc;
+impl ImportWet for ImportWetDoc {}
+
+impl Command for ImportWetDoc {
+    fn subcommand() -> clap::App<'static>
+    where
+        Self: Sized,
+    {
+        clap::App::new("import-wet")
+            .about("Import WARC/WET files into OSCAR v2 documents.")
+            .long_about("Import WARC/WET files into OSCAR v2 documents.
+Text records (conversion and resource records) become documents, with the record headers as warc_headers.
+Documents have empty metadata: they are labelled LANG with a probability of 0, have no annotations and no line identifications.
+Files can be gzip-compressed.")
+            .arg(arg!([SOURCE] "WARC/WET file, or folder of WARC/WET files.").required(true))
+            .arg(arg!([DESTINATION] "Destination JSONL file.").required(true))
+            .arg(arg!(-l --lang <LANG> "Language label of the documents.").required(true))
+    }
+
+    fn run(matches: &ArgMatches) -> Result<(), Error>
+    where
+        Self: Sized,
+    {
+        let src: PathBuf = matches
+            .value_of("SOURCE")
+            .expect("Value of 'SOURCE' is required.")
+            .into();
+        let dst: PathBuf = matches
+            .value_of("DESTINATION")
+            .expect("Value of 'DESTINATION' is required.")
+            .into();
+        let lang = matches
+            .value_of("lang")
+            .expect("Value of 'lang' is required.");
+
+        Self::import_wet(&src, &dst, lang)
+    }
+}
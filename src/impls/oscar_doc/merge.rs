@@ -0,0 +1,94 @@
+//! Merging and resharding of OSCAR v2 corpora (see [crate::ops::Merge]).
+use std::path::PathBuf;
+
+use clap::{arg, ArgMatches};
+
+use crate::{
+    cli::Command,
+    error::Error,
+    ops::{Merge, Rotation},
+};
+
+pub(crate) struct MergeDoc;
+impl Merge for MergeDoc {}
+
+impl Command for MergeDoc {
+    fn subcommand() -> clap::App<'static>
+    where
+        Self: Sized,
+    {
+        clap::App::new("merge")
+            .about("Merge split parts back, in part order.")
+            .long_about("Merge split parts back, in part order.
+If SOURCE is a language folder holding parts only (e.g. fr/fr_part_1.jsonl), DESTINATION is the merged file.
+If SOURCE is a corpus folder, DESTINATION is a folder, where each language is merged into <lang>.<ext>.")
+            .arg(arg!([SOURCE] "Language or corpus folder.").required(true))
+            .arg(arg!([DESTINATION] "Destination file/folder.").required(true))
+            .arg(arg!(--decompress "Decompress parts. Otherwise, compressed parts are concatenated as is.").required(false))
+            .arg(arg!(-J --num_threads <NUM_THREADS> "Number of threads to use (iif source is a corpus folder). If 0, take all available").default_value("0").required(false))
+    }
+
+    fn run(matches: &ArgMatches) -> Result<(), Error>
+    where
+        Self: Sized,
+    {
+        let src: PathBuf = matches
+            .value_of("SOURCE")
+            .expect("Value of 'SOURCE' is required.")
+            .into();
+        let dst: PathBuf = matches
+            .value_of("DESTINATION")
+            .expect("Value of 'DESTINATION' is required.")
+            .into();
+        let decompress = matches.is_present("decompress");
+        let num_threads: usize = matches
+            .value_of("num_threads")
+            .unwrap()
+            .parse()
+            .expect("'num_threads' has to be a number.");
+
+        Self::merge(&src, &dst, decompress, num_threads)
+    }
+}
+
+pub(crate) struct ReshardDoc;
+impl Merge for ReshardDoc {}
+
+impl Command for ReshardDoc {
+    fn subcommand() -> clap::App<'static>
+    where
+        Self: Sized,
+    {
+        clap::App::new("reshard")
+            .about("Split parts again to a new size, in one step. Compressed parts are decompressed.")
+            .long_about("Split parts again to a new size, in one step. Compressed parts are decompressed.
+If SOURCE is a language folder holding parts only (e.g. fr/fr_part_1.jsonl), DESTINATION is the base file name of the new parts.
+If SOURCE is a corpus folder, DESTINATION must be an empty folder, where each language is split into <lang>/<lang>_part_N.<ext>.")
+            .arg(arg!([SOURCE] "Language or corpus folder.").required(true))
+            .arg(arg!([DESTINATION] "Destination file/folder.").required(true))
+            .args(Rotation::args(Some("500")))
+            .arg(arg!(-J --num_threads <NUM_THREADS> "Number of threads to use (iif source is a corpus folder). If 0, take all available").default_value("0").required(false))
+    }
+
+    fn run(matches: &ArgMatches) -> Result<(), Error>
+    where
+        Self: Sized,
+    {
+        let src: PathBuf = matches
+            .value_of("SOURCE")
+            .expect("Value of 'SOURCE' is required.")
+            .into();
+        let dst: PathBuf = matches
+            .value_of("DESTINATION")
+            .expect("Value of 'DESTINATION' is required.")
+            .into();
+        let rotation = Rotation::from_matches(matches)?.expect("'size' has a default value.");
+        let num_threads: usize = matches
+            .value_of("num_threads")
+            .unwrap()
+            .parse()
+            .expect("'num_threads' has to be a number.");
+
+        Self::reshard(&src, &dst, rotation, num_threads)
+    }
+}
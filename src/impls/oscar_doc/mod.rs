@@ -1,5 +1,18 @@
 /*! OSCAR v2 (22.01) operation implementations!*/
+mod audit_lang;
 mod compress;
+mod decontaminate;
+mod export;
+mod extract_fields;
 mod filter_tags;
+mod get;
+mod hf;
+mod import_wet;
+mod merge;
 mod oscar_doc;
+mod partition;
+mod sample;
+mod stats;
+mod validate;
+mod webdataset;
 pub(crate) use oscar_doc::*;
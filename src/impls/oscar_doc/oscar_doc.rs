@@ -1,12 +1,28 @@
 //! OSCAR Schema v2 (See [oscar-corpus.com](https://oscar-corpus.com)) operation implementations.
 //!
 //! Implementations mostly use default trait implementations, as the format is simple.
+use crate::impls::oscar_doc::audit_lang::AuditLangDoc;
 use crate::impls::oscar_doc::compress::CompressDoc;
+use crate::impls::oscar_doc::decontaminate::DecontaminateDoc;
+use crate::impls::oscar_doc::export::ExportDoc;
+use crate::impls::oscar_doc::extract_fields::ExtractFieldsDoc;
+use crate::impls::oscar_doc::get::GetDoc;
+use crate::impls::oscar_doc::hf::HfDatasetDoc;
+use crate::impls::oscar_doc::import_wet::ImportWetDoc;
+use crate::impls::oscar_doc::merge::{MergeDoc, ReshardDoc};
+use crate::impls::oscar_doc::partition::PartitionDoc;
+use crate::impls::oscar_doc::sample::{SampleDoc, SampleLanguages};
+use crate::impls::oscar_doc::stats::StatsDoc;
+use crate::impls::oscar_doc::validate::ValidateDoc;
+use crate::impls::oscar_doc::webdataset::WebDatasetDoc;
 use crate::ops::FilterTags;
 use crate::{
     cli::Command,
     error::Error,
-    ops::{Checksum, ExtractText, Split},
+    ops::{
+        is_compressed, language_parts, Checksum, ExtractText, IndexCorpus, KeyIndex, Rotation,
+        Split, TextFormat, DEFAULT_EOS,
+    },
     versions::{Schema, Version},
 };
 use clap::{arg, ArgMatches};
@@ -37,7 +53,23 @@ impl Command for OscarDoc {
             .subcommand(CompressDoc::subcommand())
             .subcommand(ChecksumDoc::subcommand())
             .subcommand(ExtractFromDoc::subcommand())
-            .subcommand(FilterTagDoc::subcommand());
+            .subcommand(FilterTagDoc::subcommand())
+            .subcommand(DecontaminateDoc::subcommand())
+            .subcommand(SampleDoc::subcommand())
+            .subcommand(SampleLanguages::subcommand())
+            .subcommand(IndexDoc::subcommand())
+            .subcommand(GetDoc::subcommand())
+            .subcommand(PartitionDoc::subcommand())
+            .subcommand(MergeDoc::subcommand())
+            .subcommand(ReshardDoc::subcommand())
+            .subcommand(StatsDoc::subcommand())
+            .subcommand(AuditLangDoc::subcommand())
+            .subcommand(ValidateDoc::subcommand())
+            .subcommand(ExportDoc::subcommand())
+            .subcommand(HfDatasetDoc::subcommand())
+            .subcommand(WebDatasetDoc::subcommand())
+            .subcommand(ImportWetDoc::subcommand())
+            .subcommand(ExtractFieldsDoc::subcommand());
 
         subcommand
     }
@@ -51,6 +83,22 @@ impl Command for OscarDoc {
             "checksum" => ChecksumDoc::run(matches),
             "extract-text" => ExtractFromDoc::run(matches),
             "extract-tags" => FilterTagDoc::run(matches),
+            "decontaminate" => DecontaminateDoc::run(matches),
+            "sample" => SampleDoc::run(matches),
+            "sample-languages" => SampleLanguages::run(matches),
+            "index" => IndexDoc::run(matches),
+            "get" => GetDoc::run(matches),
+            "partition" => PartitionDoc::run(matches),
+            "merge" => MergeDoc::run(matches),
+            "reshard" => ReshardDoc::run(matches),
+            "stats" => StatsDoc::run(matches),
+            "audit-lang" => AuditLangDoc::run(matches),
+            "validate" => ValidateDoc::run(matches),
+            "export" => ExportDoc::run(matches),
+            "export-hf" => HfDatasetDoc::run(matches),
+            "export-webdataset" => WebDatasetDoc::run(matches),
+            "import-wet" => ImportWetDoc::run(matches),
+            "extract-fields" => ExtractFieldsDoc::run(matches),
             x => Err(Error::Custom(format!(
                 "{x} op is not supported on this corpus version"
             ))),
@@ -119,13 +167,28 @@ impl Command for ExtractFromDoc {
         Self: Sized,
     {
         clap::App::new("extract-text")
-            .about("Extract text from documents. The output will be a OSCAR v1 (2019)-compatible corpus.")
+            .about("Extract text from documents. By default, the output will be a OSCAR v1 (2019)-compatible corpus.")
             .arg(arg!([SOURCE] "Corpus source file.").required(true))
             .arg(arg!([DESTINATION] "Corpus destination file (OSCAR v1 (2019)-like)").required(true))
             .arg(
                 arg!(--del_src "If set, deletes source files as they are being extracted.")
                     .required(false),
             )
+            .arg(
+                arg!(-f --format <FORMAT> "Output layout: documents separated by a blank line (paragraphs), one document per line with escaped newlines (lines), documents followed by an end of sequence token (eos), or one file per document in the DESTINATION folder (files).")
+                    .possible_values(TextFormat::NAMES)
+                    .default_value("paragraphs")
+                    .required(false),
+            )
+            .arg(
+                arg!(--eos <TOKEN> "End of sequence token of the eos format.")
+                    .default_value(DEFAULT_EOS)
+                    .required(false),
+            )
+            .arg(
+                arg!(--header "If set, precedes each document by a JSON line holding its language and URL.")
+                    .required(false),
+            )
     }
 
     fn run(matches: &ArgMatches) -> Result<(), Error>
@@ -135,7 +198,13 @@ impl Command for ExtractFromDoc {
         let src: PathBuf = matches.value_of("SOURCE").unwrap().into();
         let dst: PathBuf = matches.value_of("DESTINATION").unwrap().into();
         let del_src = matches.is_present("del_src");
-        Self::extract_from_path(&src, &dst, del_src)
+        let format: TextFormat = matches
+            .value_of("format")
+            .expect("'format' has a default value.")
+            .parse()?;
+        let format = format.with_eos(matches.value_of("eos").expect("'eos' has a default value."));
+        let with_header = matches.is_present("header");
+        Self::extract_from_path_as(&src, &dst, del_src, &format, with_header)
     }
 }
 struct ChecksumDoc;
@@ -169,6 +238,56 @@ impl Command for ChecksumDoc {
         Ok(())
     }
 }
+/// Documents are indexed, blank lines are not.
+pub(crate) struct IndexDoc;
+impl IndexCorpus for IndexDoc {
+    const SKIP_BLANK: bool = true;
+}
+impl Command for IndexDoc {
+    fn subcommand() -> clap::App<'static>
+    where
+        Self: Sized,
+    {
+        clap::App::new("index")
+            .about("Write a document offset index next to each (uncompressed) corpus file, used by sample and get for faster runs.")
+            .arg(arg!([SOURCE] "Corpus source file/folder."))
+            .arg(arg!(--keys "Also write a record id and URL index next to each corpus file, used by get.").required(false))
+            .arg(arg!(-J --num_threads <NUM_THREADS> "Number of threads to use (iif source is a folder). If 0, take all available").default_value("0").required(false))
+    }
+
+    fn run(matches: &ArgMatches) -> Result<(), Error>
+    where
+        Self: Sized,
+    {
+        let src: PathBuf = matches
+            .value_of("SOURCE")
+            .expect("Value of 'SOURCE' is required.")
+            .into();
+        let num_threads: usize = matches
+            .value_of("num_threads")
+            .unwrap()
+            .parse()
+            .expect("'num_threads' has to be a number.");
+
+        Self::index(&src, num_threads)?;
+        if matches.is_present("keys") {
+            let files: Vec<PathBuf> = if src.is_file() {
+                vec![src]
+            } else {
+                language_parts(&src)?
+                    .into_iter()
+                    .flat_map(|(_, files)| files)
+                    .collect()
+            };
+            for file in files {
+                if !is_compressed(&file) {
+                    KeyIndex::index_file(&file)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
 /// internal struct for split implementation
 struct SplitDoc;
 /// Use default implementation of splitting (see [crate::ops::Split])
@@ -181,11 +300,13 @@ impl Command for SplitDoc {
         clap::App::new("split")
         .about("File splitting. Supports file and folder splitting.")
         .long_about("if SOURCE is a file, DESTINATION must be a valid file path.
-if SOURCE is a folder, DESTINATION must be an empty folder. Subfolders will be created for each file in SOURCE folder.")
+if SOURCE is a folder, DESTINATION must be an empty folder. Subfolders will be created for each file in SOURCE folder.
+Use --resume to resume an interrupted folder split: files that have already been split are skipped.")
             .arg(arg!([SOURCE] "Corpus source file/folder. If folder, splits corpus files in provided folder"))
             .arg(arg!([DESTINATION] "File/folder to write to."))
-            .arg(arg!(-s --size <SIZE_MB> "Split size (in MBytes)").default_value("500").required(false))
+            .args(Rotation::args(Some("500")))
             .arg(arg!(-J --num_threads <NUM_THREADS> "Number of threads to use (iif source is a folder). If 0, take all available").default_value("0").required(false))
+            .arg(arg!(--resume "If set, skips files split by a previous run into DESTINATION (iif source is a folder).").required(false))
     }
 
     fn run(matches: &ArgMatches) -> Result<(), Error>
@@ -202,13 +323,7 @@ if SOURCE is a folder, DESTINATION must be an empty folder. Subfolders will be c
             .expect("Value of 'DESTINATION' is required.")
             .into();
 
-        // parse size and convert from MBytes into Bytes
-        let size: usize = matches
-            .value_of("size")
-            .unwrap()
-            .parse::<usize>()
-            .expect("'size' has to be a number.")
-            * 1_000_000usize;
+        let rotation = Rotation::from_matches(matches)?.expect("'size' has a default value.");
         let num_threads: usize = matches
             .value_of("num_threads")
             .unwrap()
@@ -216,9 +331,15 @@ if SOURCE is a folder, DESTINATION must be an empty folder. Subfolders will be c
             .expect("'num_threads' has to be a number.");
 
         if src.is_file() {
-            SplitDoc::split_file(&src, &dst, size)?;
+            SplitDoc::split_file(&src, &dst, rotation)?;
         } else if src.is_dir() {
-            SplitDoc::split_all(&src, &dst, size, num_threads)?;
+            SplitDoc::split_all(
+                &src,
+                &dst,
+                rotation,
+                num_threads,
+                matches.is_present("resume"),
+            )?;
         } else {
             return Err(
                 std::io::Error::new(std::io::ErrorKind::NotFound, format!("{:?}", src)).into(),
@@ -276,11 +397,14 @@ impl OscarDoc {
 #[cfg(test)]
 mod tests {
 
-    use super::SplitDoc;
+    use super::{ExtractFromDoc, IndexDoc, SplitDoc};
     use crate::impls::oscar_doc::compress::CompressDoc;
-    use crate::ops::Split;
+    use crate::impls::oscar_doc::filter_tags::FilterTagDoc;
+    use crate::ops::{ExtractText, FilterTags, IndexCorpus, KeyIndex, Rotation, Split};
     use crate::{impls::OscarDoc, ops::Compress};
+    use oscar_io::oscar_doc::{Document, Metadata};
     use std::{
+        collections::{HashMap, HashSet},
         fs::File,
         io::{Read, Write},
     };
@@ -413,7 +537,8 @@ quux
 
         // create destination path and compress
         let tmpdst = tempfile::tempdir().unwrap();
-        CompressDoc::compress_folder(tmpdir.path(), tmpdst.path(), false, "gzip", 1).unwrap();
+        CompressDoc::compress_folder(tmpdir.path(), tmpdst.path(), false, "gzip", 1, false)
+            .unwrap();
 
         println!(
             "{:?}",
@@ -457,7 +582,7 @@ quux
         std::fs::create_dir(&split_folder).unwrap();
 
         let corpus_dst = split_folder.join("corpus-split.jsonl");
-        SplitDoc::split_file(&corpus_orig, &corpus_dst, 1000).unwrap();
+        SplitDoc::split_file(&corpus_orig, &corpus_dst, Rotation::Bytes(1000)).unwrap();
 
         let mut corpus_from_split = String::with_capacity(corpus.len());
 
@@ -475,4 +600,74 @@ quux
 
         assert_eq!(from_split_corpus, from_split_list);
     }
+
+    #[test]
+    fn test_extract_text_indexed() {
+        let corpus = tempdir().unwrap();
+        let fr = corpus.path().join("fr");
+        std::fs::create_dir(&fr).unwrap();
+        let docs: Vec<String> = ["foo\nbar", "baz", "quux"]
+            .iter()
+            .map(|content| {
+                let doc = Document::new(content.to_string(), HashMap::new(), Metadata::default());
+                serde_json::to_string(&doc).unwrap()
+            })
+            .collect();
+        std::fs::write(fr.join("fr_part_1.jsonl"), docs[..2].join("\n") + "\n").unwrap();
+        std::fs::write(fr.join("fr_part_2.jsonl"), docs[2..].join("\n") + "\n").unwrap();
+        IndexDoc::index(corpus.path(), 1).unwrap();
+        for part in ["fr_part_1.jsonl", "fr_part_2.jsonl"] {
+            KeyIndex::index_file(&fr.join(part)).unwrap();
+        }
+
+        let dst = corpus.path().join("fr.txt");
+        ExtractFromDoc::extract_from_path(&fr, &dst, false).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dst).unwrap(),
+            "foo\nbar\n\nbaz\n\nquux\n\n"
+        );
+
+        let dst = corpus.path().join("fr.jsonl");
+        FilterTagDoc::filter_tags(&fr, &dst, false, &HashSet::new(), &HashSet::new()).unwrap();
+        assert_eq!(std::fs::read_to_string(dst).unwrap().lines().count(), 3);
+    }
+
+    #[test]
+    fn test_split_compress_indexed() {
+        let src = tempdir().unwrap();
+        let fr = src.path().join("fr.jsonl");
+        let docs: Vec<String> = ["foo", "bar", "baz"]
+            .iter()
+            .map(|content| {
+                let doc = Document::new(content.to_string(), HashMap::new(), Metadata::default());
+                serde_json::to_string(&doc).unwrap()
+            })
+            .collect();
+        std::fs::write(&fr, docs.join("\n") + "\n").unwrap();
+        IndexDoc::index(src.path(), 1).unwrap();
+        KeyIndex::index_file(&fr).unwrap();
+
+        let split = tempdir().unwrap();
+        SplitDoc::split_all(src.path(), split.path(), Rotation::Docs(2), 1, false).unwrap();
+        let compressed = tempdir().unwrap();
+        CompressDoc::compress_folder(src.path(), compressed.path(), false, "gzip", 1, false)
+            .unwrap();
+
+        let file_names = |dst: &std::path::Path| {
+            let mut names: Vec<String> = walkdir::WalkDir::new(dst)
+                .into_iter()
+                .map(|entry| entry.unwrap())
+                .filter(|entry| entry.file_type().is_file())
+                .map(|entry| entry.file_name().to_string_lossy().to_string())
+                .filter(|name| !name.starts_with('.'))
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(
+            file_names(split.path()),
+            ["fr_part_1.jsonl", "fr_part_2.jsonl"]
+        );
+        assert_eq!(file_names(compressed.path()), ["fr.jsonl.gz"]);
+    }
 }
@@ -0,0 +1,158 @@
+//! Train/validation/test partitioning of OSCAR v2 corpora (see [crate::ops::Partition]).
+use std::{borrow::Cow, io::BufRead};
+
+use clap::{arg, ArgMatches};
+use serde_json::Value;
+
+use crate::{
+    cli::Command,
+    error::Error,
+    ops::{host, partition_args, Partition, PartitionKey},
+};
+
+/// Documents are assigned by URL, record id or URL host.
+/// Documents lacking the key header are assigned by content.
+pub(crate) struct PartitionDoc;
+
+impl Partition for PartitionDoc {
+    const EXTENSION: &'static str = "jsonl";
+
+    fn next_doc<R: BufRead>(r: &mut R, doc: &mut String) -> Result<bool, Error> {
+        loop {
+            doc.clear();
+            if r.read_line(doc)? == 0 {
+                return Ok(false);
+            }
+            if !doc.trim().is_empty() {
+                if !doc.ends_with('\n') {
+                    doc.push('\n');
+                }
+                return Ok(true);
+            }
+        }
+    }
+
+    fn doc_key(doc: &str, key: PartitionKey) -> Result<Cow<'_, str>, Error> {
+        let parsed: Value = serde_json::from_str(doc)?;
+        let headers = &parsed["warc_headers"];
+        let header = match key {
+            PartitionKey::Url | PartitionKey::Domain => headers["warc-target-uri"].as_str(),
+            PartitionKey::RecordId => headers["warc-record-id"].as_str(),
+            PartitionKey::Content => None,
+        };
+        let key = match (key, header) {
+            (PartitionKey::Domain, Some(url)) => host(url),
+            (_, header) => header.map(String::from),
+        };
+        match key {
+            Some(key) => Ok(Cow::Owned(key)),
+            None => match parsed["content"].as_str() {
+                Some(content) => Ok(Cow::Owned(content.to_string())),
+                None => Err(Error::MissingContent(parsed)),
+            },
+        }
+    }
+}
+
+impl Command for PartitionDoc {
+    fn subcommand() -> clap::App<'static>
+    where
+        Self: Sized,
+    {
+        clap::App::new("partition")
+            .about("Assign documents to train/validation/test (or other) splits, by hash of their URL or record id.")
+            .long_about("Assign documents to train/validation/test (or other) splits, by hash of their URL or record id.
+Assignments are stable across runs. Each language is written in DESTINATION/<split>/<lang>.jsonl.
+Documents lacking the hashed header are assigned by content.")
+            .arg(arg!([SOURCE] "Corpus source file/folder.").required(true))
+            .arg(arg!([DESTINATION] "Destination folder.").required(true))
+            .args(PartitionKey::args())
+            .args(partition_args())
+    }
+
+    fn run(matches: &ArgMatches) -> Result<(), Error>
+    where
+        Self: Sized,
+    {
+        Self::partition_from_matches(matches, PartitionKey::from_matches(matches))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use oscar_io::oscar_doc::{Document, Metadata, Writer};
+    use tempfile::tempdir;
+
+    use crate::ops::{host, Partition, PartitionKey, Ratios, Rotation};
+
+    use super::PartitionDoc;
+
+    fn urls(path: &std::path::Path) -> Vec<String> {
+        std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                let doc: serde_json::Value = serde_json::from_str(line).unwrap();
+                doc["warc_headers"]["warc-target-uri"]
+                    .as_str()
+                    .unwrap()
+                    .to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_partition() {
+        let dir = tempdir().unwrap();
+        let src = dir.path().join("fr_meta.jsonl");
+        let docs: Vec<Document> = (0..200)
+            .map(|i| {
+                let headers = HashMap::from([(
+                    "warc-target-uri".to_string(),
+                    format!("https://site{}.com/{i}", i % 20),
+                )]);
+                Document::new(format!("document {i}"), headers, Metadata::default())
+            })
+            .collect();
+        let mut wr = Writer::new(std::fs::File::create(&src).unwrap());
+        wr.write_multiple(&docs).unwrap();
+        wr.flush().unwrap();
+
+        let ratios = Ratios::parse("train=0.5,validation=0.25,test=0.25").unwrap();
+        for key in [PartitionKey::Url, PartitionKey::Domain] {
+            let dst = dir.path().join(format!("{key:?}"));
+            PartitionDoc::partition(&src, &dst, &ratios, key, 0, Some(Rotation::Bytes(1000)), 1)
+                .unwrap();
+
+            let mut domains_per_split = Vec::new();
+            let mut nb_docs = 0;
+            for name in ratios.names() {
+                let split_dir = dst.join(name);
+                let mut domains = HashSet::new();
+                for file in std::fs::read_dir(&split_dir).unwrap() {
+                    let file = file.unwrap().path();
+                    // rotated
+                    assert!(std::fs::metadata(&file).unwrap().len() <= 1000);
+                    for url in urls(&file) {
+                        domains.insert(host(&url).unwrap());
+                        nb_docs += 1;
+                    }
+                }
+                domains_per_split.push(domains);
+            }
+            assert_eq!(nb_docs, 200);
+
+            let leaked = domains_per_split[0]
+                .intersection(&domains_per_split[1])
+                .chain(domains_per_split[0].intersection(&domains_per_split[2]))
+                .chain(domains_per_split[1].intersection(&domains_per_split[2]))
+                .count();
+            match key {
+                PartitionKey::Domain => assert_eq!(leaked, 0),
+                _ => assert!(leaked > 0),
+            }
+        }
+    }
+}
@@ -0,0 +1,559 @@
+/*! Sampling of whole documents (see [crate::ops::SampleText]) !*/
+use std::{
+    fs::File,
+    io::{BufRead, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use clap::{arg, ArgMatches};
+use rand::seq::SliceRandom;
+use serde_json::Value;
+
+use crate::{
+    cli::Command,
+    error::Error,
+    ops::{
+        corpus_files, open_decompressed, open_stream, rng_from_matches, seed_arg, LineIndex,
+        Reservoir, SampleBudget, SampleText, SamplingKind, SamplingRng, StratifiedSample,
+    },
+};
+
+/// Document sampling on OSCAR v2 corpora.
+///
+/// Documents are copied verbatim, so sampled documents keep their metadata.
+/// Since (compressed) corpus files can't be accessed randomly, the corpus is read twice:
+/// once to get document sizes and once to write the sampled documents.
+pub struct SampleDoc;
+
+impl SampleDoc {
+    /// Call `f` on every document (that is, raw JSON line) of `files`, along with its ordinal.
+    fn for_each_doc<F>(files: &[PathBuf], mut f: F) -> Result<(), Error>
+    where
+        F: FnMut(usize, &[u8]) -> Result<(), Error>,
+    {
+        let mut ordinal = 0;
+        let mut line = Vec::new();
+        for file in files {
+            debug!("reading {:?}", file);
+            let mut r = open_decompressed(file)?;
+            loop {
+                line.clear();
+                if r.read_until(b'\n', &mut line)? == 0 {
+                    break;
+                }
+                if line.iter().all(u8::is_ascii_whitespace) {
+                    continue;
+                }
+                f(ordinal, &line)?;
+                ordinal += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Sizes of the documents of `files`, in the unit of `budget` (see [StratifiedSample::doc_size]).
+    fn doc_sizes(files: &[PathBuf], budget: SampleBudget) -> Result<Vec<usize>, Error> {
+        info!("getting document sizes...");
+        let mut sizes = Vec::new();
+        for file in files {
+            // byte and document sizes are in fresh indices
+            if !matches!(budget, SampleBudget::Tokens(_)) {
+                if let Some(index) = LineIndex::load_fresh(file, true)? {
+                    sizes.extend(index.iter().map(|(_, length)| match budget {
+                        SampleBudget::Bytes(_) => length,
+                        _ => 1,
+                    }));
+                    continue;
+                }
+            }
+            Self::for_each_doc(std::slice::from_ref(file), |_, doc| {
+                sizes.push(Self::doc_size(doc, budget)?);
+                Ok(())
+            })?;
+        }
+        Ok(sizes)
+    }
+
+    /// Sample documents of `src` in a single pass (see [Reservoir]).
+    /// `src` can be a file, a folder or `-` (stdin).
+    fn sample_stream(
+        src: &Path,
+        dst: &Path,
+        budget: SampleBudget,
+        weighted: bool,
+        rng: &mut SamplingRng,
+    ) -> Result<(), Error> {
+        if dst.exists() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("{:?}", dst),
+            )
+            .into());
+        }
+        let files = if src == Path::new("-") {
+            vec![src.to_path_buf()]
+        } else {
+            corpus_files(src)?
+        };
+
+        let mut w = BufWriter::new(File::create(dst)?);
+        let mut reservoir = Reservoir::new(budget, weighted);
+        for file in &files {
+            debug!("reading {:?}", file);
+            let mut r = open_stream(file)?;
+            reservoir.read(&mut r, &mut w, rng, |doc| Self::doc_size(doc, budget))?;
+        }
+        let (nb_sampled, nb_read) = reservoir.finish(&mut w)?;
+        info!("sampled {nb_sampled} documents out of {nb_read}");
+        Ok(())
+    }
+
+    /// Sample document ordinals up to `budget`, `sizes` being measured in the budget's unit.
+    /// Documents that don't fit in the budget are never sampled, nor are empty ones with replacement.
+    ///
+    /// Ordinals are sorted, and are repeated if documents have been sampled several times.
+    fn sample_ordinals(
+        sizes: &[usize],
+        budget: SampleBudget,
+        sampling: SamplingKind,
+        rng: &mut SamplingRng,
+    ) -> Result<Vec<usize>, Error> {
+        info!("sampling documents...");
+        let max_size = budget.size();
+        // with replacement, empty documents don't consume the budget, and could be drawn forever
+        let min_size = match sampling {
+            SamplingKind::WithReplacement => 1,
+            SamplingKind::WithoutReplacement => 0,
+        };
+        let mut candidates: Vec<usize> = (0..sizes.len())
+            .filter(|ordinal| (min_size..=max_size).contains(&sizes[*ordinal]))
+            .collect();
+        if candidates.is_empty() {
+            return Err(Error::Custom("no document to sample from".to_string()));
+        }
+
+        let mut size = 0;
+        let mut ordinals = Vec::new();
+        match sampling {
+            SamplingKind::WithReplacement => {
+                // candidates is not empty
+                while let Some(ordinal) = candidates.choose(rng) {
+                    let cost = sizes[*ordinal];
+                    if size + cost > max_size {
+                        break;
+                    }
+                    ordinals.push(*ordinal);
+                    size += cost;
+                }
+            }
+            SamplingKind::WithoutReplacement => {
+                candidates.shuffle(rng);
+                for ordinal in candidates {
+                    let cost = sizes[ordinal];
+                    if size + cost > max_size {
+                        break;
+                    }
+                    ordinals.push(ordinal);
+                    size += cost;
+                }
+            }
+        }
+
+        ordinals.sort_unstable();
+        Ok(ordinals)
+    }
+
+    /// Write the documents of `files` whose ordinals are in `ordinals` (sorted) into `w`.
+    fn write_samples<W: Write>(
+        files: &[PathBuf],
+        ordinals: &[usize],
+        w: &mut W,
+    ) -> Result<(), Error> {
+        info!("reading corpus and writing samples...");
+        let mut ordinals = ordinals.iter().peekable();
+        Self::for_each_doc(files, |ordinal, doc| {
+            while ordinals.next_if_eq(&&ordinal).is_some() {
+                w.write_all(doc)?;
+                if doc.last() != Some(&b'\n') {
+                    w.write_all(b"\n")?;
+                }
+            }
+            Ok(())
+        })?;
+        w.flush()?;
+        Ok(())
+    }
+}
+
+impl SampleText for SampleDoc {
+    fn sample(
+        src: &Path,
+        dst: &Path,
+        budget: SampleBudget,
+        sampling: SamplingKind,
+        rng: &mut SamplingRng,
+    ) -> Result<(), Error> {
+        if dst.exists() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("{:?}", dst),
+            )
+            .into());
+        }
+        let files = corpus_files(src)?;
+        if files.is_empty() {
+            return Err(Error::Custom(format!("No files found in {:?}", src)));
+        }
+
+        let sizes = Self::doc_sizes(&files, budget)?;
+        let budget = budget.resolve(sizes.len());
+        let ordinals = Self::sample_ordinals(&sizes, budget, sampling, rng)?;
+        info!(
+            "sampled {} documents out of {}",
+            ordinals.len(),
+            sizes.len()
+        );
+
+        let mut w = BufWriter::new(File::create(dst)?);
+        Self::write_samples(&files, &ordinals, &mut w)
+    }
+}
+
+impl StratifiedSample for SampleDoc {
+    const EXTENSION: &'static str = "jsonl";
+
+    /// Bytes are those of the whole JSON document, while tokens are those of its content.
+    fn doc_size(doc: &[u8], budget: SampleBudget) -> Result<usize, Error> {
+        let size = match budget {
+            SampleBudget::Bytes(_) => doc.len(),
+            SampleBudget::Tokens(_) => {
+                let doc: Value = serde_json::from_slice(doc)?;
+                match doc["content"].as_str() {
+                    Some(content) => budget.measure(content),
+                    None => return Err(Error::MissingContent(doc)),
+                }
+            }
+            SampleBudget::Docs(_) | SampleBudget::Fraction(_) => 1,
+        };
+        Ok(size)
+    }
+}
+
+impl Command for SampleDoc {
+    fn subcommand() -> clap::App<'static>
+    where
+        Self: Sized,
+    {
+        clap::App::new("sample")
+            .about("Sample documents from a corpus file or a folder of (compressed) corpus parts, up to a size in bytes, documents, tokens or corpus fraction.")
+            .arg(arg!([SOURCE] "Corpus source file/folder. If folder, reads all corpus files in provided folder, in part order. Use '-' for stdin (requires --reservoir).").required(true).requires_if("-", "reservoir"))
+            .arg(arg!([DESTINATION] "Corpus destination file. Should not exist.").required(true))
+            .args(SampleBudget::args())
+            .group(SampleBudget::group())
+            .arg(SamplingKind::arg().conflicts_with("reservoir"))
+            .args(Reservoir::args())
+            .arg(seed_arg())
+    }
+
+    fn run(matches: &ArgMatches) -> Result<(), Error>
+    where
+        Self: Sized,
+    {
+        let src: PathBuf = matches.value_of("SOURCE").unwrap().into();
+        let dst: PathBuf = matches.value_of("DESTINATION").unwrap().into();
+        let budget = SampleBudget::from_matches(matches)?;
+        let sampling = SamplingKind::from_matches(matches);
+        let mut rng = rng_from_matches(matches)?;
+
+        if matches.is_present("reservoir") {
+            let weighted = matches.is_present("weighted");
+            Self::sample_stream(&src, &dst, budget, weighted, &mut rng)
+        } else {
+            Self::sample(&src, &dst, budget, sampling, &mut rng)
+        }
+    }
+}
+
+/// Stratified multilingual sampling (see [StratifiedSample]).
+pub struct SampleLanguages;
+
+impl Command for SampleLanguages {
+    fn subcommand() -> clap::App<'static>
+    where
+        Self: Sized,
+    {
+        SampleDoc::sample_languages_subcommand()
+    }
+
+    fn run(matches: &ArgMatches) -> Result<(), Error>
+    where
+        Self: Sized,
+    {
+        SampleDoc::sample_languages_from_matches(matches)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, fs::File, io::Cursor, path::Path};
+
+    use flate2::{write::GzEncoder, Compression};
+    use oscar_io::oscar_doc::{Document, Metadata, Reader, Writer};
+    use tempfile::tempdir;
+
+    use crate::ops::{
+        sampling_rng, Mixing, SampleBudget, SampleText, SamplingKind, StratifiedSample,
+        MANIFEST_FILE,
+    };
+
+    use super::SampleDoc;
+    use crate::impls::oscar_doc::oscar_doc::IndexDoc;
+    use crate::ops::IndexCorpus;
+
+    /// Write 10 documents in two parts, the second one being gzipped.
+    fn write_corpus(dir: &Path) {
+        let docs: Vec<Document> = (0..10)
+            .map(|i| {
+                let mut metadata = Metadata::default();
+                metadata.add_annotation(format!("annotation {i}"));
+                Document::new(format!("document {i}"), HashMap::new(), metadata)
+            })
+            .collect();
+
+        let mut wr = Writer::new(File::create(dir.join("fr_meta_part_1.jsonl")).unwrap());
+        wr.write_multiple(&docs[..5]).unwrap();
+        wr.flush().unwrap();
+
+        let f = File::create(dir.join("fr_meta_part_2.jsonl.gz")).unwrap();
+        let mut wr = Writer::new(GzEncoder::new(f, Compression::default()));
+        wr.write_multiple(&docs[5..]).unwrap();
+        wr.flush().unwrap();
+    }
+
+    fn sample(
+        src: &Path,
+        budget: SampleBudget,
+        sampling: SamplingKind,
+        seed: u64,
+    ) -> (String, Vec<Document>) {
+        let dst_dir = tempdir().unwrap();
+        let dst = dst_dir.path().join("sample.jsonl");
+        SampleDoc::sample(src, &dst, budget, sampling, &mut sampling_rng(seed)).unwrap();
+
+        let sample = std::fs::read_to_string(&dst).unwrap();
+        let docs = Reader::new(Cursor::new(sample.clone()))
+            .map(|doc| doc.unwrap())
+            .collect();
+        (sample, docs)
+    }
+
+    #[test]
+    fn test_doc_sizes() {
+        let src = tempdir().unwrap();
+        write_corpus(src.path());
+        let files = crate::ops::corpus_files(src.path()).unwrap();
+        let sizes = SampleDoc::doc_sizes(&files, SampleBudget::Bytes(0)).unwrap();
+        assert_eq!(sizes.len(), 10);
+
+        // the gzipped part can't be indexed
+        assert!(IndexDoc::index(src.path(), 1).is_ok());
+        assert!(crate::ops::LineIndex::path(&files[0]).is_file());
+        assert_eq!(
+            SampleDoc::doc_sizes(&files, SampleBudget::Bytes(0)).unwrap(),
+            sizes
+        );
+        let sizes = SampleDoc::doc_sizes(&files, SampleBudget::Tokens(0)).unwrap();
+        assert_eq!(sizes, [2; 10]);
+    }
+
+    #[test]
+    fn test_sample_docs() {
+        let src = tempdir().unwrap();
+        write_corpus(src.path());
+
+        let (_, docs) = sample(
+            src.path(),
+            SampleBudget::Docs(4),
+            SamplingKind::WithoutReplacement,
+            0,
+        );
+        assert_eq!(docs.len(), 4);
+        let mut contents: Vec<&String> = docs.iter().map(|doc| doc.content()).collect();
+        contents.dedup();
+        assert_eq!(contents.len(), 4);
+
+        // metadata is kept
+        for doc in &docs {
+            let i = doc.content().strip_prefix("document ").unwrap();
+            assert_eq!(
+                doc.metadata().annotation(),
+                Some(&vec![format!("annotation {i}")])
+            );
+        }
+
+        // asking for more documents than there are
+        let (_, docs) = sample(
+            src.path(),
+            SampleBudget::Docs(20),
+            SamplingKind::WithoutReplacement,
+            0,
+        );
+        assert_eq!(docs.len(), 10);
+    }
+
+    #[test]
+    fn test_sample_replacement() {
+        let src = tempdir().unwrap();
+        write_corpus(src.path());
+
+        let (_, docs) = sample(
+            src.path(),
+            SampleBudget::Docs(20),
+            SamplingKind::WithReplacement,
+            0,
+        );
+        assert_eq!(docs.len(), 20);
+    }
+
+    #[test]
+    fn test_sample_replacement_empty() {
+        let src = tempdir().unwrap();
+        let docs: Vec<Document> = ["", " ", "\n"]
+            .iter()
+            .map(|content| Document::new(content.to_string(), HashMap::new(), Metadata::default()))
+            .collect();
+        let mut wr = Writer::new(File::create(src.path().join("fr.jsonl")).unwrap());
+        wr.write_multiple(&docs).unwrap();
+        wr.flush().unwrap();
+
+        let dst_dir = tempdir().unwrap();
+        for budget in [
+            SampleBudget::Tokens(10),
+            SampleBudget::Tokens(0),
+            SampleBudget::Docs(0),
+        ] {
+            let dst = dst_dir.path().join("sample.jsonl");
+            assert!(SampleDoc::sample(
+                src.path(),
+                &dst,
+                budget,
+                SamplingKind::WithReplacement,
+                &mut sampling_rng(0),
+            )
+            .is_err());
+            let _ = std::fs::remove_file(dst);
+        }
+    }
+
+    #[test]
+    fn test_sample_bytes() {
+        let src = tempdir().unwrap();
+        write_corpus(src.path());
+
+        for sampling in [
+            SamplingKind::WithReplacement,
+            SamplingKind::WithoutReplacement,
+        ] {
+            let (sample, docs) = sample(src.path(), SampleBudget::Bytes(500), sampling, 0);
+            assert!(!docs.is_empty());
+            assert!(sample.len() <= 500);
+        }
+    }
+
+    #[test]
+    fn test_sample_tokens_fraction() {
+        let src = tempdir().unwrap();
+        write_corpus(src.path());
+
+        // documents have 2 tokens each
+        let (_, docs) = sample(
+            src.path(),
+            SampleBudget::Tokens(7),
+            SamplingKind::WithoutReplacement,
+            0,
+        );
+        assert_eq!(docs.len(), 3);
+
+        let (_, docs) = sample(
+            src.path(),
+            SampleBudget::Fraction(0.5),
+            SamplingKind::WithoutReplacement,
+            0,
+        );
+        assert_eq!(docs.len(), 5);
+    }
+
+    #[test]
+    fn test_sample_stream() {
+        let src = tempdir().unwrap();
+        write_corpus(src.path());
+        let dst_dir = tempdir().unwrap();
+        let dst = dst_dir.path().join("sample.jsonl");
+
+        SampleDoc::sample_stream(
+            src.path(),
+            &dst,
+            SampleBudget::Tokens(8),
+            false,
+            &mut sampling_rng(0),
+        )
+        .unwrap();
+        let docs: Vec<Document> = Reader::new(Cursor::new(std::fs::read_to_string(&dst).unwrap()))
+            .map(|doc| doc.unwrap())
+            .collect();
+        assert_eq!(docs.len(), 4);
+        assert!(docs.iter().all(|doc| doc.metadata().annotation().is_some()));
+    }
+
+    #[test]
+    fn test_sample_seeded() {
+        let src = tempdir().unwrap();
+        write_corpus(src.path());
+
+        for sampling in [
+            SamplingKind::WithReplacement,
+            SamplingKind::WithoutReplacement,
+        ] {
+            let (a, _) = sample(src.path(), SampleBudget::Docs(5), sampling, 42);
+            let (b, _) = sample(src.path(), SampleBudget::Docs(5), sampling, 42);
+            assert_eq!(a, b);
+        }
+    }
+
+    #[test]
+    fn test_sample_languages() {
+        let src = tempdir().unwrap();
+        let fr = src.path().join("fr");
+        std::fs::create_dir(&fr).unwrap();
+        write_corpus(&fr);
+        let docs: Vec<Document> = (0..100)
+            .map(|i| Document::new(format!("doc {i}"), HashMap::new(), Metadata::default()))
+            .collect();
+        let mut wr = Writer::new(File::create(src.path().join("en_meta.jsonl")).unwrap());
+        wr.write_multiple(&docs).unwrap();
+        wr.flush().unwrap();
+
+        let dst = tempdir().unwrap();
+        SampleDoc::sample_languages(
+            src.path(),
+            dst.path(),
+            SampleBudget::Docs(20),
+            &Mixing::Temperature(0.0),
+            SamplingKind::WithoutReplacement,
+            &mut sampling_rng(0),
+        )
+        .unwrap();
+
+        // uniform mixing: 10 documents each
+        for lang in ["en_meta", "fr"] {
+            let sample = std::fs::read_to_string(dst.path().join(format!("{lang}.jsonl"))).unwrap();
+            assert_eq!(sample.lines().count(), 10);
+        }
+        let manifest: serde_json::Value =
+            serde_json::from_reader(File::open(dst.path().join(MANIFEST_FILE)).unwrap()).unwrap();
+        assert_eq!(manifest["sampled"], 20);
+        assert_eq!(manifest["languages"][1]["lang"], "fr");
+        assert_eq!(manifest["languages"][1]["available"], 10);
+        assert_eq!(manifest["languages"][1]["proportion"], 0.5);
+    }
+}
@@ -0,0 +1,52 @@
+//! Statistics of OSCAR v2 corpora (see [crate::ops::Stats]).
+use std::path::PathBuf;
+
+use clap::{arg, ArgMatches};
+
+use crate::{cli::Command, error::Error, ops::Stats};
+
+pub(crate) struct StatsDoc;
+impl Stats for StatsDoc {}
+
+impl Command for StatsDoc {
+    fn subcommand() -> clap::App<'static>
+    where
+        Self: Sized,
+    {
+        clap::App::new("stats")
+            .about("Compute per-language and overall statistics, written as JSON and Markdown reports.")
+            .long_about("Compute per-language and overall statistics, written as JSON and Markdown reports.
+Reports hold document, line, word and byte counts, annotation frequencies, an identification probability histogram and top domains.
+Each language gets <lang>.json and <lang>.md in DESTINATION, and the whole corpus gets stats.json and stats.md.")
+            .arg(arg!([SOURCE] "Corpus source file/folder.").required(true))
+            .arg(arg!([DESTINATION] "Report folder.").required(true))
+            .arg(arg!(--top <NB_DOMAINS> "Number of top domains to report.").default_value("50").required(false))
+            .arg(arg!(-J --num_threads <NUM_THREADS> "Number of threads to use (iif source is a folder). If 0, take all available").default_value("0").required(false))
+    }
+
+    fn run(matches: &ArgMatches) -> Result<(), Error>
+    where
+        Self: Sized,
+    {
+        let src: PathBuf = matches
+            .value_of("SOURCE")
+            .expect("Value of 'SOURCE' is required.")
+            .into();
+        let dst: PathBuf = matches
+            .value_of("DESTINATION")
+            .expect("Value of 'DESTINATION' is required.")
+            .into();
+        let top: usize = matches
+            .value_of("top")
+            .unwrap()
+            .parse()
+            .expect("'top' has to be a number.");
+        let num_threads: usize = matches
+            .value_of("num_threads")
+            .unwrap()
+            .parse()
+            .expect("'num_threads' has to be a number.");
+
+        Self::stats(&src, &dst, top, num_threads)
+    }
+}
@@ -0,0 +1,50 @@
+//! Schema validation of OSCAR v2 corpora (see [crate::ops::Validate]).
+use std::{fs::File, io::BufWriter, path::PathBuf};
+
+use clap::{arg, ArgMatches};
+
+use crate::{cli::Command, error::Error, ops::Validate};
+
+pub(crate) struct ValidateDoc;
+impl Validate for ValidateDoc {}
+
+impl Command for ValidateDoc {
+    fn subcommand() -> clap::App<'static>
+    where
+        Self: Sized,
+    {
+        clap::App::new("validate")
+            .about("Check that every document follows the OSCAR v2 schema, reporting violations as file:line: message.")
+            .long_about("Check that every document follows the OSCAR v2 schema, reporting violations as file:line: message.
+Documents must be JSON objects with a content, string WARC headers and metadata.
+Labels must be known language codes, probabilities must be in [0, 1],
+there must be one sentence identification per content line, and content-length must match the content.
+Exits with an error if any violation is found.")
+            .arg(arg!([SOURCE] "Corpus source file/folder.").required(true))
+            .arg(arg!(-o --output <OUTPUT> "File to write violations to. Defaults to stdout.").required(false))
+            .arg(arg!(-J --num_threads <NUM_THREADS> "Number of threads to use (iif source is a folder). If 0, take all available").default_value("0").required(false))
+    }
+
+    fn run(matches: &ArgMatches) -> Result<(), Error>
+    where
+        Self: Sized,
+    {
+        let src: PathBuf = matches
+            .value_of("SOURCE")
+            .expect("Value of 'SOURCE' is required.")
+            .into();
+        let num_threads: usize = matches
+            .value_of("num_threads")
+            .unwrap()
+            .parse()
+            .expect("'num_threads' has to be a number.");
+
+        match matches.value_of("output") {
+            Some(dst) => {
+                let mut w = BufWriter::new(File::create(dst)?);
+                Self::validate(&src, &mut w, num_threads)
+            }
+            None => Self::validate(&src, &mut std::io::stdout().lock(), num_threads),
+        }
+    }
+}
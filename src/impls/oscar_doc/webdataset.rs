@@ -0,0 +1,121 @@
+//! WebDataset export of OSCAR v2 corpora (see [crate::ops::WebDataset]).
+use std::io::BufRead;
+
+use serde_json::Value;
+
+use crate::{
+    cli::Command,
+    error::Error,
+    ops::{webdataset_args, WebDataset},
+};
+
+/// The metadata of a document is the document itself, without its content.
+pub(crate) struct WebDatasetDoc;
+
+impl WebDataset for WebDatasetDoc {
+    fn next_sample<R: BufRead>(r: &mut R, _lang: &str) -> Result<Option<(String, Value)>, Error> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if r.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            if !line.trim().is_empty() {
+                break;
+            }
+        }
+        let mut doc: Value = serde_json::from_str(&line)?;
+        let content = match doc.as_object_mut().and_then(|doc| doc.remove("content")) {
+            Some(Value::String(content)) => content,
+            Some(_) => return Err(Error::MalformedContent(doc)),
+            None => return Err(Error::MissingContent(doc)),
+        };
+        Ok(Some((content, doc)))
+    }
+}
+
+impl Command for WebDatasetDoc {
+    fn subcommand() -> clap::App<'static>
+    where
+        Self: Sized,
+    {
+        clap::App::new("export-webdataset")
+            .about("Pack documents into WebDataset-style tar shards.")
+            .long_about("Pack documents into WebDataset-style tar shards.
+Each language is written into DESTINATION/<lang>/<lang>-000000.tar, <lang>-000001.tar...
+Each document is a sample made of a .txt member (its content) and a .json member (its warc_headers and metadata).
+Each language folder also holds a shardindex.json file, listing shard sizes and number of samples.")
+            .args(webdataset_args())
+    }
+
+    fn run(matches: &clap::ArgMatches) -> Result<(), Error>
+    where
+        Self: Sized,
+    {
+        Self::webdataset_from_matches(matches)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, io::Read};
+
+    use serde_json::{json, Value};
+    use tar::Archive;
+    use tempfile::tempdir;
+
+    use crate::ops::{Rotation, WebDataset, SHARD_INDEX};
+
+    use super::WebDatasetDoc;
+
+    #[test]
+    fn test_webdataset() {
+        let dir = tempdir().unwrap();
+        let src = dir.path().join("src");
+        std::fs::create_dir_all(src.join("fr")).unwrap();
+        let docs: Vec<String> = (0..3)
+            .map(|i| {
+                json!({
+                    "content": format!("doc {i}"),
+                    "warc_headers": {"warc-record-id": format!("<urn:uuid:{i}>")},
+                    "metadata": {"identification": {"label": "fr", "prob": 0.5}},
+                })
+                .to_string()
+            })
+            .collect();
+        std::fs::write(src.join("fr/fr_part_1.jsonl"), docs[..2].join("\n")).unwrap();
+        std::fs::write(src.join("fr/fr_part_2.jsonl"), docs[2].clone() + "\n").unwrap();
+
+        let dst = dir.path().join("dst");
+        WebDatasetDoc::webdataset(&src, &dst, Rotation::Docs(2), 1).unwrap();
+
+        let mut archive = Archive::new(File::open(dst.join("fr/fr-000001.tar")).unwrap());
+        let members: Vec<(String, String)> = archive
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let mut entry = entry.unwrap();
+                let name = entry.path().unwrap().to_string_lossy().into_owned();
+                let mut content = String::new();
+                entry.read_to_string(&mut content).unwrap();
+                (name, content)
+            })
+            .collect();
+        assert_eq!(
+            members[0],
+            ("fr-000000002.txt".to_string(), "doc 2".to_string())
+        );
+        let metadata: Value = serde_json::from_str(&members[1].1).unwrap();
+        assert_eq!(members[1].0, "fr-000000002.json");
+        assert_eq!(metadata["warc_headers"]["warc-record-id"], "<urn:uuid:2>");
+        assert!(metadata.get("content").is_none());
+
+        let index: Value = serde_json::from_str(
+            &std::fs::read_to_string(dst.join("fr").join(SHARD_INDEX)).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(index["shardlist"][0]["url"], "fr-000000.tar");
+        assert_eq!(index["shardlist"][0]["nsamples"], 2);
+        assert_eq!(index["shardlist"][1]["nsamples"], 1);
+    }
+}
@@ -0,0 +1,161 @@
+/*! Removal of lines matching a reference set (see [crate::ops::Decontaminate]) !*/
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use clap::arg;
+
+use crate::{
+    cli::Command,
+    error::Error,
+    ops::{Decontaminate, Normalization, ReferenceSet, TextUnit},
+};
+
+pub struct DecontaminateTxt;
+
+impl DecontaminateTxt {
+    /// Write lines from the reader that don't match `references` into the writer.
+    /// Blank lines (document boundaries) are always kept.
+    fn decontaminate_lines<R, W>(
+        r: &mut R,
+        w: &mut W,
+        references: &mut ReferenceSet,
+    ) -> Result<(), Error>
+    where
+        R: BufRead,
+        W: Write,
+    {
+        for line in r.lines() {
+            let line = line?;
+            if line.trim().is_empty() || !references.check(&line) {
+                w.write_all(line.as_bytes())?;
+                w.write_all(b"\n")?;
+            }
+        }
+        w.flush()?;
+        Ok(())
+    }
+}
+
+impl Decontaminate for DecontaminateTxt {
+    fn decontaminate(
+        src: &Path,
+        dst: &Path,
+        references: &mut ReferenceSet,
+        flag: bool,
+    ) -> Result<(), Error> {
+        if flag {
+            return Err(Error::Custom(
+                "Flagging is not supported on OSCAR v1 corpora, since they have no metadata"
+                    .to_string(),
+            ));
+        }
+        let mut r = BufReader::new(File::open(src)?);
+        let mut w = BufWriter::new(File::create(dst)?);
+        Self::decontaminate_lines(&mut r, &mut w, references)
+    }
+}
+
+impl Command for DecontaminateTxt {
+    fn subcommand() -> clap::App<'static>
+    where
+        Self: Sized,
+    {
+        clap::App::new("decontaminate")
+            .about("Remove lines matching reference (benchmark) items, by line or by word n-gram.")
+            .arg(arg!([SOURCE] "Corpus source file.").required(true))
+            .arg(arg!([DESTINATION] "Corpus destination file.").required(true))
+            .arg(arg!(-r --references <FILES> "Comma separated reference files (can be repeated).").use_value_delimiter(true).require_value_delimiter(true).multiple_occurrences(true).required(true))
+            .arg(arg!(--unit <UNIT> "Reference items are lines (line) or blank-line separated blocks (doc).").default_value("line").required(false))
+            .arg(arg!(--ngram <N> "Also match word n-grams of size N.").required(false))
+            .arg(arg!(--report <REPORT> "Path of the JSON report of hit reference items.").required(false))
+            .args(Normalization::args())
+    }
+
+    fn run(matches: &clap::ArgMatches) -> Result<(), Error>
+    where
+        Self: Sized,
+    {
+        let src: PathBuf = matches.value_of("SOURCE").unwrap().into();
+        let dst: PathBuf = matches.value_of("DESTINATION").unwrap().into();
+        let reference_paths: Vec<PathBuf> = matches
+            .values_of("references")
+            .unwrap()
+            .map(PathBuf::from)
+            .collect();
+        let unit: TextUnit = matches.value_of("unit").unwrap().parse()?;
+        let ngram_size: Option<usize> = matches
+            .value_of("ngram")
+            .map(|n| n.parse().expect("'ngram' has to be a number."));
+
+        let normalization = Normalization::from_matches(matches);
+
+        let mut references =
+            ReferenceSet::from_paths(&reference_paths, unit, ngram_size, normalization)?;
+        Self::decontaminate(&src, &dst, &mut references, false)?;
+
+        let (checked, matched) = references.stats();
+        info!("removed {matched} lines out of {checked}");
+        if let Some(report) = matches.value_of("report") {
+            let mut w = BufWriter::new(File::create(report)?);
+            references.write_report(&mut w)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::{
+        cli::Command,
+        ops::{Normalization, ReferenceSet},
+    };
+
+    use super::DecontaminateTxt;
+
+    #[test]
+    fn test_decontaminate_lines() {
+        let data = "foo
+Who wrote Hamlet?
+bar
+
+baz
+in short Paris, France is the answer";
+
+        let mut references = ReferenceSet::new(Some(3), Normalization::default());
+        references.add_item("bench.txt", 1, "Who wrote Hamlet?");
+        references.add_item("bench.txt", 2, "Paris, France is the capital");
+
+        let mut dst = Vec::new();
+        DecontaminateTxt::decontaminate_lines(&mut Cursor::new(data), &mut dst, &mut references)
+            .unwrap();
+
+        assert_eq!(String::from_utf8_lossy(&dst), "foo\nbar\n\nbaz\n");
+        assert_eq!(references.stats(), (5, 2));
+    }
+
+    #[test]
+    fn test_references_before_positionals() {
+        let matches = DecontaminateTxt::subcommand()
+            .try_get_matches_from([
+                "decontaminate",
+                "-r",
+                "a.txt,b.txt",
+                "-r",
+                "c.txt",
+                "in",
+                "out",
+            ])
+            .unwrap();
+        assert_eq!(
+            matches.values_of("references").unwrap().collect::<Vec<_>>(),
+            ["a.txt", "b.txt", "c.txt"]
+        );
+        assert_eq!(matches.value_of("SOURCE"), Some("in"));
+        assert_eq!(matches.value_of("DESTINATION"), Some("out"));
+    }
+}
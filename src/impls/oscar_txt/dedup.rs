@@ -1,50 +1,242 @@
-/*! Line hash based (exact) deduplication !*/
+/*! Line (or blank-line separated block) hash based (exact) deduplication !*/
 use std::{
     fs::{File, OpenOptions},
     io::{BufRead, BufReader, BufWriter, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
 use clap::arg;
-use runiq::filters::{DigestFilter, Filter};
+use runiq::filters::Filter;
 
-use crate::{cli::Command, error::Error, ops::Dedup};
+use crate::{
+    cli::Command,
+    error::Error,
+    ops::{
+        for_each_language, language_parts, Dedup, DedupScope, DigestSet, Normalization, TextUnit,
+    },
+};
 
-// #[derive(Default)]
+#[derive(Default, Clone)]
 pub struct DedupTxt {
-    filter: Box<dyn Filter>,
+    filter: DigestSet,
+    unit: TextUnit,
+    normalization: Normalization,
 }
 
 impl DedupTxt {
-    /// TODO: provide a way to specify filter?
-    fn new(filter: Box<dyn Filter>) -> Self {
-        Self { filter }
+    /// Create a deduplicator with an empty filter, deduplicating lines or blocks depending on `unit`.
+    /// Text is normalized with `normalization` before hashing, but written as is.
+    fn new(unit: TextUnit, normalization: Normalization) -> Self {
+        Self {
+            filter: DigestSet::default(),
+            unit,
+            normalization,
+        }
+    }
+
+    /// Check if `text` has never been seen, after normalization.
+    #[inline]
+    fn detect(&mut self, text: &str) -> bool {
+        let normalized = self.normalization.normalize(text);
+        self.filter.detect(normalized.as_bytes())
+    }
+
+    /// Load the filter state saved at `state`, replacing the current one.
+    /// If there is no state yet, the current filter is kept.
+    fn load_state(&mut self, state: &Path) -> Result<(), Error> {
+        if state.exists() {
+            info!("loading dedup state from {:?}", state);
+            let mut r = BufReader::new(File::open(state)?);
+            self.filter = DigestSet::load(&mut r, self.normalization)?;
+        } else {
+            info!("{:?} does not exist, starting from an empty state", state);
+        }
+        Ok(())
+    }
+
+    /// Save the filter state at `state`, so that it can be reused by [DedupTxt::load_state].
+    fn save_state(&self, state: &Path) -> Result<(), Error> {
+        info!(
+            "saving dedup state ({} digests) to {:?}",
+            self.filter.len(),
+            state
+        );
+        let mut w = BufWriter::new(File::create(state)?);
+        self.filter.save(&mut w, self.normalization)
     }
 
     /// get the input from the reader, deduplicate it and send it to the writer.
     /// Stops at the end of stream
     /// Use a [BufWriter] to have better performance.
+    ///
+    /// Blank lines are document boundaries and are kept,
+    /// except around documents that end up empty (only made of duplicates).
     fn dedup<R, W>(&mut self, r: &mut R, w: &mut W) -> Result<(), Error>
     where
         R: BufRead,
         W: Write,
     {
+        match self.unit {
+            TextUnit::Line => self.dedup_lines(r, w)?,
+            TextUnit::Doc => self.dedup_docs(r, w)?,
+        }
+
+        w.flush()?;
+        Ok(())
+    }
+
+    /// Line-level deduplication.
+    fn dedup_lines<R, W>(&mut self, r: &mut R, w: &mut W) -> Result<(), Error>
+    where
+        R: BufRead,
+        W: Write,
+    {
+        // separators are written lazily, before the first kept line of the next document,
+        // so that empty documents don't leave consecutive separators behind.
+        let mut doc_written = false;
+        let mut pending_separator = false;
         for line in r.lines() {
             let line = line?;
             let line_bytes = line.as_bytes();
             // check if line is a newline between documents
-            if line == "\n" {
-                w.write_all(b"\n")?;
-            } else if self.filter.detect(line_bytes) {
+            if line.trim().is_empty() {
+                if doc_written {
+                    pending_separator = true;
+                    doc_written = false;
+                }
+            } else if self.detect(&line) {
                 // write iif line is detected by filter as a unique, never seen line
+                if pending_separator {
+                    w.write_all(b"\n")?;
+                    pending_separator = false;
+                }
                 w.write_all(line_bytes)?;
                 w.write_all(b"\n")?;
+                doc_written = true;
             }
         }
+        Ok(())
+    }
 
-        w.flush()?;
+    /// Block-level deduplication: blank-line separated blocks are kept or discarded as a whole.
+    fn dedup_docs<R, W>(&mut self, r: &mut R, w: &mut W) -> Result<(), Error>
+    where
+        R: BufRead,
+        W: Write,
+    {
+        let mut doc = String::new();
+        let mut doc_written = false;
+        for line in r.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                self.write_doc(&mut doc, &mut doc_written, w)?;
+            } else {
+                doc.push_str(&line);
+                doc.push('\n');
+            }
+        }
+        self.write_doc(&mut doc, &mut doc_written, w)
+    }
+
+    /// Write `doc` if it has never been seen (preceded by a separator if a document has already been written),
+    /// then clear it.
+    fn write_doc<W: Write>(
+        &mut self,
+        doc: &mut String,
+        doc_written: &mut bool,
+        w: &mut W,
+    ) -> Result<(), Error> {
+        if !doc.is_empty() && self.detect(doc) {
+            if *doc_written {
+                w.write_all(b"\n")?;
+            }
+            w.write_all(doc.as_bytes())?;
+            *doc_written = true;
+        }
+        doc.clear();
         Ok(())
     }
+
+    /// Deduplicate files in order, using the same filter.
+    /// Destination files are written in `dst`, keeping their path relative to `src`.
+    fn dedup_files(&mut self, files: &[PathBuf], src: &Path, dst: &Path) -> Result<(), Error> {
+        for file in files {
+            let dst_file = dst.join(file.strip_prefix(src).map_err(Error::StripPrefixError)?);
+            if dst_file.exists() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::AlreadyExists,
+                    format!("{:?}", dst_file),
+                )
+                .into());
+            }
+            if let Some(parent) = dst_file.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            info!("deduplicating {:?} into {:?}", file, dst_file);
+            <DedupTxt as Dedup>::dedup(self, file, &dst_file)?;
+        }
+        Ok(())
+    }
+
+    /// Deduplicate a corpus folder (see [language_parts] for the expected layout) into `dst`, keeping its layout.
+    ///
+    /// With [DedupScope::Lang], each language has its own filter, shared by all of its parts,
+    /// and languages are deduplicated in parallel.
+    /// With [DedupScope::Global], a single filter is shared by all languages, that are deduplicated sequentially.
+    ///
+    /// Filters are cloned from `self` (usually empty).
+    /// If `state` is provided, filters are loaded from it (if it exists) and saved back when done.
+    /// `state` is a folder holding a `<lang>.digests` file per language with [DedupScope::Lang],
+    /// and a single file with [DedupScope::Global].
+    fn dedup_folder(
+        &self,
+        src: &Path,
+        dst: &Path,
+        scope: DedupScope,
+        state: Option<&Path>,
+        num_threads: usize,
+    ) -> Result<(), Error> {
+        let languages = language_parts(src)?;
+        debug!("got languages: {:?}", languages);
+
+        match scope {
+            DedupScope::Global => {
+                let mut dedup = self.clone();
+                if let Some(state) = state {
+                    dedup.load_state(state)?;
+                }
+                for (lang, files) in &languages {
+                    info!("deduplicating {lang}");
+                    dedup.dedup_files(files, src, dst)?;
+                }
+                if let Some(state) = state {
+                    dedup.save_state(state)?;
+                }
+                Ok(())
+            }
+            DedupScope::Lang => {
+                if let Some(state) = state {
+                    std::fs::create_dir_all(state)?;
+                }
+
+                for_each_language(&languages, num_threads, "deduplicating", |lang, files| {
+                    let lang_state = state.map(|s| s.join(format!("{lang}.digests")));
+                    let mut dedup = self.clone();
+                    if let Some(lang_state) = &lang_state {
+                        dedup.load_state(lang_state)?;
+                    }
+                    info!("deduplicating {lang}");
+                    dedup.dedup_files(files, src, dst)?;
+                    if let Some(lang_state) = &lang_state {
+                        dedup.save_state(lang_state)?;
+                    }
+                    Ok(())
+                })?;
+                Ok(())
+            }
+        }
+    }
 }
 
 impl Dedup for DedupTxt {
@@ -69,8 +261,25 @@ impl Command for DedupTxt {
     {
         clap::App::new("dedup")
             .about("line deduplication")
-            .arg(arg!([SOURCE] "Corpus source file."))
-            .arg(arg!([DESTINATION] "Corpus destination file. Should not exist."))
+            .long_about("Line deduplication.
+
+Blank lines are considered as document boundaries.
+Use --unit doc to deduplicate whole blank-line separated blocks (paragraphs/documents) instead of lines.
+Normalization options (--nfkc, --lowercase, ...) are applied before hashing, but original text is written.
+
+If SOURCE is a file, DESTINATION must be a file path.
+If SOURCE is a folder, each language (subfolder of split parts, or single file) is deduplicated into DESTINATION, keeping the folder layout.
+Use --scope to share the filter across all parts of a language (lang) or across all languages (global).
+
+Use --state to deduplicate against previous runs: the filter state is loaded from it if it exists, and saved back when done.
+With the lang scope on a folder, --state is a folder holding one state file per language.")
+            .arg(arg!([SOURCE] "Corpus source file/folder."))
+            .arg(arg!([DESTINATION] "Corpus destination file/folder. Should not exist."))
+            .arg(arg!(--unit <UNIT> "Deduplicate lines (line) or blank-line separated blocks (doc).").default_value("line").required(false))
+            .arg(arg!(--scope <SCOPE> "Filter sharing when SOURCE is a folder (lang, global)").default_value("lang").required(false))
+            .arg(arg!(--state <STATE> "Filter state file/folder to load from and save to.").required(false))
+            .arg(arg!(-J --num_threads <NUM_THREADS> "Number of threads to use (iif source is a folder). If 0, take all available").default_value("0").required(false))
+            .args(Normalization::args())
     }
 
     fn run(matches: &clap::ArgMatches) -> Result<(), Error>
@@ -79,29 +288,77 @@ impl Command for DedupTxt {
     {
         let src: PathBuf = matches.value_of("SOURCE").unwrap().into();
         let dst: PathBuf = matches.value_of("DESTINATION").unwrap().into();
+        let unit: TextUnit = matches.value_of("unit").unwrap().parse()?;
+        let normalization = Normalization::from_matches(matches);
+        let scope: DedupScope = matches.value_of("scope").unwrap().parse()?;
+        let state: Option<PathBuf> = matches.value_of("state").map(PathBuf::from);
+        let num_threads: usize = matches
+            .value_of("num_threads")
+            .unwrap()
+            .parse()
+            .expect("'num_threads' has to be a number.");
 
-        let mut d = Self::default();
-        // not sure of the syntax here...
-        // X as Y makes us "see" the struct X as the trait Y, so that we can
-        // disambiguate on similarly named methods.
-        <DedupTxt as Dedup>::dedup(&mut d, &src, &dst)?;
+        if src.is_file() {
+            let mut d = Self::new(unit, normalization);
+            if let Some(state) = &state {
+                d.load_state(state)?;
+            }
+            // not sure of the syntax here...
+            // X as Y makes us "see" the struct X as the trait Y, so that we can
+            // disambiguate on similarly named methods.
+            <DedupTxt as Dedup>::dedup(&mut d, &src, &dst)?;
+            if let Some(state) = &state {
+                d.save_state(state)?;
+            }
+        } else if src.is_dir() {
+            Self::new(unit, normalization).dedup_folder(
+                &src,
+                &dst,
+                scope,
+                state.as_deref(),
+                num_threads,
+            )?;
+        } else {
+            return Err(
+                std::io::Error::new(std::io::ErrorKind::NotFound, format!("{:?}", src)).into(),
+            );
+        }
         Ok(())
     }
 }
 
-impl Default for DedupTxt {
-    fn default() -> Self {
-        Self {
-            filter: Box::new(DigestFilter::default()),
-        }
-    }
-}
 #[cfg(test)]
 mod tests {
-    use std::io::Cursor;
+    use std::{fs::File, io::Cursor, io::Write, path::Path};
+
+    use tempfile::TempDir;
+
+    use crate::ops::{DedupScope, Normalization, TextUnit};
 
     use super::DedupTxt;
 
+    /// fr is split in two parts, en is a single file.
+    fn gen_corpus() -> TempDir {
+        let corpus = tempfile::tempdir().unwrap();
+        let fr = corpus.path().join("fr");
+        std::fs::create_dir(&fr).unwrap();
+        for (name, content) in [
+            ("fr_part_1.txt", "foo\nbar\n"),
+            ("fr_part_2.txt", "bar\nbaz\n"),
+        ] {
+            let mut f = File::create(fr.join(name)).unwrap();
+            f.write_all(content.as_bytes()).unwrap();
+        }
+        let mut f = File::create(corpus.path().join("en.txt")).unwrap();
+        f.write_all(b"foo\nquux\n").unwrap();
+
+        corpus
+    }
+
+    fn read(path: &Path) -> String {
+        std::fs::read_to_string(path).unwrap()
+    }
+
     #[test]
     fn test_simple() {
         let data = "foo
@@ -164,6 +421,7 @@ doc2
 hey
 newline
 never seen
+
 never seen again
 hoop
 last document is only duplicates :o
@@ -178,4 +436,150 @@ last document is only duplicates :o
         let result = String::from_utf8_lossy(&dest);
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn test_folder_lang_scope() {
+        let corpus = gen_corpus();
+        let dst = tempfile::tempdir().unwrap();
+
+        DedupTxt::default()
+            .dedup_folder(corpus.path(), dst.path(), DedupScope::Lang, None, 1)
+            .unwrap();
+
+        // duplicates across parts are removed, but not across languages
+        assert_eq!(read(&dst.path().join("fr/fr_part_1.txt")), "foo\nbar\n");
+        assert_eq!(read(&dst.path().join("fr/fr_part_2.txt")), "baz\n");
+        assert_eq!(read(&dst.path().join("en.txt")), "foo\nquux\n");
+    }
+
+    #[test]
+    fn test_folder_global_scope() {
+        let corpus = gen_corpus();
+        let dst = tempfile::tempdir().unwrap();
+
+        DedupTxt::default()
+            .dedup_folder(corpus.path(), dst.path(), DedupScope::Global, None, 1)
+            .unwrap();
+
+        // en comes first, so foo is kept there
+        assert_eq!(read(&dst.path().join("en.txt")), "foo\nquux\n");
+        assert_eq!(read(&dst.path().join("fr/fr_part_1.txt")), "bar\n");
+        assert_eq!(read(&dst.path().join("fr/fr_part_2.txt")), "baz\n");
+    }
+
+    #[test]
+    fn test_folder_state() {
+        let corpus = gen_corpus();
+        let state = tempfile::tempdir().unwrap();
+
+        // first run populates the state
+        let dst = tempfile::tempdir().unwrap();
+        DedupTxt::default()
+            .dedup_folder(
+                corpus.path(),
+                dst.path(),
+                DedupScope::Lang,
+                Some(state.path()),
+                1,
+            )
+            .unwrap();
+        assert!(state.path().join("fr.digests").exists());
+        assert!(state.path().join("en.digests").exists());
+
+        // second run on the same corpus drops everything
+        let dst = tempfile::tempdir().unwrap();
+        DedupTxt::default()
+            .dedup_folder(
+                corpus.path(),
+                dst.path(),
+                DedupScope::Lang,
+                Some(state.path()),
+                1,
+            )
+            .unwrap();
+        assert_eq!(read(&dst.path().join("fr/fr_part_1.txt")), "");
+        assert_eq!(read(&dst.path().join("fr/fr_part_2.txt")), "");
+        assert_eq!(read(&dst.path().join("en.txt")), "");
+    }
+
+    #[test]
+    fn test_state_roundtrip() {
+        let state_dir = tempfile::tempdir().unwrap();
+        let state = state_dir.path().join("state.digests");
+
+        let mut dedup = DedupTxt::default();
+        dedup.load_state(&state).unwrap();
+        let mut dest = Vec::new();
+        dedup
+            .dedup(&mut Cursor::new("foo\nbar"), &mut dest)
+            .unwrap();
+        dedup.save_state(&state).unwrap();
+
+        let mut dedup = DedupTxt::default();
+        dedup.load_state(&state).unwrap();
+        let mut dest = Vec::new();
+        dedup
+            .dedup(&mut Cursor::new("bar\nbaz"), &mut dest)
+            .unwrap();
+        assert_eq!(String::from_utf8_lossy(&dest), "baz\n");
+    }
+
+    #[test]
+    fn test_empty_docs() {
+        // second and fourth documents only have duplicates,
+        // and there are consecutive blank lines before the last one.
+        let data = "foo\nbar\n\nfoo\n\nbaz\n\nbar\n\n\nquux\n";
+        let expected = "foo\nbar\n\nbaz\n\nquux\n";
+
+        let mut dedup = DedupTxt::default();
+        let mut dest = Vec::new();
+        dedup.dedup(&mut Cursor::new(data), &mut dest).unwrap();
+
+        assert_eq!(String::from_utf8_lossy(&dest), expected);
+    }
+
+    #[test]
+    fn test_doc_unit() {
+        let data = "foo
+bar
+
+baz
+foo
+
+foo
+bar
+
+baz
+foo
+quux";
+        // only the third block is an exact duplicate.
+        let expected = "foo
+bar
+
+baz
+foo
+
+baz
+foo
+quux
+";
+
+        let mut dedup = DedupTxt::new(TextUnit::Doc, Normalization::default());
+        let mut dest = Vec::new();
+        dedup.dedup(&mut Cursor::new(data), &mut dest).unwrap();
+
+        assert_eq!(String::from_utf8_lossy(&dest), expected);
+    }
+
+    #[test]
+    fn test_normalization() {
+        let data = "Hello,  World!\nhello world\nHELLO WORLD 2\nhello world 3\n";
+        let expected = "Hello,  World!\nHELLO WORLD 2\n";
+
+        let mut dedup = DedupTxt::new(TextUnit::Line, Normalization::all());
+        let mut dest = Vec::new();
+        dedup.dedup(&mut Cursor::new(data), &mut dest).unwrap();
+
+        assert_eq!(String::from_utf8_lossy(&dest), expected);
+    }
 }
@@ -0,0 +1,38 @@
+/*! Line indices of OSCAR v1 corpora (see [crate::ops::IndexCorpus]) !*/
+use std::path::PathBuf;
+
+use clap::arg;
+
+use crate::{cli::Command, error::Error, ops::IndexCorpus};
+
+/// Every line is indexed, including the blank lines separating documents.
+pub struct IndexTxt;
+
+impl IndexCorpus for IndexTxt {
+    const SKIP_BLANK: bool = false;
+}
+
+impl Command for IndexTxt {
+    fn subcommand() -> clap::App<'static>
+    where
+        Self: Sized,
+    {
+        clap::App::new("index")
+            .about("Write a line offset index next to each (uncompressed) corpus file, used by sample for faster runs.")
+            .arg(arg!([SOURCE] "Corpus source file/folder.").required(true))
+            .arg(arg!(-J --num_threads <NUM_THREADS> "Number of threads to use (iif source is a folder). If 0, take all available").default_value("0").required(false))
+    }
+
+    fn run(matches: &clap::ArgMatches) -> Result<(), Error>
+    where
+        Self: Sized,
+    {
+        let src: PathBuf = matches.value_of("SOURCE").unwrap().into();
+        let num_threads: usize = matches
+            .value_of("num_threads")
+            .unwrap()
+            .parse()
+            .expect("'num_threads' has to be a number.");
+        Self::index(&src, num_threads)
+    }
+}
@@ -1,8 +1,18 @@
 /*! OSCAR v1 (2019) operation implementations. !*/
+mod decontaminate;
 mod dedup;
+mod index;
 mod oscar_txt;
+mod partition;
 mod sampling;
+mod to_doc;
+mod webdataset;
 
+pub(crate) use decontaminate::DecontaminateTxt;
 pub(crate) use dedup::DedupTxt;
+pub(crate) use index::IndexTxt;
 pub(crate) use oscar_txt::OscarTxt;
-pub(crate) use sampling::SampleDoc;
+pub(crate) use partition::PartitionTxt;
+pub(crate) use sampling::{SampleDoc, SampleLanguages};
+pub(crate) use to_doc::ToDocTxt;
+pub(crate) use webdataset::WebDatasetTxt;
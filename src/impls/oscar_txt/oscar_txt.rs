@@ -4,11 +4,11 @@ use clap::ArgMatches;
 use crate::{
     cli::Command,
     error::Error,
-    impls::oscar_txt::SampleDoc,
+    impls::oscar_txt::{SampleDoc, SampleLanguages},
     versions::{Schema, Version},
 };
 
-use super::DedupTxt;
+use super::{DecontaminateTxt, DedupTxt, IndexTxt, PartitionTxt, ToDocTxt, WebDatasetTxt};
 
 pub struct OscarTxt;
 
@@ -26,7 +26,13 @@ impl Command for OscarTxt {
         // add commands here
         let subcommand = clap::App::new(Self::version().to_string())
             .subcommand(DedupTxt::subcommand())
-            .subcommand(SampleDoc::subcommand());
+            .subcommand(SampleDoc::subcommand())
+            .subcommand(SampleLanguages::subcommand())
+            .subcommand(DecontaminateTxt::subcommand())
+            .subcommand(IndexTxt::subcommand())
+            .subcommand(PartitionTxt::subcommand())
+            .subcommand(WebDatasetTxt::subcommand())
+            .subcommand(ToDocTxt::subcommand());
 
         subcommand
     }
@@ -37,6 +43,12 @@ impl Command for OscarTxt {
         match subcommand {
             "dedup" => DedupTxt::run(matches),
             "sample" => SampleDoc::run(matches),
+            "sample-languages" => SampleLanguages::run(matches),
+            "decontaminate" => DecontaminateTxt::run(matches),
+            "index" => IndexTxt::run(matches),
+            "partition" => PartitionTxt::run(matches),
+            "export-webdataset" => WebDatasetTxt::run(matches),
+            "to-v2" => ToDocTxt::run(matches),
             x => Err(Error::Custom(format!(
                 "{x} op is not supported on this corpus version"
             ))),
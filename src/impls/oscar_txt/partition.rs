@@ -0,0 +1,115 @@
+/*! Train/validation/test partitioning of OSCAR v1 corpora (see [crate::ops::Partition]) !*/
+use std::{borrow::Cow, io::BufRead};
+
+use clap::arg;
+
+use crate::{
+    cli::Command,
+    error::Error,
+    ops::{partition_args, Partition, PartitionKey},
+};
+
+/// Documents are blank-line separated blocks, and hold no URL: they are assigned by content.
+pub struct PartitionTxt;
+
+impl Partition for PartitionTxt {
+    const EXTENSION: &'static str = "txt";
+
+    fn next_doc<R: BufRead>(r: &mut R, doc: &mut String) -> Result<bool, Error> {
+        doc.clear();
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if r.read_line(&mut line)? == 0 {
+                break;
+            }
+            if line.trim().is_empty() {
+                if doc.is_empty() {
+                    // leading blank lines
+                    continue;
+                }
+                break;
+            }
+            doc.push_str(&line);
+            if !line.ends_with('\n') {
+                doc.push('\n');
+            }
+        }
+        if doc.is_empty() {
+            return Ok(false);
+        }
+        // document separator
+        doc.push('\n');
+        Ok(true)
+    }
+
+    fn doc_key(doc: &str, _key: PartitionKey) -> Result<Cow<'_, str>, Error> {
+        Ok(Cow::Borrowed(doc.trim_end()))
+    }
+}
+
+impl Command for PartitionTxt {
+    fn subcommand() -> clap::App<'static>
+    where
+        Self: Sized,
+    {
+        clap::App::new("partition")
+            .about("Assign documents to train/validation/test (or other) splits, by hash of their content.")
+            .long_about("Assign documents to train/validation/test (or other) splits, by hash of their content.
+Assignments are stable across runs. Each language is written in DESTINATION/<split>/<lang>.txt.")
+            .arg(arg!([SOURCE] "Corpus source file/folder.").required(true))
+            .arg(arg!([DESTINATION] "Destination folder.").required(true))
+            .args(partition_args())
+    }
+
+    fn run(matches: &clap::ArgMatches) -> Result<(), Error>
+    where
+        Self: Sized,
+    {
+        Self::partition_from_matches(matches, PartitionKey::Content)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use crate::ops::{Partition, PartitionKey, Ratios};
+
+    use super::PartitionTxt;
+
+    #[test]
+    fn test_partition() {
+        let dir = tempdir().unwrap();
+        let src = dir.path().join("src");
+        std::fs::create_dir(&src).unwrap();
+        let corpus: String = (0..100)
+            .map(|i| format!("doc {i}\nline 2 of doc {i}\n\n"))
+            .collect();
+        std::fs::write(src.join("fr.txt"), &corpus).unwrap();
+
+        let ratios = Ratios::parse("train=0.5,test=0.5").unwrap();
+        let dst = dir.path().join("dst");
+        PartitionTxt::partition(&src, &dst, &ratios, PartitionKey::Content, 0, None, 1).unwrap();
+        let train = std::fs::read_to_string(dst.join("train/fr.txt")).unwrap();
+        let test = std::fs::read_to_string(dst.join("test/fr.txt")).unwrap();
+        assert_eq!(train.len() + test.len(), corpus.len());
+        assert!(!train.is_empty() && !test.is_empty());
+        assert!(train
+            .split("\n\n")
+            .all(|doc| doc.is_empty() || doc.lines().count() == 2));
+
+        // stable
+        let dst_2 = dir.path().join("dst_2");
+        PartitionTxt::partition(&src, &dst_2, &ratios, PartitionKey::Content, 0, None, 1).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dst_2.join("train/fr.txt")).unwrap(),
+            train
+        );
+        // not overwritten
+        assert!(
+            PartitionTxt::partition(&src, &dst, &ratios, PartitionKey::Content, 0, None, 1)
+                .is_err()
+        );
+    }
+}
@@ -2,4 +2,4 @@ mod indexed_reader;
 mod indexer;
 pub mod sampling;
 
-pub(crate) use sampling::SampleDoc;
+pub(crate) use sampling::{SampleDoc, SampleLanguages};
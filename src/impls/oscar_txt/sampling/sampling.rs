@@ -6,14 +6,16 @@ use crate::error::{self, Error};
 use crate::impls::oscar_txt::sampling::indexed_reader::IndexedReader;
 use crate::impls::oscar_txt::sampling::indexer::Indexer;
 use crate::ops::SampleText;
-use crate::ops::SamplingKind;
-use clap::arg;
+use crate::ops::{
+    is_compressed, open_stream, rng_from_matches, seed_arg, LineIndex, Reservoir, SampleBudget,
+    SamplingKind, SamplingRng, StratifiedSample,
+};
+use clap::{arg, ArgMatches};
 use itertools::Itertools;
 use rand::seq::SliceRandom;
-use rand::thread_rng;
 use std::collections::HashMap;
 use std::fs::File;
-use std::io::{BufReader, BufWriter, Write};
+use std::io::{BufRead, BufReader, BufWriter, Write};
 
 use std::path::{Path, PathBuf};
 
@@ -24,9 +26,14 @@ impl Command for SampleDoc {
     {
         clap::App::new("sample")
             .about("sample form corpus")
-            .arg(arg!([SOURCE] "Corpus source file."))
+            .arg(arg!([SOURCE] "Corpus source file. Use '-' for stdin (implies --reservoir)."))
             .arg(arg!([DESTINATION] "Corpus destination file. Should not exist."))
             .arg(arg!([SIZE] "size of the sample MB"))
+            .args(SampleBudget::args())
+            .group(SampleBudget::group().arg("SIZE"))
+            .arg(SamplingKind::arg().conflicts_with("reservoir"))
+            .args(Reservoir::args())
+            .arg(seed_arg())
     }
 
     fn run(matches: &clap::ArgMatches) -> Result<(), Error>
@@ -35,10 +42,20 @@ impl Command for SampleDoc {
     {
         let src: PathBuf = matches.value_of("SOURCE").unwrap().into();
         let dst: PathBuf = matches.value_of("DESTINATION").unwrap().into();
-        let size: usize =
-            matches.value_of("SIZE").unwrap().parse::<usize>().unwrap() * 10usize.pow(6);
+        let budget = match matches.value_of("SIZE") {
+            Some(size) => SampleBudget::Bytes(size.parse::<usize>().unwrap() * 10usize.pow(6)),
+            None => SampleBudget::from_matches(matches)?,
+        };
+        let sampling = SamplingKind::from_matches(matches);
+        let mut rng = rng_from_matches(matches)?;
 
-        Self::sample(&src, &dst, size, SamplingKind::WithoutReplacement)?;
+        // compressed files and stdin can't be indexed
+        if matches.is_present("reservoir") || src == Path::new("-") || is_compressed(&src) {
+            let weighted = matches.is_present("weighted");
+            Self::sample_stream(&[src], &dst, budget, weighted, &mut rng)?;
+        } else {
+            Self::sample(&src, &dst, budget, sampling, &mut rng)?;
+        }
         Ok(())
     }
 }
@@ -46,7 +63,11 @@ impl Command for SampleDoc {
 // do not know what is dyn is
 pub struct SampleDoc;
 impl SampleDoc {
+    /// Map line offsets to line sizes. Uses the index of `src` if it is fresh (see [crate::ops::IndexCorpus]).
     fn build_index(src: &Path) -> Result<HashMap<u64, usize>, error::Error> {
+        if let Some(index) = LineIndex::load_fresh(src, false)? {
+            return Ok(index.iter().collect());
+        }
         info!("indexing the corpus...");
         let corpus = File::open(&src)?;
         let corpus_buf = BufReader::new(corpus);
@@ -54,55 +75,87 @@ impl SampleDoc {
         let ret: std::io::Result<_> = indexer.collect();
         Ok(ret?)
     }
+
+    /// Measure the documents of `index` in the unit of `budget` (see [SampleBudget::measure]).
+    fn measure_docs(
+        src: &Path,
+        index: HashMap<u64, usize>,
+        budget: SampleBudget,
+    ) -> Result<HashMap<u64, usize>, Error> {
+        match budget {
+            SampleBudget::Bytes(_) => Ok(index),
+            SampleBudget::Docs(_) | SampleBudget::Fraction(_) => {
+                Ok(index.into_keys().map(|offset| (offset, 1)).collect())
+            }
+            SampleBudget::Tokens(_) => {
+                info!("counting tokens...");
+                let mut corpus_buf = BufReader::new(File::open(src)?);
+                let mut measures = HashMap::with_capacity(index.len());
+                let mut offset = 0;
+                let mut line = String::new();
+                loop {
+                    line.clear();
+                    let read = corpus_buf.read_line(&mut line)?;
+                    if read == 0 {
+                        break;
+                    }
+                    measures.insert(offset, budget.measure(&line));
+                    offset += read as u64;
+                }
+                Ok(measures)
+            }
+        }
+    }
+
+    /// Offsets of `collection`, sorted so that sampling doesn't depend on the map's iteration order.
+    fn sorted_offsets(collection: &HashMap<u64, usize>) -> Vec<&u64> {
+        collection.keys().sorted_unstable().collect()
+    }
+
+    /// Sample offsets of `collection` (mapping offsets to document sizes in the unit of `budget`) with replacement.
     fn sample_indices(
         collection: &HashMap<u64, usize>,
-        max_size: usize,
+        budget: SampleBudget,
+        rng: &mut SamplingRng,
     ) -> Result<Vec<u64>, Error> {
         info!("sampling doc indices...");
-        let mut rng = thread_rng();
+        let max_size = budget.size();
+        // empty documents don't consume the budget, and could be drawn forever
+        let candidates: Vec<&u64> = Self::sorted_offsets(collection)
+            .into_iter()
+            .filter(|offset| (1..=max_size).contains(&collection[*offset]))
+            .collect();
+        if candidates.is_empty() {
+            return Err(Error::Custom("no document to sample from".to_string()));
+        }
+
         let mut size = 0;
-        let mut offsets = Vec::with_capacity(collection.len());
-        offsets.extend(collection.keys());
         let mut sampled_offsets = Vec::new();
-        loop {
-            let chosen_offset = offsets
-                .choose(&mut rng)
-                .ok_or_else(|| Error::Custom("no document to sample from".to_string()))?;
-            let doc_length = collection
-                .get(chosen_offset)
-                .ok_or_else(|| Error::Custom("no document to sample from".to_string()))?;
-
-            if doc_length > &max_size {
-                continue;
-            }
+        // candidates is not empty
+        while let Some(offset) = candidates.choose(rng) {
+            let doc_length = collection[*offset];
             if size + doc_length > max_size {
                 break;
             }
-            sampled_offsets.push(*chosen_offset);
+            sampled_offsets.push(**offset);
             size += doc_length;
         }
+        // repeated draws are kept: they are written once per draw
         sampled_offsets.sort_unstable();
-        let size_before = sampled_offsets.len();
-        sampled_offsets.dedup();
-        if sampled_offsets.is_empty() {
-            return Err(Error::Custom("no sample is selected".to_string()));
-        }
-
-        let dup_pctg = (sampled_offsets.len() as f64 / size_before as f64) * 100.0;
-        info!("Dedup: keeping {:.1}% of sampled data", dup_pctg);
-
         Ok(sampled_offsets)
     }
 
+    /// Sample offsets of `collection` (mapping offsets to document sizes in the unit of `budget`) without replacement.
     fn sample_indices_discard(
         collection: &HashMap<u64, usize>,
-        max_size: usize,
+        budget: SampleBudget,
+        rng: &mut SamplingRng,
     ) -> Result<Vec<u64>, Error> {
         // let doc_sizes: Vec<usize> = vec![100, 20, 120, 5, 133, 40, 894, 12, 496];
-        let mut offsets = collection.keys().collect_vec();
+        let mut offsets = Self::sorted_offsets(collection);
         // let mut doc_indices = (0..400_000_000).collect_vec();
-        let mut rng = rand::thread_rng();
-        offsets.shuffle(&mut rng);
+        offsets.shuffle(rng);
+        let max_size = budget.size();
 
         let mut cur_size = 0;
         let mut sample_indices: Vec<u64> = Vec::new();
@@ -110,7 +163,7 @@ impl SampleDoc {
             let doc_size = collection.get(idx).unwrap();
 
             // if doc fits, add it
-            if cur_size + doc_size < max_size {
+            if cur_size + doc_size <= max_size {
                 sample_indices.push(*idx);
                 cur_size += doc_size;
             // if doc is larger than max size, skip it
@@ -123,10 +176,34 @@ impl SampleDoc {
             }
         }
 
+        if sample_indices.is_empty() {
+            return Err(Error::Custom("no document to sample from".to_string()));
+        }
         sample_indices.sort_unstable();
         Ok(sample_indices)
     }
 
+    /// Sample lines of `files` (read in order) in a single pass (see [Reservoir]).
+    /// Files can be compressed or `-` (stdin).
+    fn sample_stream(
+        files: &[PathBuf],
+        dst: &Path,
+        budget: SampleBudget,
+        weighted: bool,
+        rng: &mut SamplingRng,
+    ) -> Result<(), Error> {
+        info!("sampling in a single pass...");
+        let mut w = BufWriter::new(File::create(dst)?);
+        let mut reservoir = Reservoir::new(budget, weighted);
+        for file in files {
+            let mut r = open_stream(file)?;
+            reservoir.read(&mut r, &mut w, rng, |line| Self::doc_size(line, budget))?;
+        }
+        let (nb_sampled, nb_read) = reservoir.finish(&mut w)?;
+        info!("sampled {nb_sampled} lines out of {nb_read}");
+        Ok(())
+    }
+
     fn write_samples(src: &Path, dst: &Path, sample_idx: &[u64]) -> Result<(), Error> {
         info!("reading corpus and writing samples...");
         let corpus = File::open(&src)?;
@@ -148,20 +225,30 @@ impl SampleText for SampleDoc {
     fn sample(
         src: &Path,
         dst: &Path,
-        sample_size: usize,
+        budget: SampleBudget,
         sampling: SamplingKind,
+        rng: &mut SamplingRng,
     ) -> Result<(), Error> {
         //check that sample size < file size.
         let src_size = src.metadata()?.len();
-        if sample_size > src_size as usize {
-            return Err(Error::Custom(format!("Requested sample size is too big for the source corpus (corpus is {}MB, sample size is  {}MB)", src_size/10u64.pow(6), sample_size/10usize.pow(6))));
+        if let SampleBudget::Bytes(sample_size) = budget {
+            if sample_size > src_size as usize {
+                return Err(Error::Custom(format!("Requested sample size is too big for the source corpus (corpus is {}MB, sample size is  {}MB)", src_size/10u64.pow(6), sample_size/10usize.pow(6))));
+            }
         }
         let indices = Self::build_index(src)?;
+        let budget = budget.resolve(indices.len());
+        if budget.size() == 0 {
+            return Err(Error::Custom(format!(
+                "Requested sample is empty ({budget:?})"
+            )));
+        }
+        let indices = Self::measure_docs(src, indices, budget)?;
 
         let indices = match sampling {
-            SamplingKind::WithReplacement => Self::sample_indices(&indices, sample_size)?,
+            SamplingKind::WithReplacement => Self::sample_indices(&indices, budget, rng)?,
             SamplingKind::WithoutReplacement => {
-                Self::sample_indices_discard(&indices, sample_size)?
+                Self::sample_indices_discard(&indices, budget, rng)?
             }
         };
         // let indices = Self::sample_(&indices, sample_size)?;
@@ -170,6 +257,54 @@ impl SampleText for SampleDoc {
         Ok(())
     }
 }
+impl StratifiedSample for SampleDoc {
+    const EXTENSION: &'static str = "txt";
+
+    /// Index-based sampling needs a single uncompressed file:
+    /// split or compressed languages are sampled in a single pass, which can't be done with replacement.
+    fn sample_language(
+        files: &[PathBuf],
+        dst: &Path,
+        budget: SampleBudget,
+        sampling: SamplingKind,
+        rng: &mut SamplingRng,
+    ) -> Result<(), Error> {
+        match (files, sampling) {
+            ([file], _) if !is_compressed(file) => Self::sample(file, dst, budget, sampling, rng),
+            (_, SamplingKind::WithoutReplacement) => {
+                Self::sample_stream(files, dst, budget, false, rng)
+            }
+            (_, SamplingKind::WithReplacement) => Err(Error::Custom(format!(
+                "{:?}: split or compressed languages can't be sampled with replacement",
+                files
+            ))),
+        }
+    }
+
+    fn doc_size(doc: &[u8], budget: SampleBudget) -> Result<usize, Error> {
+        Ok(budget.measure(&String::from_utf8_lossy(doc)))
+    }
+}
+
+/// Stratified multilingual sampling (see [StratifiedSample]).
+pub struct SampleLanguages;
+
+impl Command for SampleLanguages {
+    fn subcommand() -> clap::App<'static>
+    where
+        Self: Sized,
+    {
+        SampleDoc::sample_languages_subcommand()
+    }
+
+    fn run(matches: &ArgMatches) -> Result<(), Error>
+    where
+        Self: Sized,
+    {
+        SampleDoc::sample_languages_from_matches(matches)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use itertools::Itertools;
@@ -178,6 +313,7 @@ mod tests {
     use tempfile::NamedTempFile;
 
     use crate::impls::oscar_txt::SampleDoc;
+    use crate::ops::{sampling_rng, SampleBudget, SampleText, SamplingKind};
 
     #[test]
     fn test_index() {
@@ -188,12 +324,33 @@ mod tests {
         let testmap: HashMap<u64, usize> = HashMap::from([(27, 49), (76, 269), (0, 27)]);
         assert_eq!(SampleDoc::build_index(&path).unwrap(), testmap);
     }
+    #[test]
+    fn test_index_reuse() {
+        use crate::impls::oscar_txt::IndexTxt;
+        use crate::ops::IndexCorpus;
+
+        let text = "foo\n\nbar baz\n";
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(text.as_bytes()).unwrap();
+        let path = file.into_temp_path();
+
+        let scanned = SampleDoc::build_index(&path).unwrap();
+        IndexTxt::index_file(&path).unwrap();
+        assert_eq!(SampleDoc::build_index(&path).unwrap(), scanned);
+        std::fs::remove_file(crate::ops::LineIndex::path(&path)).unwrap();
+    }
+
     #[test]
     fn test_sample_sampling() {
         let testmap: HashMap<u64, usize> = HashMap::from([(1, 48), (2, 269), (0, 26)]);
         //test the sampling
         let max_size = 80;
-        let sample = SampleDoc::sample_indices(&testmap, max_size).unwrap();
+        let sample = SampleDoc::sample_indices(
+            &testmap,
+            SampleBudget::Bytes(max_size),
+            &mut sampling_rng(0),
+        )
+        .unwrap();
         let iter: Vec<usize> = sample.iter().map(|x| *testmap.get(x).unwrap()).collect();
         //this will give me the index I need to sum the values corrsponding to the indecie
 
@@ -204,7 +361,12 @@ mod tests {
     fn test_sample_sorting() {
         let testmap: HashMap<u64, usize> = HashMap::from([(1, 48), (2, 269), (0, 26)]);
         let max_size = 80;
-        let sample = SampleDoc::sample_indices(&testmap, max_size).unwrap();
+        let sample = SampleDoc::sample_indices(
+            &testmap,
+            SampleBudget::Bytes(max_size),
+            &mut sampling_rng(0),
+        )
+        .unwrap();
         let iter: Vec<usize> = sample.iter().map(|x| *testmap.get(x).unwrap()).collect();
 
         let _sort: Vec<usize> = sample
@@ -235,7 +397,12 @@ rust is hard";
 
         let testmap: HashMap<u64, usize> = SampleDoc::build_index(&src_path).unwrap();
         let max_size = 80;
-        let sampled_offsets = SampleDoc::sample_indices_discard(&testmap, max_size).unwrap();
+        let sampled_offsets = SampleDoc::sample_indices_discard(
+            &testmap,
+            SampleBudget::Bytes(max_size),
+            &mut sampling_rng(0),
+        )
+        .unwrap();
 
         SampleDoc::write_samples(&src_path, &dst_path, &sampled_offsets).unwrap();
         let sampled_file = std::fs::read_to_string(&dst_path).unwrap();
@@ -255,6 +422,200 @@ rust is hard";
         expected.sort_unstable();
         assert_eq!(positions_in_corpus, expected);
     }
+
+    fn sample_with_seed(src: &std::path::Path, sampling: SamplingKind, seed: u64) -> String {
+        let dst = NamedTempFile::new().unwrap().into_temp_path();
+        SampleDoc::sample(
+            src,
+            &dst,
+            SampleBudget::Bytes(200),
+            sampling,
+            &mut sampling_rng(seed),
+        )
+        .unwrap();
+        std::fs::read_to_string(&dst).unwrap()
+    }
+
+    #[test]
+    fn test_sample_seeded() {
+        let text: String = (0..100).map(|i| format!("line number {i}\n")).collect();
+        let mut src = NamedTempFile::new().unwrap();
+        src.write_all(text.as_bytes()).unwrap();
+        let src_path = src.into_temp_path();
+
+        for sampling in [
+            SamplingKind::WithReplacement,
+            SamplingKind::WithoutReplacement,
+        ] {
+            let sample = sample_with_seed(&src_path, sampling, 42);
+            assert!(!sample.is_empty());
+            assert_eq!(sample, sample_with_seed(&src_path, sampling, 42));
+        }
+
+        // different seeds pick different lines
+        assert_ne!(
+            sample_with_seed(&src_path, SamplingKind::WithoutReplacement, 1),
+            sample_with_seed(&src_path, SamplingKind::WithoutReplacement, 2)
+        );
+    }
+
+    #[test]
+    fn test_measure_docs() {
+        let text = "foo bar\nbaz\n\nquux quuux quuuux\n";
+        let mut src = NamedTempFile::new().unwrap();
+        src.write_all(text.as_bytes()).unwrap();
+        let src_path = src.into_temp_path();
+
+        let index = SampleDoc::build_index(&src_path).unwrap();
+        let tokens =
+            SampleDoc::measure_docs(&src_path, index.clone(), SampleBudget::Tokens(0)).unwrap();
+        assert_eq!(tokens, HashMap::from([(0, 2), (8, 1), (12, 0), (13, 3)]));
+        let docs = SampleDoc::measure_docs(&src_path, index, SampleBudget::Docs(0)).unwrap();
+        assert_eq!(docs, HashMap::from([(0, 1), (8, 1), (12, 1), (13, 1)]));
+    }
+
+    #[test]
+    fn test_sample_stream() {
+        let text: String = (0..100).map(|i| format!("line number {i}\n")).collect();
+        let src_dir = tempfile::tempdir().unwrap();
+        let src_path = src_dir.path().join("fr.txt.gz");
+        let mut enc = flate2::write::GzEncoder::new(
+            std::fs::File::create(&src_path).unwrap(),
+            flate2::Compression::default(),
+        );
+        enc.write_all(text.as_bytes()).unwrap();
+        enc.finish().unwrap();
+
+        let dst = NamedTempFile::new().unwrap().into_temp_path();
+        SampleDoc::sample_stream(
+            &[src_path],
+            &dst,
+            SampleBudget::Docs(10),
+            false,
+            &mut sampling_rng(0),
+        )
+        .unwrap();
+        let sample = std::fs::read_to_string(&dst).unwrap();
+        assert_eq!(sample.lines().count(), 10);
+        assert!(sample.lines().all(|line| text.contains(line)));
+    }
+
+    #[test]
+    fn test_sample_with_replacement_docs() {
+        let mut src = NamedTempFile::new().unwrap();
+        src.write_all(b"foo\nbar\nbaz\n").unwrap();
+        let src_path = src.into_temp_path();
+
+        for seed in 1..=3 {
+            let dst = NamedTempFile::new().unwrap().into_temp_path();
+            SampleDoc::sample(
+                &src_path,
+                &dst,
+                SampleBudget::Docs(5),
+                SamplingKind::WithReplacement,
+                &mut sampling_rng(seed),
+            )
+            .unwrap();
+            // 5 draws out of 3 documents: repeated draws are written again
+            let sample = std::fs::read_to_string(&dst).unwrap();
+            let lines: Vec<&str> = sample.lines().collect();
+            assert_eq!(lines.len(), 5);
+            let mut distinct = lines.clone();
+            distinct.sort_unstable();
+            distinct.dedup();
+            assert!(distinct.len() < lines.len());
+        }
+    }
+
+    #[test]
+    fn test_sample_empty_budget() {
+        let mut src = NamedTempFile::new().unwrap();
+        src.write_all(b"foo\nbar\nbaz\n").unwrap();
+        let src_path = src.into_temp_path();
+
+        for sampling in [
+            SamplingKind::WithReplacement,
+            SamplingKind::WithoutReplacement,
+        ] {
+            for budget in [
+                SampleBudget::Fraction(0.01),
+                SampleBudget::Docs(0),
+                SampleBudget::Bytes(2),
+                SampleBudget::Tokens(0),
+            ] {
+                let dst = NamedTempFile::new().unwrap().into_temp_path();
+                assert!(
+                    SampleDoc::sample(&src_path, &dst, budget, sampling, &mut sampling_rng(0))
+                        .is_err()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_sample_languages() {
+        use crate::ops::{Mixing, StratifiedSample};
+
+        let src = tempfile::tempdir().unwrap();
+        std::fs::create_dir(src.path().join("fr")).unwrap();
+        for part in 1..=2 {
+            let text: String = (0..10).map(|i| format!("fr {part} {i}\n")).collect();
+            std::fs::write(src.path().join(format!("fr/fr_part_{part}.txt")), text).unwrap();
+        }
+        let mut enc = flate2::write::GzEncoder::new(
+            std::fs::File::create(src.path().join("en.txt.gz")).unwrap(),
+            flate2::Compression::default(),
+        );
+        enc.write_all(
+            (0..20)
+                .map(|i| format!("en {i}\n"))
+                .collect::<String>()
+                .as_bytes(),
+        )
+        .unwrap();
+        enc.finish().unwrap();
+
+        let dst = tempfile::tempdir().unwrap();
+        SampleDoc::sample_languages(
+            src.path(),
+            dst.path(),
+            SampleBudget::Docs(10),
+            &Mixing::Temperature(1.0),
+            SamplingKind::WithoutReplacement,
+            &mut sampling_rng(0),
+        )
+        .unwrap();
+        for lang in ["fr", "en"] {
+            let sample = std::fs::read_to_string(dst.path().join(format!("{lang}.txt"))).unwrap();
+            assert_eq!(sample.lines().count(), 5);
+            assert!(sample.lines().all(|line| line.starts_with(lang)));
+        }
+
+        // streamed languages can't be sampled with replacement
+        let dst = tempfile::tempdir().unwrap();
+        assert!(SampleDoc::sample_languages(
+            src.path(),
+            dst.path(),
+            SampleBudget::Docs(10),
+            &Mixing::Temperature(1.0),
+            SamplingKind::WithReplacement,
+            &mut sampling_rng(0),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_sample_docs() {
+        let testmap: HashMap<u64, usize> = (0..10).map(|i| (i * 100, 1)).collect();
+        let sample = SampleDoc::sample_indices_discard(
+            &testmap,
+            SampleBudget::Docs(3),
+            &mut sampling_rng(0),
+        )
+        .unwrap();
+        assert_eq!(sample.len(), 3);
+    }
+
     #[test]
     #[ignore]
     fn test_get_sample_on_scale() {
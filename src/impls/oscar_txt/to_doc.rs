@@ -0,0 +1,99 @@
+/*! Conversion of OSCAR v1 corpora into OSCAR v2 documents (see [crate::ops::ToDoc]) !*/
+use std::{io::BufRead, path::PathBuf};
+
+use clap::arg;
+
+use crate::{
+    cli::Command,
+    error::Error,
+    impls::oscar_txt::PartitionTxt,
+    ops::{Partition, ToDoc},
+};
+
+pub struct ToDocTxt;
+
+impl ToDoc for ToDocTxt {
+    fn next_doc<R: BufRead>(r: &mut R, doc: &mut String) -> Result<bool, Error> {
+        if !PartitionTxt::next_doc(r, doc)? {
+            return Ok(false);
+        }
+        // document separator
+        doc.pop();
+        Ok(true)
+    }
+}
+
+impl Command for ToDocTxt {
+    fn subcommand() -> clap::App<'static>
+    where
+        Self: Sized,
+    {
+        clap::App::new("to-v2")
+            .about("Convert a v1 corpus into minimal v2 (JSONL) documents.")
+            .long_about("Convert a v1 corpus into minimal v2 (JSONL) documents.
+Each blank-line separated document becomes a v2 document holding its content and the language of its file
+(fr.txt, fr_dedup.txt.gz and fr/fr_part_1.txt are all fr), with no WARC headers, annotations or line identifications.
+Folders keep their layout: fr/fr_part_1.txt is converted into DESTINATION/fr/fr_part_1.jsonl.")
+            .arg(arg!([SOURCE] "Corpus source file/folder.").required(true))
+            .arg(arg!([DESTINATION] "Destination file/folder.").required(true))
+            .arg(arg!(-J --num_threads <NUM_THREADS> "Number of threads to use (iif source is a folder). If 0, take all available").default_value("0").required(false))
+    }
+
+    fn run(matches: &clap::ArgMatches) -> Result<(), Error>
+    where
+        Self: Sized,
+    {
+        let src: PathBuf = matches
+            .value_of("SOURCE")
+            .expect("Value of 'SOURCE' is required.")
+            .into();
+        let dst: PathBuf = matches
+            .value_of("DESTINATION")
+            .expect("Value of 'DESTINATION' is required.")
+            .into();
+        let num_threads: usize = matches
+            .value_of("num_threads")
+            .unwrap()
+            .parse()
+            .expect("'num_threads' has to be a number.");
+
+        Self::to_doc(&src, &dst, num_threads)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use oscar_io::{lang::Lang, oscar_doc::Document};
+    use tempfile::tempdir;
+
+    use crate::ops::ToDoc;
+
+    use super::ToDocTxt;
+
+    #[test]
+    fn test_to_doc() {
+        let dir = tempdir().unwrap();
+        let src = dir.path().join("src");
+        std::fs::create_dir_all(src.join("de")).unwrap();
+        std::fs::write(src.join("fr.txt"), "foo\nbar\n\nbaz\n\n").unwrap();
+        std::fs::write(src.join("de/de_part_1.txt"), "quux\n").unwrap();
+
+        let dst = dir.path().join("dst");
+        ToDocTxt::to_doc(&src, &dst, 1).unwrap();
+
+        let docs: Vec<Document> = std::fs::read_to_string(dst.join("fr.jsonl"))
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(docs.len(), 2);
+        assert_eq!(docs[0].content(), "foo\nbar");
+        assert_eq!(docs[0].identification().label(), &Lang::Fr);
+        assert!(docs[0].warc_headers().is_empty());
+        assert_eq!(docs[1].content(), "baz");
+
+        let de = std::fs::read_to_string(dst.join("de/de_part_1.jsonl")).unwrap();
+        let de: Document = serde_json::from_str(de.trim_end()).unwrap();
+        assert_eq!(de.identification().label(), &Lang::De);
+    }
+}
@@ -0,0 +1,72 @@
+/*! WebDataset export of OSCAR v1 corpora (see [crate::ops::WebDataset]) !*/
+use std::io::BufRead;
+
+use serde_json::{json, Value};
+
+use crate::{
+    cli::Command,
+    error::Error,
+    impls::oscar_txt::PartitionTxt,
+    ops::{webdataset_args, Partition, WebDataset},
+};
+
+/// Documents are blank-line separated blocks, and their only metadata is their language.
+pub struct WebDatasetTxt;
+
+impl WebDataset for WebDatasetTxt {
+    fn next_sample<R: BufRead>(r: &mut R, lang: &str) -> Result<Option<(String, Value)>, Error> {
+        let mut doc = String::new();
+        if !PartitionTxt::next_doc(r, &mut doc)? {
+            return Ok(None);
+        }
+        // document separator
+        doc.pop();
+        Ok(Some((doc, json!({ "lang": lang }))))
+    }
+}
+
+impl Command for WebDatasetTxt {
+    fn subcommand() -> clap::App<'static>
+    where
+        Self: Sized,
+    {
+        clap::App::new("export-webdataset")
+            .about("Pack documents into WebDataset-style tar shards.")
+            .long_about(
+                "Pack documents into WebDataset-style tar shards.
+Each language is written into DESTINATION/<lang>/<lang>-000000.tar, <lang>-000001.tar...
+Each document is a sample made of a .txt member (its content) and a .json member (its language).
+Each language folder also holds a shardindex.json file, listing shard sizes and number of samples.",
+            )
+            .args(webdataset_args())
+    }
+
+    fn run(matches: &clap::ArgMatches) -> Result<(), Error>
+    where
+        Self: Sized,
+    {
+        Self::webdataset_from_matches(matches)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::ops::WebDataset;
+
+    use super::WebDatasetTxt;
+
+    #[test]
+    fn test_next_sample() {
+        let mut r = "\nfoo\nbar\n\nbaz\n".as_bytes();
+        let sample = WebDatasetTxt::next_sample(&mut r, "fr").unwrap();
+        assert_eq!(
+            sample,
+            Some(("foo\nbar\n".to_string(), json!({"lang": "fr"})))
+        );
+        let sample = WebDatasetTxt::next_sample(&mut r, "fr").unwrap();
+        assert_eq!(sample, Some(("baz\n".to_string(), json!({"lang": "fr"}))));
+        assert_eq!(WebDatasetTxt::next_sample(&mut r, "fr").unwrap(), None);
+    }
+}
@@ -0,0 +1,286 @@
+//! Consistency audit of document-level and line-level language identifications.
+//!
+//! For each document label, counts the labels of the lines of its documents (the confusion),
+//! and flags documents whose majority line label differs from their label.
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use log::info;
+use serde_json::{json, Value};
+
+use crate::{
+    error::Error,
+    ops::{for_each_file, open_decompressed, source_languages},
+};
+
+/// Label of lines and documents lacking an identification.
+pub const UNIDENTIFIED: &str = "unidentified";
+
+/// Audit of the documents of a label.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct LabelAudit {
+    docs: u64,
+    flagged_docs: u64,
+    /// Lines per line label.
+    line_labels: HashMap<String, u64>,
+    /// Flagged documents per majority line label.
+    majority_labels: HashMap<String, u64>,
+}
+
+/// Audit of a set of documents, by document label.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct LangAudit {
+    labels: HashMap<String, LabelAudit>,
+}
+
+impl LangAudit {
+    /// Majority label of `line_labels`, unidentified lines left aside.
+    /// Ties are broken in favour of `doc_label`, then alphabetically.
+    fn majority<'a>(line_labels: &'a HashMap<&str, u64>, doc_label: &str) -> Option<&'a str> {
+        line_labels
+            .iter()
+            .filter(|(label, _)| **label != UNIDENTIFIED)
+            .max_by(|(a, a_count), (b, b_count)| {
+                a_count
+                    .cmp(b_count)
+                    .then((**a == doc_label).cmp(&(**b == doc_label)))
+                    .then(b.cmp(a))
+            })
+            .map(|(label, _)| *label)
+    }
+
+    /// Account for a document. Returns `true` if its majority line label differs from its label.
+    pub fn add_doc(&mut self, doc: &Value) -> Result<bool, Error> {
+        let metadata = &doc["metadata"];
+        if metadata.is_null() {
+            return Err(Error::Custom(format!("document has no metadata: {doc}")));
+        }
+        let doc_label = metadata["identification"]["label"]
+            .as_str()
+            .unwrap_or(UNIDENTIFIED);
+
+        let mut line_labels: HashMap<&str, u64> = HashMap::new();
+        if let Some(sentences) = metadata["sentence_identifications"].as_array() {
+            for sentence in sentences {
+                let label = sentence["label"].as_str().unwrap_or(UNIDENTIFIED);
+                *line_labels.entry(label).or_default() += 1;
+            }
+        }
+        let majority = Self::majority(&line_labels, doc_label);
+        let flagged = majority.is_some_and(|majority| majority != doc_label);
+
+        let audit = self.labels.entry(doc_label.to_string()).or_default();
+        audit.docs += 1;
+        for (label, count) in &line_labels {
+            *audit.line_labels.entry(label.to_string()).or_default() += count;
+        }
+        if let (true, Some(majority)) = (flagged, majority) {
+            audit.flagged_docs += 1;
+            *audit
+                .majority_labels
+                .entry(majority.to_string())
+                .or_default() += 1;
+        }
+        Ok(flagged)
+    }
+
+    /// Account for the documents (non blank lines) of a reader, writing the flagged ones in `flagged`.
+    pub fn add_docs<R: BufRead, W: Write>(
+        &mut self,
+        r: R,
+        flagged: Option<&Mutex<W>>,
+    ) -> Result<(), Error> {
+        for line in r.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if self.add_doc(&serde_json::from_str(&line)?)? {
+                if let Some(flagged) = flagged {
+                    let mut w = flagged
+                        .lock()
+                        .map_err(|_| Error::Custom("flagged file lock is poisoned".to_string()))?;
+                    writeln!(w, "{line}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Add the audit of `other`.
+    pub fn merge(&mut self, other: &Self) {
+        for (label, other) in &other.labels {
+            let audit = self.labels.entry(label.clone()).or_default();
+            audit.docs += other.docs;
+            audit.flagged_docs += other.flagged_docs;
+            for (line_label, count) in &other.line_labels {
+                *audit.line_labels.entry(line_label.clone()).or_default() += count;
+            }
+            for (majority, count) in &other.majority_labels {
+                *audit.majority_labels.entry(majority.clone()).or_default() += count;
+            }
+        }
+    }
+
+    /// JSON report. Labels are sorted, and line labels are sorted by decreasing count.
+    pub fn to_json(&self) -> Value {
+        let mut labels: Vec<(&String, &LabelAudit)> = self.labels.iter().collect();
+        labels.sort_unstable_by_key(|(label, _)| *label);
+        let labels: Vec<Value> = labels
+            .into_iter()
+            .map(|(label, audit)| {
+                let total_lines: u64 = audit.line_labels.values().sum();
+                let mut line_labels: Vec<(&String, &u64)> = audit.line_labels.iter().collect();
+                line_labels.sort_unstable_by(|(a, a_count), (b, b_count)| {
+                    b_count.cmp(a_count).then(a.cmp(b))
+                });
+                let confusion: Vec<Value> = line_labels
+                    .into_iter()
+                    .map(|(line_label, count)| {
+                        json!({
+                            "label": line_label,
+                            "lines": count,
+                            "share": *count as f64 / total_lines as f64,
+                        })
+                    })
+                    .collect();
+                json!({
+                    "label": label,
+                    "docs": audit.docs,
+                    "flagged_docs": audit.flagged_docs,
+                    "flagged_share": audit.flagged_docs as f64 / audit.docs as f64,
+                    "lines": total_lines,
+                    "line_labels": confusion,
+                    "flagged_majority_labels": audit.majority_labels,
+                })
+            })
+            .collect();
+        let docs: u64 = self.labels.values().map(|audit| audit.docs).sum();
+        let flagged_docs: u64 = self.labels.values().map(|audit| audit.flagged_docs).sum();
+        json!({
+            "docs": docs,
+            "flagged_docs": flagged_docs,
+            "labels": labels,
+        })
+    }
+}
+
+pub trait AuditLang {
+    /// Audit the corpus at `src` (files in parallel), writing a JSON report at `report`,
+    /// and the flagged documents in `flagged` if provided.
+    fn audit(
+        src: &Path,
+        report: &Path,
+        flagged: Option<&Path>,
+        num_threads: usize,
+    ) -> Result<(), Error> {
+        let files: Vec<PathBuf> = source_languages(src)?
+            .into_iter()
+            .flat_map(|(_, files)| files)
+            .collect();
+        let flagged = match flagged {
+            Some(path) => Some(Mutex::new(BufWriter::new(File::create(path)?))),
+            None => None,
+        };
+
+        let results = for_each_file(&files, num_threads, "auditing", |file| {
+            info!("auditing {:?}", file);
+            let mut audit = LangAudit::default();
+            audit.add_docs(open_decompressed(file)?, flagged.as_ref())?;
+            Ok(audit)
+        });
+        if let Some(flagged) = flagged {
+            flagged
+                .into_inner()
+                .map_err(|_| Error::Custom("flagged file lock is poisoned".to_string()))?
+                .flush()?;
+        }
+        let mut audit = LangAudit::default();
+        for file_audit in results? {
+            audit.merge(&file_audit);
+        }
+
+        let report_json = audit.to_json();
+        info!(
+            "{} flagged documents out of {}",
+            report_json["flagged_docs"], report_json["docs"]
+        );
+        let mut w = BufWriter::new(File::create(report)?);
+        serde_json::to_writer_pretty(&mut w, &report_json)?;
+        writeln!(w)?;
+        w.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Mutex};
+
+    use serde_json::{json, Value};
+
+    use super::LangAudit;
+
+    fn doc(label: &str, lines: &[Option<&str>]) -> Value {
+        let sentences: Vec<Value> = lines
+            .iter()
+            .map(|line| match line {
+                Some(label) => json!({"label": label, "prob": 0.9}),
+                None => Value::Null,
+            })
+            .collect();
+        json!({
+            "content": "",
+            "metadata": {
+                "identification": {"label": label, "prob": 0.9},
+                "annotation": null,
+                "sentence_identifications": sentences,
+            },
+        })
+    }
+
+    #[test]
+    fn test_majority() {
+        let labels = HashMap::from([("fr", 2), ("en", 2), ("unidentified", 5)]);
+        assert_eq!(LangAudit::majority(&labels, "fr"), Some("fr"));
+        assert_eq!(LangAudit::majority(&labels, "de"), Some("en"));
+        assert_eq!(LangAudit::majority(&HashMap::new(), "de"), None);
+    }
+
+    #[test]
+    fn test_audit() {
+        let mut audit = LangAudit::default();
+        let docs = [
+            doc("fr", &[Some("fr"), Some("en"), None]),
+            doc("fr", &[Some("en"), Some("en"), Some("fr")]),
+            doc("fr", &[None, None]),
+        ];
+        let corpus: Vec<String> = docs.iter().map(|doc| doc.to_string()).collect();
+        let flagged = Mutex::new(Vec::new());
+        audit
+            .add_docs(corpus.join("\n").as_bytes(), Some(&flagged))
+            .unwrap();
+
+        let flagged = String::from_utf8(flagged.into_inner().unwrap()).unwrap();
+        assert_eq!(flagged, format!("{}\n", corpus[1]));
+
+        let report = audit.to_json();
+        assert_eq!(report["docs"], 3);
+        assert_eq!(report["flagged_docs"], 1);
+        let fr = &report["labels"][0];
+        assert_eq!(fr["label"], "fr");
+        assert_eq!(fr["lines"], 8);
+        assert_eq!(fr["line_labels"][0]["label"], "en");
+        assert_eq!(fr["line_labels"][0]["lines"], 3);
+        assert_eq!(fr["flagged_majority_labels"]["en"], 1);
+
+        let mut merged = audit.clone();
+        merged.merge(&audit);
+        assert_eq!(merged.to_json()["flagged_docs"], 2);
+    }
+}
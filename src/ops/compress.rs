@@ -5,12 +5,15 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use flate2::{write::GzEncoder, Compression};
+use flate2::{read::MultiGzDecoder, write::GzEncoder, Compression};
 use log::{debug, error, info, warn};
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use walkdir::WalkDir;
 
-use crate::error::Error;
+use crate::{
+    error::Error,
+    ops::{is_corpus_file, write_atomically, Progress},
+};
 
 const COMPRESSED_FILE_EXTS: [&'static str; 2] = ["gz", "zst"];
 
@@ -19,6 +22,7 @@ pub trait Compress {
     /// If `del_src` is set to `true`, removes the file at `src` upon compression completion.
     ///
     /// `src` has to exist and be a file, and `dst` should not exist.
+    /// The compressed file is written atomically (see [write_atomically]).
     fn compress_file(
         src: &Path,
         dst: &Path,
@@ -70,8 +74,12 @@ pub trait Compress {
             )
             .into());
         }
-        let mut dest_file = File::create(dst)?;
-        compress(&mut dest_file, src_file, compression)?;
+        write_atomically(&dst, |temp| {
+            let mut dest_file = File::create(temp)?;
+            compress(&mut dest_file, src_file, compression)?;
+            dest_file.sync_all()?;
+            Ok(())
+        })?;
 
         if del_src {
             info!("removing {:?}", src);
@@ -84,12 +92,16 @@ pub trait Compress {
     /// Recursively compresses files in provided folder.
     /// If `del_src` is set to `true`, removes the compressed files at `src` upon compression completion.
     /// `src` has to exist and be a folder
+    ///
+    /// Compressed files are recorded in a progress manifest (see [crate::ops::Progress]).
+    /// If `resume` is set, files completed by a previous run are skipped.
     fn compress_folder(
         src: &Path,
         dst: &Path,
         del_src: bool,
         compression: &str,
         num_threads: usize,
+        resume: bool,
     ) -> Result<(), Error> {
         if num_threads != 1 {
             rayon::ThreadPoolBuilder::new()
@@ -101,11 +113,12 @@ pub trait Compress {
         if !dst.exists() {
             std::fs::create_dir(dst)?;
         }
+        let progress = Progress::open(dst, resume)?;
 
         let files_paths: Vec<walkdir::DirEntry> = WalkDir::new(src)
             .into_iter()
             .filter_map(Result::ok)
-            .filter(|e| e.file_type().is_file())
+            .filter(|e| is_corpus_file(e.path()))
             .collect();
 
         let folders_to_create = WalkDir::new(src)
@@ -140,11 +153,33 @@ pub trait Compress {
                         return Err(Error::StripPrefixError(e));
                     }
                 };
-                Self::compress_file(&file_path, &dst_file_path, del_src, compression)
+                if progress.is_completed(&file_path, src) {
+                    info!("skipping {:?}: already compressed", file_path);
+                    return Ok(());
+                }
+                match Self::compress_file(&file_path, &dst_file_path, del_src, compression) {
+                    // renamed, but not recorded before the previous run was interrupted
+                    Err(Error::Io(e))
+                        if resume && e.kind() == std::io::ErrorKind::AlreadyExists =>
+                    {
+                        warn!("{:?} has already been compressed", file_path);
+                        if del_src {
+                            std::fs::remove_file(&file_path)?;
+                        }
+                    }
+                    result => result?,
+                }
+                progress.complete(&file_path, src)
             })
             .collect();
+        let mut has_errors = false;
         for result in results.into_iter().filter(|r| r.is_err()) {
             error!("{:?}", result);
+            has_errors = true;
+        }
+        // keep track of the failed files, so that they can be retried with `resume`
+        if !has_errors {
+            progress.finish()?;
         }
         Ok(())
     }
@@ -154,7 +189,11 @@ pub trait Compress {
 /// Consumes the whole reader.
 // TODO: should it be inside the compress trait?
 // TODO: merge compress_gzip and compress_zstd?
-fn compress<T: Read>(dest_file: &mut impl Write, r: T, compression: &str) -> Result<(), Error> {
+pub(crate) fn compress<T: Read>(
+    dest_file: &mut impl Write,
+    r: T,
+    compression: &str,
+) -> Result<(), Error> {
     match compression {
         "gzip" => compress_gzip(dest_file, r)?,
         #[cfg(feature="zstd")]
@@ -164,6 +203,39 @@ fn compress<T: Read>(dest_file: &mut impl Write, r: T, compression: &str) -> Res
     Ok(())
 }
 
+/// `true` if `path` has one of the [COMPRESSED_FILE_EXTS] extensions.
+pub(crate) fn is_compressed(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| COMPRESSED_FILE_EXTS.contains(&ext))
+}
+
+/// Open a corpus file for reading, decompressing it if its extension is one of [COMPRESSED_FILE_EXTS].
+pub(crate) fn open_decompressed(path: &Path) -> Result<Box<dyn BufRead>, Error> {
+    decompressed(File::open(path)?, path)
+}
+
+/// Read `f`, the content of the file at `path`, decompressing it if `path` has one of the [COMPRESSED_FILE_EXTS] extensions.
+pub(crate) fn decompressed<'a, R: Read + 'a>(
+    f: R,
+    path: &Path,
+) -> Result<Box<dyn BufRead + 'a>, Error> {
+    let r: Box<dyn BufRead + 'a> = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("gz") => Box::new(BufReader::new(MultiGzDecoder::new(f))),
+        #[cfg(feature = "zstd")]
+        Some("zst") => Box::new(BufReader::new(zstd::Decoder::new(f)?)),
+        #[cfg(not(feature = "zstd"))]
+        Some("zst") => {
+            return Err(Error::Custom(format!(
+                "{:?} is zstd-compressed: enable the zstd feature to read it.",
+                path
+            )))
+        }
+        _ => Box::new(BufReader::new(f)),
+    };
+    Ok(r)
+}
+
 /// compress using GZip
 fn compress_gzip<T: Read>(dest_file: &mut impl Write, r: T) -> Result<(), Error> {
     let mut b = BufReader::new(r);
@@ -207,9 +279,30 @@ mod test {
 
     #[cfg(feature = "zstd")]
     use crate::ops::compress::compress_zstd;
-    use crate::ops::Compress;
+    use crate::ops::{Compress, PROGRESS_FILE};
 
-    use super::compress;
+    use super::{compress, open_decompressed};
+
+    #[test]
+    fn test_open_decompressed() {
+        let dir = tempdir().unwrap();
+        let content = "foo\nbar\n";
+
+        let plain = dir.path().join("foo.jsonl");
+        std::fs::write(&plain, content).unwrap();
+        let gzipped = dir.path().join("foo.jsonl.gz");
+        let mut f = File::create(&gzipped).unwrap();
+        compress(&mut f, content.as_bytes(), "gzip").unwrap();
+
+        for path in [plain, gzipped] {
+            let mut read = String::new();
+            open_decompressed(&path)
+                .unwrap()
+                .read_to_string(&mut read)
+                .unwrap();
+            assert_eq!(read, content);
+        }
+    }
 
     #[test]
     fn test_compress() {
@@ -258,6 +351,35 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_compress_folder_resume() {
+        struct Dummy;
+        impl Compress for Dummy {}
+
+        let src = tempdir().unwrap();
+        let dst = tempdir().unwrap();
+        for lang in ["de", "en", "fr"] {
+            std::fs::write(src.path().join(format!("{lang}.txt")), lang).unwrap();
+        }
+        // interrupted run: de is done, fr is half written
+        std::fs::write(dst.path().join(PROGRESS_FILE), "de.txt\n").unwrap();
+        std::fs::write(dst.path().join("fr.txt.gz.tmp"), "garbage").unwrap();
+
+        Dummy::compress_folder(src.path(), dst.path(), false, "gzip", 1, true).unwrap();
+        assert!(!dst.path().join("de.txt.gz").exists());
+        for lang in ["en", "fr"] {
+            let mut read = String::new();
+            open_decompressed(&dst.path().join(format!("{lang}.txt.gz")))
+                .unwrap()
+                .read_to_string(&mut read)
+                .unwrap();
+            assert_eq!(read, lang);
+        }
+        assert!(!dst.path().join("fr.txt.gz.tmp").exists());
+        // done
+        assert!(!dst.path().join(PROGRESS_FILE).exists());
+    }
+
     #[test]
     fn test_dst_exists() {
         struct Dummy;
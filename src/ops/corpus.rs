@@ -0,0 +1,337 @@
+/*! Corpus layout.
+
+ Corpora are single files, or folders of languages (as created by [crate::ops::Split::split_all]):
+ subfolders hold the parts of a language, and files at the root of the folder are languages of their own.
+ Checksum, index and progress files live next to corpus files, and are skipped.
+!*/
+use std::{
+    ffi::OsStr,
+    io::BufRead,
+    path::{Path, PathBuf},
+};
+
+use log::{debug, error, warn};
+use rayon::prelude::*;
+
+use crate::{
+    error::Error,
+    ops::{is_progress_file, open_decompressed, sort_parts, INDEX_EXTENSION, KEYS_EXTENSION},
+};
+
+/// List the corpus files of `src` in part order. If `src` is a file, it is the only corpus file.
+///
+/// Checksum, index and progress files are ignored.
+pub(crate) fn corpus_files(src: &Path) -> Result<Vec<PathBuf>, Error> {
+    if src.is_file() {
+        return Ok(vec![src.to_path_buf()]);
+    }
+
+    let mut files = Vec::new();
+    for file in std::fs::read_dir(src)? {
+        let file = file?.path();
+        if is_corpus_file(&file) {
+            files.push(file);
+        }
+    }
+    sort_parts(&mut files);
+    Ok(files)
+}
+
+/// Documents of the corpus files of `src` (see [corpus_files]), read in part order.
+///
+/// Unlike [oscar_io::oscar_doc::SplitFolderReader], checksum, index and progress files are skipped,
+/// and compressed parts are decompressed.
+pub(crate) struct CorpusReader {
+    files: std::vec::IntoIter<PathBuf>,
+    current: Option<oscar_io::oscar_doc::Reader<Box<dyn BufRead>>>,
+}
+
+impl CorpusReader {
+    pub fn new(src: &Path) -> Result<Self, Error> {
+        let files = corpus_files(src)?;
+        if files.is_empty() {
+            return Err(Error::Custom(format!("No files found in {:?}", src)));
+        }
+        Ok(Self {
+            files: files.into_iter(),
+            current: None,
+        })
+    }
+}
+
+impl Iterator for CorpusReader {
+    type Item = Result<oscar_io::oscar_doc::Document, oscar_io::error::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(doc) = self.current.as_mut().and_then(Iterator::next) {
+                return Some(doc);
+            }
+            let file = self.files.next()?;
+            debug!("reading {:?}", file);
+            match open_decompressed(&file) {
+                Ok(r) => self.current = Some(oscar_io::oscar_doc::Reader::new(r)),
+                Err(Error::Io(e)) => return Some(Err(e.into())),
+                Err(e) => return Some(Err(oscar_io::error::Error::Custom(format!("{:?}", e)))),
+            }
+        }
+    }
+}
+
+/// Whether `path` is a corpus file, rather than a folder or a checksum, index or progress file.
+#[inline]
+pub(crate) fn is_corpus_file(path: &Path) -> bool {
+    path.is_file()
+        && path.extension() != Some(OsStr::new("sha384"))
+        && path.extension() != Some(OsStr::new(INDEX_EXTENSION))
+        && path.extension() != Some(OsStr::new(KEYS_EXTENSION))
+        && !is_progress_file(path)
+}
+
+/// List the languages of a corpus folder, along with their files in part order.
+///
+/// Subfolders are languages (as created by [crate::ops::Split::split_all]) and hold one or more parts.
+/// Files at the root of `src` are languages of their own, named after their file name without extensions.
+/// Checksum, index and progress files are ignored.
+pub(crate) fn language_parts(src: &Path) -> Result<Vec<(String, Vec<PathBuf>)>, Error> {
+    let mut languages = Vec::new();
+    for entry in std::fs::read_dir(src)? {
+        let path = entry?.path();
+        let lang = match path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.split('.').next())
+        {
+            Some(lang) => lang.to_string(),
+            None => {
+                warn!("{:?} has no valid file name: ignoring", path);
+                continue;
+            }
+        };
+
+        // leftovers of interrupted runs
+        if is_progress_file(&path) {
+            continue;
+        }
+        if path.is_dir() {
+            let files = corpus_files(&path)?;
+            if files.is_empty() {
+                warn!("{:?} has no files: ignoring", path);
+                continue;
+            }
+            languages.push((lang, files));
+        } else if is_corpus_file(&path) {
+            languages.push((lang, vec![path]));
+        }
+    }
+
+    languages.sort_unstable();
+    Ok(languages)
+}
+
+/// List the languages of a corpus file or folder (see [language_parts]).
+///
+/// A corpus file is a language of its own, named after its file name without extensions.
+pub(crate) fn source_languages(src: &Path) -> Result<Vec<(String, Vec<PathBuf>)>, Error> {
+    if !src.is_file() {
+        return language_parts(src);
+    }
+    let lang = src
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.split('.').next())
+        .ok_or_else(|| Error::Custom(format!("invalid file name: {:?}", src)))?;
+    Ok(vec![(lang.to_string(), vec![src.to_path_buf()])])
+}
+
+/// Path of `file` (in `src`) mirrored into `dst`, replacing the extension(s) of `file` by `ext`.
+///
+/// `src/fr/fr_part_1.jsonl.gz` is mirrored into `dst/fr/fr_part_1.<ext>`.
+pub(crate) fn mirrored_path(file: &Path, src: &Path, dst: &Path, ext: &str) -> PathBuf {
+    let relative = file.strip_prefix(src).unwrap_or(file);
+    let stem = relative
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.split('.').next())
+        .unwrap_or_default();
+    dst.join(relative).with_file_name(format!("{stem}.{ext}"))
+}
+
+/// Build the global thread pool with `num_threads` threads (all available if `0`), unless `num_threads` is `1`.
+///
+/// The global pool can only be built once per process: if it already exists, it is kept and a warning is logged.
+pub(crate) fn build_thread_pool(num_threads: usize) {
+    if num_threads == 1 {
+        return;
+    }
+    match rayon::ThreadPoolBuilder::new()
+        .num_threads(num_threads)
+        .build_global()
+    {
+        Ok(()) => debug!("Built rayon threadpool with num_threads={num_threads}"),
+        Err(e) => warn!("could not build a thread pool of {num_threads} threads: {e}"),
+    }
+}
+
+/// Run `op` on each of `items` in parallel, returning the results in order.
+///
+/// Errors are logged along with the name of their item, and fail the run once every item is done.
+fn run_parallel<T, R, F>(
+    items: &[T],
+    name: impl Fn(&T) -> String,
+    num_threads: usize,
+    op_name: &str,
+    op: F,
+) -> Result<Vec<R>, Error>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> Result<R, Error> + Sync,
+{
+    build_thread_pool(num_threads);
+    let results: Vec<Result<R, Error>> = items.par_iter().map(&op).collect();
+
+    let mut outputs = Vec::with_capacity(items.len());
+    let mut has_errors = false;
+    for (item, result) in items.iter().zip(results) {
+        match result {
+            Ok(output) => outputs.push(output),
+            Err(e) => {
+                error!("error while {op_name} {}: {:?}", name(item), e);
+                has_errors = true;
+            }
+        }
+    }
+    if has_errors {
+        return Err(Error::Custom(format!(
+            "Error(s) during {op_name}. Check logs."
+        )));
+    }
+    Ok(outputs)
+}
+
+/// Run `op` on each language (see [language_parts]) in parallel, returning the results in order.
+///
+/// The global thread pool is built with `num_threads` threads beforehand (see [build_thread_pool]).
+pub(crate) fn for_each_language<R, F>(
+    languages: &[(String, Vec<PathBuf>)],
+    num_threads: usize,
+    op_name: &str,
+    op: F,
+) -> Result<Vec<R>, Error>
+where
+    R: Send,
+    F: Fn(&str, &[PathBuf]) -> Result<R, Error> + Sync,
+{
+    if languages.is_empty() {
+        return Err(Error::Custom("No languages found".to_string()));
+    }
+    run_parallel(
+        languages,
+        |(lang, _)| lang.clone(),
+        num_threads,
+        op_name,
+        |(lang, files)| op(lang, files),
+    )
+}
+
+/// Run `op` on each corpus file in parallel, returning the results in order.
+///
+/// See [for_each_language] for `num_threads`.
+pub(crate) fn for_each_file<R, F>(
+    files: &[PathBuf],
+    num_threads: usize,
+    op_name: &str,
+    op: F,
+) -> Result<Vec<R>, Error>
+where
+    R: Send,
+    F: Fn(&Path) -> Result<R, Error> + Sync,
+{
+    if files.is_empty() {
+        return Err(Error::Custom("No files found".to_string()));
+    }
+    run_parallel(
+        files,
+        |file| format!("{:?}", file),
+        num_threads,
+        op_name,
+        |file| op(file),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::File,
+        path::{Path, PathBuf},
+    };
+
+    use crate::error::Error;
+
+    use super::{for_each_file, language_parts, mirrored_path, source_languages};
+
+    #[test]
+    fn test_language_parts() {
+        let corpus = tempfile::tempdir().unwrap();
+        let fr = corpus.path().join("fr");
+        std::fs::create_dir(&fr).unwrap();
+        for name in [
+            "fr_part_10.txt",
+            "fr_part_2.txt",
+            "checksum.sha384",
+            "fr_part_2.txt.idx",
+        ] {
+            File::create(fr.join(name)).unwrap();
+        }
+        File::create(corpus.path().join("en.txt")).unwrap();
+        File::create(corpus.path().join("en.txt.idx")).unwrap();
+        File::create(corpus.path().join("de.txt.gz")).unwrap();
+
+        let languages = language_parts(corpus.path()).unwrap();
+        let expected = vec![
+            ("de".to_string(), vec![corpus.path().join("de.txt.gz")]),
+            ("en".to_string(), vec![corpus.path().join("en.txt")]),
+            (
+                "fr".to_string(),
+                vec![fr.join("fr_part_2.txt"), fr.join("fr_part_10.txt")],
+            ),
+        ];
+        assert_eq!(languages, expected);
+        assert_eq!(
+            source_languages(&corpus.path().join("de.txt.gz")).unwrap(),
+            expected[..1]
+        );
+    }
+
+    #[test]
+    fn test_mirrored_path() {
+        let src = Path::new("corpus");
+        let dst = Path::new("out");
+        assert_eq!(
+            mirrored_path(&src.join("fr/fr_part_1.jsonl.gz"), src, dst, "parquet"),
+            Path::new("out/fr/fr_part_1.parquet")
+        );
+        assert_eq!(
+            mirrored_path(&src.join("de.txt"), src, dst, "jsonl"),
+            Path::new("out/de.jsonl")
+        );
+    }
+
+    #[test]
+    fn test_for_each_file() {
+        let files: Vec<PathBuf> = ["a", "b", "c"].iter().map(PathBuf::from).collect();
+        let names = for_each_file(&files, 1, "reading", |file| {
+            Ok(file.to_string_lossy().to_uppercase())
+        })
+        .unwrap();
+        assert_eq!(names, ["A", "B", "C"]);
+
+        let result = for_each_file(&files, 1, "reading", |file| match file.to_str() {
+            Some("b") => Err(Error::Custom("b".to_string())),
+            _ => Ok(()),
+        });
+        assert!(result.is_err());
+        assert!(for_each_file(&[], 1, "reading", |_| Ok(())).is_err());
+    }
+}
@@ -0,0 +1,316 @@
+//! Decontamination against a reference set (usually evaluation benchmarks).
+//!
+//! Reference items are indexed by line and by word n-gram windows, and corpus content matching any of them
+//! is dropped (or flagged, if the corpus version supports it).
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    hash::Hasher,
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+use log::info;
+use serde_json::json;
+use twox_hash::XxHash64;
+
+use crate::{
+    error::Error,
+    ops::{DigestSet, Normalization, TextUnit},
+};
+
+pub trait Decontaminate {
+    /// Remove content of `src` that matches `references`, and write the rest in `dst`.
+    /// If `flag` is set, matching content is kept but flagged instead.
+    ///
+    /// Hits are recorded in `references`, see [ReferenceSet::write_report].
+    fn decontaminate(
+        src: &Path,
+        dst: &Path,
+        references: &mut ReferenceSet,
+        flag: bool,
+    ) -> Result<(), Error>;
+}
+
+/// A reference item, located by its source file and (first) line number.
+#[derive(Debug)]
+struct ReferenceItem {
+    source: String,
+    line: usize,
+    hits: u64,
+}
+
+/// Index of reference items.
+///
+/// Every line of an item is indexed, and if `ngram_size` is set, so are the word n-gram windows of the item.
+/// Both reference items and checked text are normalized with `normalization` before hashing.
+#[derive(Debug, Default)]
+pub(crate) struct ReferenceSet {
+    items: Vec<ReferenceItem>,
+    lines: HashMap<u64, Vec<usize>>,
+    ngrams: HashMap<u64, Vec<usize>>,
+    ngram_size: Option<usize>,
+    normalization: Normalization,
+    nb_checked: u64,
+    nb_matched: u64,
+}
+
+impl ReferenceSet {
+    pub fn new(ngram_size: Option<usize>, normalization: Normalization) -> Self {
+        Self {
+            ngram_size,
+            normalization,
+            ..Default::default()
+        }
+    }
+
+    /// Build a reference set from reference files.
+    pub fn from_paths(
+        paths: &[PathBuf],
+        unit: TextUnit,
+        ngram_size: Option<usize>,
+        normalization: Normalization,
+    ) -> Result<Self, Error> {
+        let mut references = Self::new(ngram_size, normalization);
+        for path in paths {
+            info!("loading references from {:?}", path);
+            let r = BufReader::new(File::open(path)?);
+            references.load(&path.to_string_lossy(), r, unit)?;
+        }
+        info!(
+            "loaded {} reference items ({} lines, {} n-grams)",
+            references.items.len(),
+            references.lines.len(),
+            references.ngrams.len()
+        );
+        Ok(references)
+    }
+
+    /// Load reference items from a reader. `source` is used to locate items in the report.
+    pub fn load<R: BufRead>(&mut self, source: &str, r: R, unit: TextUnit) -> Result<(), Error> {
+        let mut item = String::new();
+        let mut item_line = 0;
+        for (line_number, line) in r.lines().enumerate() {
+            let line = line?;
+            match unit {
+                TextUnit::Line => {
+                    if !line.trim().is_empty() {
+                        self.add_item(source, line_number + 1, &line);
+                    }
+                }
+                TextUnit::Doc => {
+                    if line.trim().is_empty() {
+                        if !item.is_empty() {
+                            self.add_item(source, item_line, &item);
+                            item.clear();
+                        }
+                    } else {
+                        if item.is_empty() {
+                            item_line = line_number + 1;
+                        }
+                        item.push_str(&line);
+                        item.push('\n');
+                    }
+                }
+            }
+        }
+        if !item.is_empty() {
+            self.add_item(source, item_line, &item);
+        }
+        Ok(())
+    }
+
+    /// Index an item.
+    pub fn add_item(&mut self, source: &str, line: usize, text: &str) {
+        let idx = self.items.len();
+        self.items.push(ReferenceItem {
+            source: source.to_string(),
+            line,
+            hits: 0,
+        });
+
+        for digest in self.line_digests(text) {
+            Self::insert(&mut self.lines, digest, idx);
+        }
+        if let Some(n) = self.ngram_size {
+            let normalized = self.normalization.normalize(text);
+            for digest in ngram_digests(&normalized, n) {
+                Self::insert(&mut self.ngrams, digest, idx);
+            }
+        }
+    }
+
+    /// Digests of the non-empty lines of `text`, normalized one by one.
+    fn line_digests(&self, text: &str) -> Vec<u64> {
+        text.lines()
+            .filter_map(|line| {
+                let line = self.normalization.normalize(line);
+                let line = line.trim();
+                if line.is_empty() {
+                    None
+                } else {
+                    Some(DigestSet::hash(line.as_bytes()))
+                }
+            })
+            .collect()
+    }
+
+    #[inline]
+    fn insert(index: &mut HashMap<u64, Vec<usize>>, digest: u64, idx: usize) {
+        let items = index.entry(digest).or_default();
+        if items.last() != Some(&idx) {
+            items.push(idx);
+        }
+    }
+
+    /// Check if `text` matches a reference item, either by one of its lines or by one of its n-grams.
+    /// Matched items get their hit count increased.
+    pub fn check(&mut self, text: &str) -> bool {
+        let mut hits: HashSet<usize> = HashSet::new();
+        for digest in self.line_digests(text) {
+            if let Some(items) = self.lines.get(&digest) {
+                hits.extend(items);
+            }
+        }
+        if let Some(n) = self.ngram_size {
+            let normalized = self.normalization.normalize(text);
+            for digest in ngram_digests(&normalized, n) {
+                if let Some(items) = self.ngrams.get(&digest) {
+                    hits.extend(items);
+                }
+            }
+        }
+
+        self.nb_checked += 1;
+        if hits.is_empty() {
+            false
+        } else {
+            self.nb_matched += 1;
+            for idx in hits {
+                self.items[idx].hits += 1;
+            }
+            true
+        }
+    }
+
+    /// Number of checked (resp. matched) corpus entries.
+    pub fn stats(&self) -> (u64, u64) {
+        (self.nb_checked, self.nb_matched)
+    }
+
+    /// Write a JSON report of the reference items that have been hit.
+    pub fn write_report<W: Write>(&self, w: &mut W) -> Result<(), Error> {
+        let hit_items: Vec<_> = self
+            .items
+            .iter()
+            .filter(|item| item.hits > 0)
+            .map(|item| json!({"source": item.source, "line": item.line, "hits": item.hits}))
+            .collect();
+        let report = json!({
+            "checked": self.nb_checked,
+            "matched": self.nb_matched,
+            "reference_items": self.items.len(),
+            "hit_items": hit_items.len(),
+            "hits": hit_items,
+        });
+        serde_json::to_writer_pretty(&mut *w, &report)?;
+        writeln!(w)?;
+        w.flush()?;
+        Ok(())
+    }
+}
+
+/// Digests of the whitespace-separated word n-grams of `text`.
+/// Yields nothing if `text` has less than `n` words.
+fn ngram_digests(text: &str, n: usize) -> impl Iterator<Item = u64> + '_ {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let nb_windows = if n == 0 {
+        0
+    } else {
+        (words.len() + 1).saturating_sub(n)
+    };
+    (0..nb_windows).map(move |start| {
+        let mut hasher = XxHash64::default();
+        for word in &words[start..start + n] {
+            hasher.write(word.as_bytes());
+            hasher.write_u8(b' ');
+        }
+        hasher.finish()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ngram_digests, ReferenceSet};
+    use crate::ops::{Normalization, TextUnit};
+
+    #[test]
+    fn test_ngram_digests() {
+        assert_eq!(ngram_digests("a b c d", 2).count(), 3);
+        assert_eq!(ngram_digests("a b", 3).count(), 0);
+
+        // windows don't depend on whitespace
+        let a: Vec<u64> = ngram_digests("a  b\nc", 3).collect();
+        let b: Vec<u64> = ngram_digests("a b c", 3).collect();
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_check_lines() {
+        let references = "What is the capital of France?\n\nWho wrote Hamlet?\n";
+        let mut set = ReferenceSet::new(None, Normalization::default());
+        set.load("bench.txt", references.as_bytes(), TextUnit::Line)
+            .unwrap();
+        assert_eq!(set.items.len(), 2);
+
+        assert!(set.check("  Who wrote Hamlet?"));
+        assert!(!set.check("Who wrote Macbeth?"));
+        assert_eq!(set.stats(), (2, 1));
+        assert_eq!(set.items[1].line, 3);
+        assert_eq!(set.items[1].hits, 1);
+    }
+
+    #[test]
+    fn test_check_ngrams() {
+        let references = "the quick brown fox\njumps over the lazy dog\n\nsomething else entirely";
+        let mut set = ReferenceSet::new(Some(4), Normalization::default());
+        set.load("bench.txt", references.as_bytes(), TextUnit::Doc)
+            .unwrap();
+        assert_eq!(set.items.len(), 2);
+
+        // n-gram spans over the item's lines
+        assert!(set.check("I saw a brown fox jumps over yesterday"));
+        assert!(!set.check("a brown fox jumped"));
+        assert_eq!(set.items[0].hits, 1);
+        assert_eq!(set.items[1].hits, 0);
+    }
+
+    #[test]
+    fn test_report() {
+        let mut set = ReferenceSet::new(None, Normalization::default());
+        set.add_item("bench.txt", 1, "foo");
+        set.add_item("bench.txt", 2, "bar");
+        set.check("foo");
+        set.check("foo");
+
+        let mut report = Vec::new();
+        set.write_report(&mut report).unwrap();
+        let report: serde_json::Value = serde_json::from_slice(&report).unwrap();
+        assert_eq!(report["checked"], 2);
+        assert_eq!(report["matched"], 2);
+        assert_eq!(report["hit_items"], 1);
+        assert_eq!(report["hits"][0]["line"], 1);
+        assert_eq!(report["hits"][0]["hits"], 2);
+    }
+
+    #[test]
+    fn test_check_normalized() {
+        let mut set = ReferenceSet::new(Some(3), Normalization::all());
+        set.add_item("bench.txt", 1, "Who wrote Hamlet?");
+        set.add_item("bench.txt", 2, "The capital of France is Paris.");
+
+        assert!(set.check("WHO  wrote hamlet"));
+        assert!(set.check("so, the Capital of FRANCE..."));
+        assert!(!set.check("who wrote"));
+    }
+}
@@ -1,10 +1,221 @@
 //! Deduplication.
 //! Multiple deduplications could be implemented.
 
-use std::path::Path;
+use std::{
+    collections::HashSet,
+    hash::Hasher,
+    io::{Read, Write},
+    path::Path,
+    str::FromStr,
+};
 
-use crate::error::Error;
+use runiq::filters::Filter;
+use twox_hash::XxHash64;
+
+use crate::{error::Error, ops::Normalization};
 
 pub trait Dedup {
     fn dedup(&mut self, src: &Path, dst: &Path) -> Result<(), Error>;
 }
+
+/// Filter sharing when deduplicating a corpus folder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DedupScope {
+    /// One filter per language, shared by all of its parts.
+    Lang,
+    /// One filter shared by all the languages of the corpus.
+    Global,
+}
+
+impl FromStr for DedupScope {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lang" => Ok(Self::Lang),
+            "global" => Ok(Self::Global),
+            x => Err(Error::Custom(format!(
+                "Unknown dedup scope {x} (expected lang or global)"
+            ))),
+        }
+    }
+}
+
+/// Unit of text that is hashed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextUnit {
+    /// Each non-empty line.
+    #[default]
+    Line,
+    /// Each blank-line separated block of lines (paragraph or document).
+    Doc,
+}
+
+impl FromStr for TextUnit {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "line" => Ok(Self::Line),
+            "doc" => Ok(Self::Doc),
+            x => Err(Error::Custom(format!(
+                "Unknown text unit {x} (expected line or doc)"
+            ))),
+        }
+    }
+}
+
+/// Start of saved [DigestSet] states, followed by a byte of normalization steps.
+const STATE_MAGIC: &[u8; 7] = b"OSCDDP1";
+
+/// Set of digests of already seen entries.
+///
+/// Works like runiq's [runiq::filters::DigestFilter] (entries are hashed with XxHash64),
+/// but its state can be saved to and loaded from disk,
+/// so that a corpus can be deduplicated against previously processed ones.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct DigestSet {
+    inner: HashSet<u64>,
+}
+
+impl DigestSet {
+    /// Hash an entry. Hashes are stable across runs and platforms.
+    #[inline]
+    pub fn hash(input: &[u8]) -> u64 {
+        let mut hasher = XxHash64::default();
+        hasher.write(input);
+        hasher.finish()
+    }
+
+    /// Number of distinct entries seen.
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Load a state written by [DigestSet::save].
+    ///
+    /// Fails if the digests were computed on text normalized otherwise than with `normalization`,
+    /// since none of them could match.
+    pub fn load<R: Read>(r: &mut R, normalization: Normalization) -> Result<Self, Error> {
+        let mut header = [0; 8];
+        r.read_exact(&mut header)?;
+        if &header[..7] != STATE_MAGIC {
+            return Err(Error::Custom("not a dedup state".to_string()));
+        }
+        let saved = Normalization::from_bits(header[7]);
+        if saved != normalization {
+            return Err(Error::Custom(format!(
+                "dedup state was computed with {saved:?}, not with {normalization:?}"
+            )));
+        }
+        let mut bytes = Vec::new();
+        r.read_to_end(&mut bytes)?;
+        if bytes.len() % 8 != 0 {
+            return Err(Error::Custom(
+                "Malformed dedup state: size is not a multiple of 8 bytes".to_string(),
+            ));
+        }
+        let inner = bytes
+            .chunks_exact(8)
+            .map(|digest| u64::from_le_bytes(digest.try_into().unwrap()))
+            .collect();
+        Ok(Self { inner })
+    }
+
+    /// Write the state as a header recording `normalization` (the one of digested text),
+    /// followed by a sorted sequence of little-endian digests.
+    pub fn save<W: Write>(&self, w: &mut W, normalization: Normalization) -> Result<(), Error> {
+        w.write_all(STATE_MAGIC)?;
+        w.write_all(&[normalization.to_bits()])?;
+        let mut digests: Vec<&u64> = self.inner.iter().collect();
+        digests.sort_unstable();
+        for digest in digests {
+            w.write_all(&digest.to_le_bytes())?;
+        }
+        w.flush()?;
+        Ok(())
+    }
+}
+
+impl Filter for DigestSet {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    fn detect(&mut self, input: &[u8]) -> bool {
+        self.inner.insert(Self::hash(input))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use runiq::filters::Filter;
+
+    use crate::ops::Normalization;
+
+    use super::{DedupScope, DigestSet, TextUnit};
+
+    #[test]
+    fn test_detect() {
+        let mut filter = DigestSet::default();
+        assert!(filter.detect(b"foo"));
+        assert!(filter.detect(b"bar"));
+        assert!(!filter.detect(b"foo"));
+        assert_eq!(filter.len(), 2);
+    }
+
+    #[test]
+    fn test_save_load() {
+        let mut filter = DigestSet::default();
+        for line in ["foo", "bar", "baz"] {
+            filter.detect(line.as_bytes());
+        }
+
+        let mut state = Vec::new();
+        filter.save(&mut state, Normalization::default()).unwrap();
+        assert_eq!(state.len(), 8 + 3 * 8);
+
+        let mut loaded = DigestSet::load(&mut state.as_slice(), Normalization::default()).unwrap();
+        assert_eq!(loaded.len(), 3);
+        assert!(!loaded.detect(b"bar"));
+        assert!(loaded.detect(b"quux"));
+    }
+
+    #[test]
+    fn test_load_malformed() {
+        let state = [0u8; 12];
+        assert!(DigestSet::load(&mut state.as_slice(), Normalization::default()).is_err());
+
+        let mut state = Vec::new();
+        DigestSet::default()
+            .save(&mut state, Normalization::default())
+            .unwrap();
+        state.extend([0u8; 4]);
+        assert!(DigestSet::load(&mut state.as_slice(), Normalization::default()).is_err());
+    }
+
+    #[test]
+    fn test_load_other_normalization() {
+        let mut state = Vec::new();
+        DigestSet::default()
+            .save(&mut state, Normalization::all())
+            .unwrap();
+        assert!(DigestSet::load(&mut state.as_slice(), Normalization::all()).is_ok());
+        assert!(DigestSet::load(&mut state.as_slice(), Normalization::default()).is_err());
+    }
+
+    #[test]
+    fn test_scope_from_str() {
+        assert_eq!("lang".parse::<DedupScope>().unwrap(), DedupScope::Lang);
+        assert_eq!("global".parse::<DedupScope>().unwrap(), DedupScope::Global);
+        assert!("foo".parse::<DedupScope>().is_err());
+    }
+
+    #[test]
+    fn test_unit_from_str() {
+        assert_eq!("line".parse::<TextUnit>().unwrap(), TextUnit::Line);
+        assert_eq!("doc".parse::<TextUnit>().unwrap(), TextUnit::Doc);
+        assert!("foo".parse::<TextUnit>().is_err());
+    }
+}
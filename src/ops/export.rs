@@ -0,0 +1,265 @@
+/*! Export of OSCAR v2 corpora to columnar formats.
+
+ Exports keep the layout of the source (see [crate::ops::Split::split_all]): each corpus file `<lang>/<lang>_part_<n>.jsonl[.gz]`
+ is exported into `<lang>/<lang>_part_<n>.<format extension>`.
+
+ Parquet export needs the `parquet` feature.
+!*/
+use std::{
+    fs::File,
+    io::BufRead,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use log::info;
+
+use crate::{
+    error::Error,
+    ops::{for_each_file, language_parts, mirrored_path, open_decompressed, write_atomically},
+};
+
+/// Export formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// One row per document, with `content`, `warc_headers` (as a map) and `metadata` (as a struct) columns.
+    Parquet,
+}
+
+impl ExportFormat {
+    /// Names of the formats, as accepted by [ExportFormat::from_str].
+    pub const NAMES: [&'static str; 1] = ["parquet"];
+
+    /// Extension of exported files.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Parquet => "parquet",
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "parquet" => Ok(Self::Parquet),
+            other => Err(Error::Custom(format!(
+                "unknown export format {other:?} (expected one of {:?})",
+                Self::NAMES
+            ))),
+        }
+    }
+}
+
+/// Arrow schema of documents, following [oscar_io::oscar_doc::Document].
+#[cfg(feature = "parquet")]
+pub(crate) fn doc_schema() -> arrow::datatypes::SchemaRef {
+    use std::sync::Arc;
+
+    use arrow::datatypes::{DataType, Field, Fields, Schema};
+
+    let identification = DataType::Struct(Fields::from(vec![
+        Field::new("label", DataType::Utf8, false),
+        Field::new("prob", DataType::Float32, false),
+    ]));
+    let warc_headers = DataType::Map(
+        Arc::new(Field::new(
+            "entries",
+            DataType::Struct(Fields::from(vec![
+                Field::new("keys", DataType::Utf8, false),
+                Field::new("values", DataType::Utf8, true),
+            ])),
+            false,
+        )),
+        false,
+    );
+    let metadata = DataType::Struct(Fields::from(vec![
+        Field::new("identification", identification.clone(), false),
+        Field::new(
+            "annotation",
+            DataType::List(Arc::new(Field::new("item", DataType::Utf8, true))),
+            true,
+        ),
+        Field::new(
+            "sentence_identifications",
+            DataType::List(Arc::new(Field::new("item", identification, true))),
+            false,
+        ),
+    ]));
+    Arc::new(Schema::new(vec![
+        Field::new("content", DataType::Utf8, false),
+        Field::new("warc_headers", warc_headers, false),
+        Field::new("metadata", metadata, false),
+    ]))
+}
+
+/// Write the documents of `r` into a Parquet file, with row groups of at most `row_group_size` documents.
+/// Returns the number of exported documents.
+#[cfg(feature = "parquet")]
+pub(crate) fn export_parquet<R: BufRead>(
+    r: R,
+    w: File,
+    row_group_size: usize,
+) -> Result<usize, Error> {
+    use arrow::json::ReaderBuilder;
+    use parquet::{arrow::ArrowWriter, file::properties::WriterProperties};
+
+    let schema = doc_schema();
+    let props = WriterProperties::builder()
+        .set_max_row_group_size(row_group_size)
+        .build();
+    let mut writer = ArrowWriter::try_new(w, schema.clone(), Some(props))?;
+    let reader = ReaderBuilder::new(schema)
+        .with_batch_size(row_group_size)
+        .build(r)?;
+    let mut nb_docs = 0;
+    for batch in reader {
+        let batch = batch?;
+        nb_docs += batch.num_rows();
+        writer.write(&batch)?;
+    }
+    writer.close()?;
+    Ok(nb_docs)
+}
+
+pub trait Export {
+    /// Write the documents of `r` in `w` in the given `format`. Returns the number of exported documents.
+    #[cfg_attr(not(feature = "parquet"), allow(unused_variables))]
+    fn export_docs<R: BufRead>(
+        r: R,
+        w: File,
+        format: ExportFormat,
+        row_group_size: usize,
+    ) -> Result<usize, Error> {
+        match format {
+            #[cfg(feature = "parquet")]
+            ExportFormat::Parquet => export_parquet(r, w, row_group_size),
+            #[cfg(not(feature = "parquet"))]
+            ExportFormat::Parquet => Err(Error::Custom(
+                "Parquet export needs the parquet feature.".to_string(),
+            )),
+        }
+    }
+
+    /// Export a corpus file into the `dst` file (see [write_atomically]). Returns the number of exported documents.
+    fn export_file(
+        src: &Path,
+        dst: &Path,
+        format: ExportFormat,
+        row_group_size: usize,
+    ) -> Result<usize, Error> {
+        info!("exporting {:?} into {:?}", src, dst);
+        let r = open_decompressed(src)?;
+        write_atomically(dst, |temp| {
+            Self::export_docs(r, File::create(temp)?, format, row_group_size)
+        })
+    }
+
+    /// Export a corpus file into the `dst` file, or a corpus folder into the `dst` folder, keeping its layout.
+    fn export(
+        src: &Path,
+        dst: &Path,
+        format: ExportFormat,
+        row_group_size: usize,
+        num_threads: usize,
+    ) -> Result<(), Error> {
+        if src.is_file() {
+            let nb_docs = Self::export_file(src, dst, format, row_group_size)?;
+            info!("exported {nb_docs} documents");
+            return Ok(());
+        }
+
+        let files: Vec<PathBuf> = language_parts(src)?
+            .into_iter()
+            .flat_map(|(_, files)| files)
+            .collect();
+        let nb_docs: usize = for_each_file(&files, num_threads, "exporting", |file| {
+            let file_dst = mirrored_path(file, src, dst, format.extension());
+            if let Some(parent) = file_dst.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            Self::export_file(file, &file_dst, format, row_group_size)
+        })?
+        .into_iter()
+        .sum();
+        info!("exported {nb_docs} documents from {} files", files.len());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::ExportFormat;
+
+    #[test]
+    fn test_export_format() {
+        assert_eq!(
+            "parquet".parse::<ExportFormat>().unwrap(),
+            ExportFormat::Parquet
+        );
+        assert!("csv".parse::<ExportFormat>().is_err());
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn test_export_parquet() {
+        use arrow::array::{Array, AsArray, StructArray};
+        use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+        use serde_json::json;
+        use tempfile::tempdir;
+
+        use super::Export;
+
+        struct ExportTest;
+        impl Export for ExportTest {}
+
+        let docs: Vec<String> = (0..5)
+            .map(|i| {
+                json!({
+                    "content": format!("line {i}\nother line"),
+                    "warc_headers": {"warc-record-id": format!("<urn:uuid:{i}>")},
+                    "metadata": {
+                        "identification": {"label": "en", "prob": 0.5},
+                        "annotation": if i == 0 { json!(["tiny"]) } else { json!(null) },
+                        "sentence_identifications": [{"label": "en", "prob": 0.9}, null],
+                    },
+                })
+                .to_string()
+            })
+            .collect();
+        let dir = tempdir().unwrap();
+        let src = dir.path().join("src/en");
+        std::fs::create_dir_all(&src).unwrap();
+        std::fs::write(src.join("en_part_1.jsonl"), docs.join("\n") + "\n").unwrap();
+        let dst = dir.path().join("dst");
+        ExportTest::export(&dir.path().join("src"), &dst, ExportFormat::Parquet, 2, 1).unwrap();
+
+        let f = std::fs::File::open(dst.join("en/en_part_1.parquet")).unwrap();
+        let builder = ParquetRecordBatchReaderBuilder::try_new(f).unwrap();
+        assert_eq!(builder.metadata().num_row_groups(), 3);
+        let batches: Vec<_> = builder.build().unwrap().collect::<Result<_, _>>().unwrap();
+        let batch = &batches[0];
+        assert_eq!(
+            batch.column(0).as_string::<i32>().value(1),
+            "line 1\nother line"
+        );
+        let metadata = batch
+            .column(2)
+            .as_any()
+            .downcast_ref::<StructArray>()
+            .unwrap();
+        let annotation = metadata.column_by_name("annotation").unwrap();
+        assert!(annotation.is_valid(0));
+        assert!(annotation.is_null(1));
+        let ids = metadata
+            .column_by_name("sentence_identifications")
+            .unwrap()
+            .as_list::<i32>()
+            .value(0);
+        assert_eq!(ids.len(), 2);
+        assert!(ids.is_null(1));
+    }
+}
@@ -0,0 +1,302 @@
+/*! Extraction of document fields into side tables.
+
+ Fields are selected by JSON pointers (RFC 6901) into documents, such as `/warc_headers/warc-target-uri`
+ or `/metadata/identification/prob`. Missing fields are empty (`null` in JSONL).
+ Strings are written as is, and other values (numbers, annotation lists...) as JSON.
+
+ Extractions keep the layout of the source (see [crate::ops::Split::split_all]): each corpus file `<lang>/<lang>_part_<n>.jsonl[.gz]`
+ is extracted into `<lang>/<lang>_part_<n>.<format extension>`, with its own header row.
+!*/
+use std::{
+    fs::File,
+    io::{BufRead, BufWriter, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use log::info;
+use serde_json::{Map, Value};
+
+use crate::{
+    error::Error,
+    ops::{for_each_file, language_parts, mirrored_path, open_decompressed, write_atomically},
+};
+
+/// Table formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldsFormat {
+    /// Tab separated values, with tabs, newlines and backslashes escaped as `\t`, `\n` and `\\`.
+    Tsv,
+    /// Comma separated values, quoted following RFC 4180.
+    Csv,
+    /// One JSON object per document, keyed by pointer.
+    Jsonl,
+}
+
+impl FieldsFormat {
+    /// Names of the formats, as accepted by [FieldsFormat::from_str].
+    pub const NAMES: [&'static str; 3] = ["tsv", "csv", "jsonl"];
+
+    /// Extension of extracted files.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Tsv => "tsv",
+            Self::Csv => "csv",
+            Self::Jsonl => "jsonl",
+        }
+    }
+}
+
+impl FromStr for FieldsFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tsv" => Ok(Self::Tsv),
+            "csv" => Ok(Self::Csv),
+            "jsonl" => Ok(Self::Jsonl),
+            other => Err(Error::Custom(format!(
+                "unknown fields format {other:?} (expected one of {:?})",
+                Self::NAMES
+            ))),
+        }
+    }
+}
+
+/// Check that `pointers` are valid JSON pointers.
+pub fn check_pointers(pointers: &[String]) -> Result<(), Error> {
+    if pointers.is_empty() {
+        return Err(Error::Custom("No fields to extract".to_string()));
+    }
+    match pointers
+        .iter()
+        .find(|pointer| !pointer.is_empty() && !pointer.starts_with('/'))
+    {
+        Some(pointer) => Err(Error::Custom(format!(
+            "invalid field {pointer:?}: JSON pointers start with '/'"
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Text of a field, for TSV/CSV tables.
+fn cell(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(s)) => s.clone(),
+        Some(other) => other.to_string(),
+    }
+}
+
+fn escape_tsv(cell: &str) -> String {
+    let mut escaped = String::with_capacity(cell.len());
+    for c in cell.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn escape_csv(cell: &str) -> String {
+    if cell.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
+
+/// Row of `cells` in `format` (TSV or CSV), without its line ending.
+fn row<'a>(cells: impl Iterator<Item = &'a str>, format: FieldsFormat) -> String {
+    let (escape, separator): (fn(&str) -> String, &str) = match format {
+        FieldsFormat::Csv => (escape_csv, ","),
+        _ => (escape_tsv, "\t"),
+    };
+    cells.map(escape).collect::<Vec<String>>().join(separator)
+}
+
+/// Extracted line of `doc`.
+pub(crate) fn extract_line(doc: &Value, pointers: &[String], format: FieldsFormat) -> String {
+    match format {
+        FieldsFormat::Jsonl => {
+            let fields: Map<String, Value> = pointers
+                .iter()
+                .map(|pointer| {
+                    (
+                        pointer.clone(),
+                        doc.pointer(pointer).cloned().unwrap_or(Value::Null),
+                    )
+                })
+                .collect();
+            Value::Object(fields).to_string()
+        }
+        _ => {
+            let cells: Vec<String> = pointers
+                .iter()
+                .map(|pointer| cell(doc.pointer(pointer)))
+                .collect();
+            row(cells.iter().map(String::as_str), format)
+        }
+    }
+}
+
+pub trait ExtractFields {
+    /// Write the `pointers` fields of the documents of `r` in `w`, preceded by a header row for TSV/CSV.
+    /// Returns the number of documents.
+    fn extract_fields_docs<R: BufRead, W: Write>(
+        r: R,
+        w: &mut W,
+        pointers: &[String],
+        format: FieldsFormat,
+    ) -> Result<usize, Error> {
+        if format != FieldsFormat::Jsonl {
+            writeln!(w, "{}", row(pointers.iter().map(String::as_str), format))?;
+        }
+        let mut nb_docs = 0;
+        for line in r.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let doc: Value = serde_json::from_str(&line)?;
+            writeln!(w, "{}", extract_line(&doc, pointers, format))?;
+            nb_docs += 1;
+        }
+        Ok(nb_docs)
+    }
+
+    /// Extract the fields of a corpus file into the `dst` file (see [write_atomically]).
+    /// Returns the number of documents.
+    fn extract_fields_file(
+        src: &Path,
+        dst: &Path,
+        pointers: &[String],
+        format: FieldsFormat,
+    ) -> Result<usize, Error> {
+        info!("extracting fields of {:?} into {:?}", src, dst);
+        let r = open_decompressed(src)?;
+        write_atomically(dst, |temp| {
+            let mut w = BufWriter::new(File::create(temp)?);
+            let nb_docs = Self::extract_fields_docs(r, &mut w, pointers, format)?;
+            w.flush()?;
+            Ok(nb_docs)
+        })
+    }
+
+    /// Extract the fields of a corpus file into the `dst` file,
+    /// or of a corpus folder into the `dst` folder (files in parallel), keeping its layout.
+    fn extract_fields(
+        src: &Path,
+        dst: &Path,
+        pointers: &[String],
+        format: FieldsFormat,
+        num_threads: usize,
+    ) -> Result<(), Error> {
+        check_pointers(pointers)?;
+        if src.is_file() {
+            let nb_docs = Self::extract_fields_file(src, dst, pointers, format)?;
+            info!("extracted fields of {nb_docs} documents");
+            return Ok(());
+        }
+
+        let files: Vec<PathBuf> = language_parts(src)?
+            .into_iter()
+            .flat_map(|(_, files)| files)
+            .collect();
+        let nb_docs: usize = for_each_file(&files, num_threads, "extracting fields of", |file| {
+            let file_dst = mirrored_path(file, src, dst, format.extension());
+            if let Some(parent) = file_dst.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            Self::extract_fields_file(file, &file_dst, pointers, format)
+        })?
+        .into_iter()
+        .sum();
+        info!(
+            "extracted fields of {nb_docs} documents from {} files",
+            files.len()
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    use super::{check_pointers, extract_line, ExtractFields, FieldsFormat};
+
+    struct FieldsTest;
+    impl ExtractFields for FieldsTest {}
+
+    fn pointers() -> Vec<String> {
+        [
+            "/warc_headers/warc-target-uri",
+            "/metadata/annotation",
+            "/missing",
+        ]
+        .map(String::from)
+        .to_vec()
+    }
+
+    #[test]
+    fn test_extract_line() {
+        let doc = json!({
+            "content": "foo",
+            "warc_headers": {"warc-target-uri": "http://example.com/a,b\tc"},
+            "metadata": {"annotation": ["tiny", "short_sentences"]},
+        });
+        assert_eq!(
+            extract_line(&doc, &pointers(), FieldsFormat::Tsv),
+            "http://example.com/a,b\\tc\t[\"tiny\",\"short_sentences\"]\t"
+        );
+        assert_eq!(
+            extract_line(&doc, &pointers(), FieldsFormat::Csv),
+            "\"http://example.com/a,b\tc\",\"[\"\"tiny\"\",\"\"short_sentences\"\"]\","
+        );
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&extract_line(
+                &doc,
+                &pointers(),
+                FieldsFormat::Jsonl
+            ))
+            .unwrap(),
+            json!({
+                "/warc_headers/warc-target-uri": "http://example.com/a,b\tc",
+                "/metadata/annotation": ["tiny", "short_sentences"],
+                "/missing": null,
+            })
+        );
+        assert!(check_pointers(&["metadata".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_extract_fields() {
+        let dir = tempdir().unwrap();
+        let src = dir.path().join("src");
+        std::fs::create_dir_all(src.join("fr")).unwrap();
+        std::fs::write(
+            src.join("fr/fr_part_1.jsonl"),
+            "{\"metadata\":{\"identification\":{\"prob\":0.5}}}\n\n{\"metadata\":{}}\n",
+        )
+        .unwrap();
+        std::fs::write(src.join("de.jsonl"), "{}\n").unwrap();
+
+        let dst = dir.path().join("dst");
+        let pointers = vec!["/metadata/identification/prob".to_string()];
+        FieldsTest::extract_fields(&src, &dst, &pointers, FieldsFormat::Tsv, 1).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dst.join("fr/fr_part_1.tsv")).unwrap(),
+            "/metadata/identification/prob\n0.5\n\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(dst.join("de.tsv")).unwrap(),
+            "/metadata/identification/prob\n\n"
+        );
+    }
+}
@@ -1,43 +1,160 @@
 /*! Extracts textual content into new files, discarding metadata. Should produce an OSCAR v1 (2019) compatible corpus.
+
+Other layouts, as expected by tokenizer trainers, are available through [TextFormat].
+Each document can also be preceded by a header line holding its language and URL (see [header]).
 !*/
 
 use std::fs::File;
-use std::io::BufWriter;
+use std::io::{BufWriter, Write};
 use std::path::Path;
+use std::str::FromStr;
+
+use oscar_io::oscar_doc::Document;
+use serde_json::json;
+
+use crate::{error::Error, ops::CorpusReader};
+
+/// Default end of sequence token of [TextFormat::Eos].
+pub const DEFAULT_EOS: &str = "</s>";
+
+/// Layouts of extracted text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextFormat {
+    /// Documents separated by a blank line (OSCAR v1 (2019)-like).
+    Paragraphs,
+    /// One document per line, with newlines escaped as `\n` (and backslashes as `\\`).
+    Lines,
+    /// Documents followed by an end of sequence token, and a newline.
+    Eos(String),
+    /// One `<ordinal>.txt` file per document, in the destination folder.
+    Files,
+}
+
+impl TextFormat {
+    /// Names of the formats, as accepted by [TextFormat::from_str].
+    pub const NAMES: [&'static str; 4] = ["paragraphs", "lines", "eos", "files"];
 
-use oscar_io::oscar_doc::{Document, SplitFolderReader};
+    /// Use `token` as the end of sequence token, if relevant.
+    pub fn with_eos(self, token: &str) -> Self {
+        match self {
+            Self::Eos(_) => Self::Eos(token.to_string()),
+            other => other,
+        }
+    }
+}
+
+impl FromStr for TextFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "paragraphs" => Ok(Self::Paragraphs),
+            "lines" => Ok(Self::Lines),
+            "eos" => Ok(Self::Eos(DEFAULT_EOS.to_string())),
+            "files" => Ok(Self::Files),
+            other => Err(Error::Custom(format!(
+                "unknown text format {other:?} (expected one of {:?})",
+                Self::NAMES
+            ))),
+        }
+    }
+}
+
+/// Header line of `doc`: a JSON object holding its language and URL (`null` if missing).
+pub fn header(doc: &Document) -> String {
+    json!({
+        "lang": doc.identification().label().to_string(),
+        "url": doc.warc_headers().get("warc-target-uri"),
+    })
+    .to_string()
+}
+
+/// Escape backslashes and line breaks of `content`, so that it fits on one line.
+fn escape_lines(content: &str) -> String {
+    let mut escaped = String::with_capacity(content.len());
+    for c in content.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
 
-use crate::error::Error;
 pub trait ExtractText {
-    fn extract_from_path(src: &Path, dst: &Path, del_src: bool) -> Result<(), Error> {
-        let mut reader = SplitFolderReader::new(src)?;
-        // let file = File::open(src)?;
-        // let bufread = BufReader::new(file);
-        // if dst.exists() {
-        //     error!("File exist!");
-        //     return Err(std::io::Error::new(
-        //         std::io::ErrorKind::AlreadyExists,
-        //         format!("File exist {:?}", dst),
-        //     )
-        //     .into());
-        // }
-        let dst_file = File::create(dst)?;
-        let mut dst_buf = BufWriter::new(dst_file);
-        Self::extract_text(&mut reader, &mut dst_buf)?;
+    /// Extract the text of the `src` file/split folder into `dst`, following `format`.
+    /// If `with_header`, each document is preceded by its [header] line.
+    fn extract_from_path_as(
+        src: &Path,
+        dst: &Path,
+        del_src: bool,
+        format: &TextFormat,
+        with_header: bool,
+    ) -> Result<(), Error> {
+        let mut reader = CorpusReader::new(src)?;
+        if *format == TextFormat::Files {
+            Self::extract_files(&mut reader, dst, with_header)?;
+        } else {
+            let mut dst_buf = BufWriter::new(File::create(dst)?);
+            Self::extract_text_as(&mut reader, &mut dst_buf, format, with_header)?;
+        }
         if del_src {
             std::fs::remove_file(src)?;
         }
         Ok(())
     }
+
+    fn extract_from_path(src: &Path, dst: &Path, del_src: bool) -> Result<(), Error> {
+        Self::extract_from_path_as(src, dst, del_src, &TextFormat::Paragraphs, false)
+    }
     fn extract_text<T, U>(src: &mut T, dst: &mut U) -> Result<(), Error>
+    where
+        T: Iterator<Item = Result<Document, oscar_io::error::Error>>,
+        U: std::io::Write,
+    {
+        Self::extract_text_as(src, dst, &TextFormat::Paragraphs, false)
+    }
+
+    /// Write the text of `src` documents into `dst`, following `format` (which can't be [TextFormat::Files]).
+    fn extract_text_as<T, U>(
+        src: &mut T,
+        dst: &mut U,
+        format: &TextFormat,
+        with_header: bool,
+    ) -> Result<(), Error>
     where
         T: Iterator<Item = Result<Document, oscar_io::error::Error>>,
         U: std::io::Write,
     {
         for doc in src {
             let doc = doc?;
-            let mut extracted = Self::extract_content(&doc).to_string();
-            extracted.push_str("\n\n");
+            let mut extracted = if with_header {
+                header(&doc) + "\n"
+            } else {
+                String::new()
+            };
+            match format {
+                TextFormat::Paragraphs => {
+                    extracted.push_str(Self::extract_content(&doc));
+                    extracted.push_str("\n\n");
+                }
+                TextFormat::Lines => {
+                    extracted.push_str(&escape_lines(Self::extract_content(&doc)));
+                    extracted.push('\n');
+                }
+                TextFormat::Eos(token) => {
+                    extracted.push_str(Self::extract_content(&doc));
+                    extracted.push_str(token);
+                    extracted.push('\n');
+                }
+                TextFormat::Files => {
+                    return Err(Error::Custom(
+                        "file per document extraction needs a destination folder".to_string(),
+                    ))
+                }
+            }
             let string_size = extracted.len();
             let written_byte = dst.write(extracted.as_bytes())?;
             if string_size != written_byte {
@@ -47,6 +164,27 @@ pub trait ExtractText {
         dst.flush()?;
         Ok(())
     }
+    /// Write the text of each `src` document into its own `dst/<ordinal>.txt` file.
+    /// Returns the number of written files.
+    fn extract_files<T>(src: &mut T, dst: &Path, with_header: bool) -> Result<usize, Error>
+    where
+        T: Iterator<Item = Result<Document, oscar_io::error::Error>>,
+    {
+        std::fs::create_dir_all(dst)?;
+        let mut nb_files = 0;
+        for doc in src {
+            let doc = doc?;
+            let mut w = BufWriter::new(File::create(dst.join(format!("{nb_files:09}.txt")))?);
+            if with_header {
+                writeln!(w, "{}", header(&doc))?;
+            }
+            writeln!(w, "{}", Self::extract_content(&doc))?;
+            w.flush()?;
+            nb_files += 1;
+        }
+        Ok(nb_files)
+    }
+
     fn extract_content(doc: &Document) -> &str {
         doc.content()
     }
@@ -64,9 +202,14 @@ mod tests {
 
     use std::collections::HashMap;
 
-    use oscar_io::oscar_doc::{Document, Metadata};
+    use oscar_io::{
+        common::Identification,
+        lang::Lang,
+        oscar_doc::{Document, Metadata},
+    };
+    use tempfile::tempdir;
 
-    use super::ExtractText;
+    use super::{ExtractText, TextFormat};
     struct TestExtract;
     impl ExtractText for TestExtract {}
     #[test]
@@ -108,4 +251,63 @@ to start
 ";
         assert_eq!(res, expected);
     }
+
+    fn docs() -> Vec<Document> {
+        let metadata = Metadata::new(&Identification::new(Lang::Fr, 0.9), &None, &[]);
+        vec![
+            Document::new(
+                "foo\nback\\slash".to_string(),
+                HashMap::from([(
+                    "warc-target-uri".to_string(),
+                    "http://example.com".to_string(),
+                )]),
+                metadata.clone(),
+            ),
+            Document::new("bar".to_string(), HashMap::new(), metadata),
+        ]
+    }
+
+    fn extract_as(format: TextFormat, with_header: bool) -> String {
+        let mut res = vec![];
+        TestExtract::extract_text_as(
+            &mut docs().into_iter().map(Ok),
+            &mut res,
+            &format,
+            with_header,
+        )
+        .unwrap();
+        String::from_utf8(res).unwrap()
+    }
+
+    #[test]
+    fn test_extract_text_formats() {
+        assert_eq!(
+            extract_as(TextFormat::Lines, false),
+            "foo\\nback\\\\slash\nbar\n"
+        );
+        assert_eq!(
+            extract_as(
+                "eos".parse::<TextFormat>().unwrap().with_eos("<|eos|>"),
+                false
+            ),
+            "foo\nback\\slash<|eos|>\nbar<|eos|>\n"
+        );
+        assert_eq!(
+            extract_as(TextFormat::Paragraphs, true),
+            "{\"lang\":\"fr\",\"url\":\"http://example.com\"}\nfoo\nback\\slash\n\n{\"lang\":\"fr\",\"url\":null}\nbar\n\n"
+        );
+        assert!("csv".parse::<TextFormat>().is_err());
+    }
+
+    #[test]
+    fn test_extract_files() {
+        let dir = tempdir().unwrap();
+        let nb_files =
+            TestExtract::extract_files(&mut docs().into_iter().map(Ok), dir.path(), true).unwrap();
+        assert_eq!(nb_files, 2);
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("000000001.txt")).unwrap(),
+            "{\"lang\":\"fr\",\"url\":null}\nbar\n"
+        );
+    }
 }
@@ -0,0 +1,356 @@
+/*! Export of OSCAR v2 corpora into the Hugging Face datasets layout.
+
+ Each language is a config, held in its own folder of shards:
+
+ ```text
+ dst/
+ ├── README.md            # dataset card, declaring configs and their data files
+ ├── dataset_infos.json   # per-config sizes, shard sizes and checksums
+ ├── fr/
+ │   ├── fr_part_1.jsonl.gz
+ │   └── fr_part_2.jsonl.gz
+ └── de/
+     └── de.jsonl.gz
+ ```
+
+ Shards are written through a [SplitWriter], and checksums are the SHA-384 ones of [Checksum].
+!*/
+use std::{
+    fs::File,
+    io::{BufRead, BufWriter, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use log::info;
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha384};
+
+use crate::{
+    error::Error,
+    ops::{
+        corpus_files, for_each_language, language_parts, open_decompressed, temp_path, Checksum,
+        Compress, Export, ExportFormat, Rotation, SplitWriter,
+    },
+};
+
+/// Name of the dataset metadata file.
+pub const DATASET_INFOS: &str = "dataset_infos.json";
+
+/// Name of the dataset card.
+pub const DATASET_CARD: &str = "README.md";
+
+/// Maximum number of documents per row group of Parquet shards.
+const PARQUET_ROW_GROUP_SIZE: usize = 10_000;
+
+/// Shard formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShardFormat {
+    JsonlGz,
+    Parquet,
+}
+
+impl ShardFormat {
+    /// Names of the formats, as accepted by [ShardFormat::from_str].
+    pub const NAMES: [&'static str; 2] = ["jsonl.gz", "parquet"];
+
+    /// Extension of shards.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::JsonlGz => "jsonl.gz",
+            Self::Parquet => "parquet",
+        }
+    }
+}
+
+impl FromStr for ShardFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "jsonl.gz" => Ok(Self::JsonlGz),
+            "parquet" => Ok(Self::Parquet),
+            other => Err(Error::Custom(format!(
+                "unknown shard format {other:?} (expected one of {:?})",
+                Self::NAMES
+            ))),
+        }
+    }
+}
+
+/// Shard of a config.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Shard {
+    /// Path relative to the dataset root.
+    path: String,
+    size: u64,
+    sha384: String,
+}
+
+/// Config (language) of the dataset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Config {
+    name: String,
+    docs: u64,
+    /// Uncompressed size of documents.
+    bytes: u64,
+    shards: Vec<Shard>,
+}
+
+impl Config {
+    /// `dataset_infos`-style entry.
+    fn to_json(&self) -> Value {
+        let download_size: u64 = self.shards.iter().map(|shard| shard.size).sum();
+        let checksums: Map<String, Value> = self
+            .shards
+            .iter()
+            .map(|shard| {
+                (
+                    shard.path.clone(),
+                    json!({"num_bytes": shard.size, "checksum": shard.sha384}),
+                )
+            })
+            .collect();
+        json!({
+            "config_name": self.name,
+            "splits": {
+                "train": {
+                    "name": "train",
+                    "num_bytes": self.bytes,
+                    "num_examples": self.docs,
+                },
+            },
+            "download_checksums": checksums,
+            "download_size": download_size,
+            "dataset_size": self.bytes,
+            "size_in_bytes": download_size + self.bytes,
+        })
+    }
+}
+
+/// Dataset card, declaring one config per language.
+fn dataset_card(name: &str, configs: &[Config], format: ShardFormat) -> String {
+    let mut card = String::from("---\n");
+    card.push_str(&format!("pretty_name: {name}\nlanguage:\n"));
+    for config in configs {
+        card.push_str(&format!("- {}\n", config.name));
+    }
+    card.push_str("configs:\n");
+    for config in configs {
+        card.push_str(&format!(
+            "- config_name: {0}\n  data_files:\n  - split: train\n    path: {0}/*.{1}\n",
+            config.name,
+            format.extension()
+        ));
+    }
+    card.push_str("---\n\n");
+    card.push_str(&format!("# {name}\n\n"));
+    card.push_str(
+        "OSCAR corpus in the Hugging Face datasets layout, with one config per language.\n\n",
+    );
+    card.push_str("| Config | Documents | Size (bytes) | Shards |\n|---|---:|---:|---:|\n");
+    for config in configs {
+        card.push_str(&format!(
+            "| {} | {} | {} | {} |\n",
+            config.name,
+            config.docs,
+            config.bytes,
+            config.shards.len()
+        ));
+    }
+    card.push_str(&format!(
+        "\nShard sizes and SHA-384 checksums are listed in `{DATASET_INFOS}`.\n"
+    ));
+    card
+}
+
+pub trait HfDataset: Checksum + Compress + Export {
+    /// Write the documents (non blank lines) of `files` into shards of `root/<lang>` rotating according to `rotation`,
+    /// and convert them into `format`.
+    fn write_config(
+        lang: &str,
+        files: &[PathBuf],
+        root: &Path,
+        format: ShardFormat,
+        rotation: Rotation,
+    ) -> Result<Config, Error> {
+        // shards are written uncompressed first
+        let raw = temp_path(&root.join(lang));
+        if raw.exists() {
+            std::fs::remove_dir_all(&raw)?;
+        }
+        std::fs::create_dir_all(&raw)?;
+        let mut w = SplitWriter::new(&raw.join(format!("{lang}.jsonl")), rotation);
+        let mut docs = 0;
+        let mut bytes = 0;
+        for file in files {
+            for line in open_decompressed(file)?.lines() {
+                let mut line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                line.push('\n');
+                w.write_all(line.as_bytes())?;
+                docs += 1;
+                bytes += line.len() as u64;
+            }
+        }
+        w.flush()?;
+        drop(w);
+
+        let dst = root.join(lang);
+        std::fs::create_dir_all(&dst)?;
+        let mut hasher = Sha384::new();
+        let mut shards = Vec::new();
+        for raw_shard in corpus_files(&raw)? {
+            let stem = raw_shard
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.split('.').next())
+                .unwrap_or(lang)
+                .to_string();
+            let shard = dst.join(format!("{stem}.{}", format.extension()));
+            match format {
+                ShardFormat::JsonlGz => Self::compress_file(&raw_shard, &dst, true, "gzip")?,
+                ShardFormat::Parquet => {
+                    Self::export_file(
+                        &raw_shard,
+                        &shard,
+                        ExportFormat::Parquet,
+                        PARQUET_ROW_GROUP_SIZE,
+                    )?;
+                }
+            }
+            shards.push(Shard {
+                path: format!("{lang}/{stem}.{}", format.extension()),
+                size: shard.metadata()?.len(),
+                sha384: Self::get_hash_path(&shard, &mut hasher)?,
+            });
+        }
+        std::fs::remove_dir_all(&raw)?;
+
+        Ok(Config {
+            name: lang.to_string(),
+            docs,
+            bytes,
+            shards,
+        })
+    }
+
+    /// Export the corpus folder `src` into the `dst` dataset (languages in parallel).
+    ///
+    /// `dst` has to be empty or not to exist. The dataset is named `name` in its card.
+    fn hf_dataset(
+        src: &Path,
+        dst: &Path,
+        name: &str,
+        format: ShardFormat,
+        rotation: Rotation,
+        num_threads: usize,
+    ) -> Result<(), Error> {
+        if dst.is_file() || (dst.is_dir() && dst.read_dir()?.next().is_some()) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("{:?}", dst),
+            )
+            .into());
+        }
+        let languages = language_parts(src)?;
+        if languages.is_empty() {
+            return Err(Error::Custom("No languages found".to_string()));
+        }
+        std::fs::create_dir_all(dst)?;
+        let configs =
+            for_each_language(&languages, num_threads, "writing config", |lang, files| {
+                info!("writing config {lang} ({rotation:?})");
+                Self::write_config(lang, files, dst, format, rotation)
+            })?;
+
+        let infos: Map<String, Value> = configs
+            .iter()
+            .map(|config| (config.name.clone(), config.to_json()))
+            .collect();
+        let mut w = BufWriter::new(File::create(dst.join(DATASET_INFOS))?);
+        serde_json::to_writer_pretty(&mut w, &infos)?;
+        writeln!(w)?;
+        w.flush()?;
+        std::fs::write(dst.join(DATASET_CARD), dataset_card(name, &configs, format))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use serde_json::Value;
+    use sha2::{Digest, Sha384};
+    use tempfile::tempdir;
+
+    use crate::ops::{open_decompressed, Checksum, Compress, Export, Rotation};
+
+    use super::{HfDataset, ShardFormat, DATASET_CARD, DATASET_INFOS};
+
+    struct HfTest;
+    impl Checksum for HfTest {}
+    impl Compress for HfTest {}
+    impl Export for HfTest {}
+    impl HfDataset for HfTest {}
+
+    #[test]
+    fn test_hf_dataset() {
+        let dir = tempdir().unwrap();
+        let src = dir.path().join("src");
+        std::fs::create_dir_all(src.join("fr")).unwrap();
+        std::fs::write(src.join("fr/fr_part_1.jsonl"), "{\"a\":1}\n{\"a\":2}\n").unwrap();
+        std::fs::write(src.join("fr/fr_part_2.jsonl"), "\n{\"a\":3}\n").unwrap();
+        std::fs::write(src.join("de.jsonl"), "{\"a\":4}\n").unwrap();
+
+        let dst = dir.path().join("dst");
+        HfTest::hf_dataset(
+            &src,
+            &dst,
+            "test",
+            ShardFormat::JsonlGz,
+            Rotation::Docs(2),
+            1,
+        )
+        .unwrap();
+
+        let mut shard = String::new();
+        open_decompressed(&dst.join("fr/fr_part_2.jsonl.gz"))
+            .unwrap()
+            .read_to_string(&mut shard)
+            .unwrap();
+        assert_eq!(shard, "{\"a\":3}\n");
+        assert!(dst.join("de/de.jsonl.gz").is_file());
+        assert!(!dst.join("fr.tmp").exists());
+
+        let infos: Value =
+            serde_json::from_str(&std::fs::read_to_string(dst.join(DATASET_INFOS)).unwrap())
+                .unwrap();
+        assert_eq!(infos["fr"]["splits"]["train"]["num_examples"], 3);
+        assert_eq!(infos["fr"]["dataset_size"], 24);
+        let shard_info = &infos["fr"]["download_checksums"]["fr/fr_part_1.jsonl.gz"];
+        let mut hasher = Sha384::new();
+        hasher.update(std::fs::read(dst.join("fr/fr_part_1.jsonl.gz")).unwrap());
+        assert_eq!(shard_info["checksum"], format!("{:x}", hasher.finalize()));
+
+        let card = std::fs::read_to_string(dst.join(DATASET_CARD)).unwrap();
+        assert!(card.contains(
+            "- config_name: fr\n  data_files:\n  - split: train\n    path: fr/*.jsonl.gz\n"
+        ));
+        assert!(card.contains("| de | 1 | 8 | 1 |"));
+
+        // no overwriting
+        assert!(HfTest::hf_dataset(
+            &src,
+            &dst,
+            "test",
+            ShardFormat::JsonlGz,
+            Rotation::Docs(2),
+            1
+        )
+        .is_err());
+    }
+}
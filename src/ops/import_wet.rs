@@ -0,0 +1,192 @@
+/*! Import of WARC/WET files into OSCAR v2 documents.
+
+ Text records (`conversion` records of WET files, and `resource` records) become documents whose `warc_headers` are the record headers.
+ Other records (`warcinfo`, `request`, `response`...) are skipped.
+
+ Documents have empty metadata: they have not been identified, so they are labelled with a user-provided language and a probability of `0`,
+ they have no annotations, and their lines have no identifications.
+!*/
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    str::FromStr,
+};
+
+use log::{debug, info, warn};
+use oscar_io::{
+    common::Identification,
+    lang::Lang,
+    oscar_doc::{Document, Metadata, Writer},
+};
+use warc::{RawRecordHeader, WarcHeader, WarcReader};
+
+use crate::{
+    error::Error,
+    ops::{corpus_files, open_decompressed, write_atomically},
+};
+
+/// Types of the records that are imported.
+const TEXT_RECORD_TYPES: [&str; 2] = ["conversion", "resource"];
+
+/// Build the document of a record, or `None` if the record holds no text.
+///
+/// Line endings are normalized to `\n` and trailing whitespace is removed,
+/// and `content-length` is updated accordingly.
+pub(crate) fn record_to_doc(
+    header: &RawRecordHeader,
+    body: &[u8],
+    lang: Lang,
+) -> Result<Option<Document>, Error> {
+    let record_type = header
+        .as_ref()
+        .get(&WarcHeader::WarcType)
+        .map(|record_type| String::from_utf8_lossy(record_type).to_lowercase());
+    if !record_type.is_some_and(|record_type| TEXT_RECORD_TYPES.contains(&record_type.as_str())) {
+        return Ok(None);
+    }
+
+    let content = String::from_utf8_lossy(body)
+        .lines()
+        .collect::<Vec<&str>>()
+        .join("\n");
+    let content = content.trim_end().to_string();
+    if content.is_empty() {
+        return Ok(None);
+    }
+
+    let mut warc_headers: HashMap<String, String> = header
+        .as_ref()
+        .iter()
+        .map(|(name, value)| {
+            (
+                name.to_string(),
+                String::from_utf8_lossy(value).into_owned(),
+            )
+        })
+        .collect();
+    warc_headers.insert(
+        WarcHeader::ContentLength.to_string(),
+        content.len().to_string(),
+    );
+
+    let sentence_identifications = vec![None; content.lines().count()];
+    let metadata = Metadata::new(
+        &Identification::new(lang, 0.0),
+        &None,
+        &sentence_identifications,
+    );
+    Ok(Some(Document::new(content, warc_headers, metadata)))
+}
+
+pub trait ImportWet {
+    /// Import the text records of `r` into `w`. Returns the number of imported and skipped records.
+    fn import_records<R: std::io::BufRead, W: Write>(
+        r: R,
+        w: &mut Writer<W>,
+        lang: Lang,
+    ) -> Result<(usize, usize), Error> {
+        let mut nb_imported = 0;
+        let mut nb_skipped = 0;
+        for record in WarcReader::new(r).iter_raw_records() {
+            let (header, body) = record?;
+            match record_to_doc(&header, &body, lang)? {
+                Some(doc) => {
+                    w.write(&doc)?;
+                    nb_imported += 1;
+                }
+                None => nb_skipped += 1,
+            }
+        }
+        Ok((nb_imported, nb_skipped))
+    }
+
+    /// Import the WARC/WET file `src`, or the WARC/WET files of the `src` folder,
+    /// into the `dst` JSONL file (see [write_atomically]). Documents are labelled `lang`.
+    fn import_wet(src: &Path, dst: &Path, lang: &str) -> Result<(), Error> {
+        let lang = Lang::from_str(lang)?;
+        let files = corpus_files(src)?;
+        if files.is_empty() {
+            return Err(Error::Custom(format!("No files found in {:?}", src)));
+        }
+
+        let nb_imported = write_atomically(dst, |temp| {
+            let mut w = Writer::new(BufWriter::new(File::create(temp)?));
+            let mut nb_imported = 0;
+            for file in &files {
+                info!("importing {:?}", file);
+                let (file_imported, file_skipped) =
+                    Self::import_records(open_decompressed(file)?, &mut w, lang)?;
+                debug!(
+                    "{:?}: {file_imported} documents, {file_skipped} skipped records",
+                    file
+                );
+                if file_imported == 0 {
+                    warn!("{:?} holds no text records", file);
+                }
+                nb_imported += file_imported;
+            }
+            w.flush()?;
+            Ok(nb_imported)
+        })?;
+
+        info!(
+            "imported {nb_imported} documents from {} files",
+            files.len()
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use oscar_io::{
+        lang::Lang,
+        oscar_doc::{Document, Writer},
+    };
+
+    use super::ImportWet;
+
+    struct ImportTest;
+    impl ImportWet for ImportTest {}
+
+    fn record(record_type: &str, id: usize, body: &str) -> String {
+        format!(
+            "WARC/1.0\r\nWARC-Type: {record_type}\r\nWARC-Target-URI: http://example.com/{id}\r\nWARC-Date: 2021-09-16T11:07:14Z\r\nWARC-Record-ID: <urn:uuid:{id}>\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n\r\n{body}\r\n\r\n",
+            body.len()
+        )
+    }
+
+    #[test]
+    fn test_import_records() {
+        let wet = [
+            record("warcinfo", 0, "software: test\r\n"),
+            record("conversion", 1, "foo\r\nbar\r\n"),
+            record("conversion", 2, "\r\n"),
+            record("conversion", 3, "baz"),
+        ]
+        .concat();
+
+        let mut out = Vec::new();
+        let mut w = Writer::new(&mut out);
+        let (imported, skipped) =
+            ImportTest::import_records(wet.as_bytes(), &mut w, Lang::Fr).unwrap();
+        w.flush().unwrap();
+        assert_eq!((imported, skipped), (2, 2));
+
+        let docs: Vec<Document> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(docs[0].content(), "foo\nbar");
+        let headers = docs[0].warc_headers();
+        assert_eq!(headers["warc-record-id"], "<urn:uuid:1>");
+        assert_eq!(headers["warc-target-uri"], "http://example.com/1");
+        assert_eq!(headers["content-length"], "7");
+        assert_eq!(docs[0].identification().label(), &Lang::Fr);
+        assert_eq!(docs[0].metadata().annotation(), None);
+        assert_eq!(docs[1].content(), "baz");
+    }
+}
@@ -0,0 +1,320 @@
+//! On-disk line indices, for random access to corpus files.
+//!
+//! The index of `foo.jsonl` is written in `foo.jsonl.idx`, and holds the byte offset and length of each
+//! document (line). An index is only used while it is newer than its corpus file.
+//!
+//! Index format (little endian):
+//! - magic: `OSCIDX01` (8 bytes),
+//! - whether blank lines have been skipped: `u8`,
+//! - number of entries: `u64`,
+//! - entries: offset (`u64`) and length including the line break (`u32`).
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use log::{debug, info, warn};
+
+use crate::{
+    error::Error,
+    ops::{for_each_file, is_compressed, language_parts},
+};
+
+const INDEX_MAGIC: &[u8; 8] = b"OSCIDX01";
+
+/// Extension of index files.
+pub const INDEX_EXTENSION: &str = "idx";
+
+/// Offsets and lengths of the documents (lines) of a corpus file.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct LineIndex {
+    skip_blank: bool,
+    entries: Vec<(u64, u32)>,
+}
+
+impl LineIndex {
+    /// Build the index of a reader. If `skip_blank` is set, blank lines are not indexed.
+    pub fn build<R: BufRead>(mut r: R, skip_blank: bool) -> Result<Self, Error> {
+        let mut entries = Vec::new();
+        let mut offset = 0;
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            let read = r.read_until(b'\n', &mut line)?;
+            if read == 0 {
+                break;
+            }
+            if !(skip_blank && line.iter().all(u8::is_ascii_whitespace)) {
+                let length = u32::try_from(read).map_err(|_| {
+                    Error::Custom(format!("line at offset {offset} is too long to be indexed"))
+                })?;
+                entries.push((offset, length));
+            }
+            offset += read as u64;
+        }
+        Ok(Self {
+            skip_blank,
+            entries,
+        })
+    }
+
+    /// Number of indexed documents.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Offset and length of the `n`-th document.
+    pub fn get(&self, n: usize) -> Option<(u64, usize)> {
+        self.entries
+            .get(n)
+            .map(|(offset, length)| (*offset, *length as usize))
+    }
+
+    /// Iterate over the offsets and lengths of the documents.
+    pub fn iter(&self) -> impl Iterator<Item = (u64, usize)> + '_ {
+        self.entries
+            .iter()
+            .map(|(offset, length)| (*offset, *length as usize))
+    }
+
+    pub fn save<W: Write>(&self, w: &mut W) -> Result<(), Error> {
+        w.write_all(INDEX_MAGIC)?;
+        w.write_all(&[self.skip_blank as u8])?;
+        w.write_all(&(self.entries.len() as u64).to_le_bytes())?;
+        for (offset, length) in &self.entries {
+            w.write_all(&offset.to_le_bytes())?;
+            w.write_all(&length.to_le_bytes())?;
+        }
+        w.flush()?;
+        Ok(())
+    }
+
+    pub fn load<R: Read>(r: &mut R) -> Result<Self, Error> {
+        let mut magic = [0; 8];
+        r.read_exact(&mut magic)?;
+        if &magic != INDEX_MAGIC {
+            return Err(Error::Custom("not an index file".to_string()));
+        }
+        let mut skip_blank = [0];
+        r.read_exact(&mut skip_blank)?;
+        let skip_blank = skip_blank[0] != 0;
+        let mut buf = [0; 8];
+        r.read_exact(&mut buf)?;
+        let nb_entries = u64::from_le_bytes(buf) as usize;
+
+        let mut entries = Vec::with_capacity(nb_entries);
+        let mut offset = [0; 8];
+        let mut length = [0; 4];
+        for _ in 0..nb_entries {
+            r.read_exact(&mut offset)?;
+            r.read_exact(&mut length)?;
+            entries.push((u64::from_le_bytes(offset), u32::from_le_bytes(length)));
+        }
+        Ok(Self {
+            skip_blank,
+            entries,
+        })
+    }
+
+    /// Path of the index of `corpus`.
+    pub fn path(corpus: &Path) -> PathBuf {
+        let mut path = corpus.as_os_str().to_owned();
+        path.push(".");
+        path.push(INDEX_EXTENSION);
+        path.into()
+    }
+
+    /// Load the index of `corpus` if it exists, is newer than `corpus` and has been built with the same `skip_blank`.
+    pub fn load_fresh(corpus: &Path, skip_blank: bool) -> Result<Option<Self>, Error> {
+        let path = Self::path(corpus);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        if path.metadata()?.modified()? < corpus.metadata()?.modified()? {
+            warn!("{:?} is older than {:?}: ignoring", path, corpus);
+            return Ok(None);
+        }
+        let mut r = BufReader::new(File::open(&path)?);
+        let index = Self::load(&mut r)?;
+        if index.skip_blank != skip_blank {
+            warn!(
+                "{:?} has been built for another corpus version: ignoring",
+                path
+            );
+            return Ok(None);
+        }
+        debug!("using index {:?}", path);
+        Ok(Some(index))
+    }
+}
+
+/// Random access to the documents of a corpus file, using its index.
+pub(crate) struct IndexedFile {
+    file: File,
+    index: LineIndex,
+}
+
+impl IndexedFile {
+    /// Open `path`, loading its index if it is fresh and building it in memory otherwise.
+    /// If `skip_blank` is set, blank lines are not documents.
+    pub fn open(path: &Path, skip_blank: bool) -> Result<Self, Error> {
+        let index = match LineIndex::load_fresh(path, skip_blank)? {
+            Some(index) => index,
+            None => {
+                info!("no index for {:?}, indexing...", path);
+                LineIndex::build(BufReader::new(File::open(path)?), skip_blank)?
+            }
+        };
+        Ok(Self {
+            file: File::open(path)?,
+            index,
+        })
+    }
+
+    pub fn index(&self) -> &LineIndex {
+        &self.index
+    }
+
+    /// Get the `n`-th document, without its line break.
+    pub fn get(&mut self, n: usize) -> Result<Option<String>, Error> {
+        let (offset, length) = match self.index.get(n) {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        self.file.seek(SeekFrom::Start(offset))?;
+        let mut buf = vec![0; length];
+        self.file.read_exact(&mut buf)?;
+        let mut doc = String::from_utf8(buf)
+            .map_err(|e| Error::Custom(format!("invalid UTF-8 at offset {offset}: {e}")))?;
+        let trimmed_length = doc.trim_end_matches(['\n', '\r']).len();
+        doc.truncate(trimmed_length);
+        Ok(Some(doc))
+    }
+}
+
+pub trait IndexCorpus {
+    /// Whether blank lines are left out of the index. Blank lines are not documents in every corpus version.
+    const SKIP_BLANK: bool;
+
+    /// Index a corpus file, writing the index next to it.
+    fn index_file(src: &Path) -> Result<LineIndex, Error> {
+        if is_compressed(src) {
+            return Err(Error::Custom(format!(
+                "{:?} is compressed, and can't be accessed randomly",
+                src
+            )));
+        }
+        info!("indexing {:?}", src);
+        let index = LineIndex::build(BufReader::new(File::open(src)?), Self::SKIP_BLANK)?;
+        let mut w = BufWriter::new(File::create(LineIndex::path(src))?);
+        index.save(&mut w)?;
+        Ok(index)
+    }
+
+    /// Index a corpus file, or every uncompressed corpus file of a corpus folder.
+    fn index(src: &Path, num_threads: usize) -> Result<(), Error> {
+        if src.is_file() {
+            Self::index_file(src)?;
+            return Ok(());
+        }
+
+        let files: Vec<PathBuf> = language_parts(src)?
+            .into_iter()
+            .flat_map(|(_, files)| files)
+            .filter(|file| {
+                if is_compressed(file) {
+                    warn!("{:?} is compressed: ignoring", file);
+                    false
+                } else {
+                    true
+                }
+            })
+            .collect();
+
+        for_each_file(&files, num_threads, "indexing", |file| {
+            Self::index_file(file)
+        })?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::File,
+        time::{Duration, SystemTime},
+    };
+
+    use tempfile::tempdir;
+
+    use super::{IndexCorpus, IndexedFile, LineIndex};
+
+    struct IndexTest;
+    impl IndexCorpus for IndexTest {
+        const SKIP_BLANK: bool = true;
+    }
+
+    #[test]
+    fn test_build() {
+        let text = "foo\nbarbaz\n\nquux";
+        let index = LineIndex::build(text.as_bytes(), false).unwrap();
+        let entries: Vec<_> = index.iter().collect();
+        assert_eq!(entries, [(0, 4), (4, 7), (11, 1), (12, 4)]);
+
+        let index = LineIndex::build(text.as_bytes(), true).unwrap();
+        let entries: Vec<_> = index.iter().collect();
+        assert_eq!(entries, [(0, 4), (4, 7), (12, 4)]);
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let index = LineIndex::build("foo\nbar\n".as_bytes(), false).unwrap();
+        let mut buf = Vec::new();
+        index.save(&mut buf).unwrap();
+        assert_eq!(buf.len(), 8 + 1 + 8 + 2 * 12);
+        assert_eq!(LineIndex::load(&mut buf.as_slice()).unwrap(), index);
+
+        assert!(LineIndex::load(&mut "not an index".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_indexed_file() {
+        let dir = tempdir().unwrap();
+        let corpus = dir.path().join("fr.jsonl");
+        std::fs::write(&corpus, "{\"a\": 1}\n\n{\"a\": 2}\n").unwrap();
+
+        IndexTest::index(dir.path(), 1).unwrap();
+        assert!(LineIndex::path(&corpus).is_file());
+        assert_eq!(LineIndex::path(&corpus), dir.path().join("fr.jsonl.idx"));
+
+        let mut f = IndexedFile::open(&corpus, true).unwrap();
+        assert_eq!(f.index().len(), 2);
+        assert_eq!(f.get(1).unwrap().unwrap(), "{\"a\": 2}");
+        assert_eq!(f.get(2).unwrap(), None);
+    }
+
+    #[test]
+    fn test_stale() {
+        let dir = tempdir().unwrap();
+        let corpus = dir.path().join("fr.txt");
+        std::fs::write(&corpus, "foo\n").unwrap();
+        IndexTest::index_file(&corpus).unwrap();
+        assert!(LineIndex::load_fresh(&corpus, true).unwrap().is_some());
+        // built with another blank line policy
+        assert!(LineIndex::load_fresh(&corpus, false).unwrap().is_none());
+
+        // corpus modified after indexing
+        File::options()
+            .write(true)
+            .open(&corpus)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+        assert!(LineIndex::load_fresh(&corpus, true).unwrap().is_none());
+    }
+}
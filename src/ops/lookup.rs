@@ -0,0 +1,364 @@
+//! Document lookup in OSCAR v2 corpora, by ordinal, WARC record id or URL.
+//!
+//! Ordinals are resolved with line indices (see [crate::ops::IndexCorpus]).
+//! Record ids and URLs are resolved with key indices, written in `foo.jsonl.keys` next to `foo.jsonl`,
+//! that map hashes of record ids and URLs to document ordinals.
+//! Missing or stale key indices are built in memory on the first lookup.
+//!
+//! Key index format (little endian):
+//! - magic: `OSCKEY01` (8 bytes),
+//! - number of record ids: `u64`, followed by that many (hash: `u64`, ordinal: `u64`) pairs, sorted,
+//! - number of URLs: `u64`, followed by that many (hash: `u64`, ordinal: `u64`) pairs, sorted.
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use log::{debug, info, warn};
+use oscar_io::oscar_doc::Document;
+use serde_json::Value;
+
+use crate::{
+    error::Error,
+    ops::{corpus_files, is_compressed, DigestSet, IndexedFile},
+};
+
+const KEYS_MAGIC: &[u8; 8] = b"OSCKEY01";
+
+/// Extension of key index files.
+pub const KEYS_EXTENSION: &str = "keys";
+
+/// WARC header holding the record id.
+const RECORD_ID_HEADER: &str = "warc-record-id";
+/// WARC header holding the URL.
+const URL_HEADER: &str = "warc-target-uri";
+
+/// A way of identifying documents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DocKey {
+    /// Position of the document in the corpus (starting at 0).
+    Ordinal(usize),
+    /// `warc-record-id` header (e.g. `<urn:uuid:7c1c010a-61ca-4383-92ba-008390a56fc9>`).
+    RecordId(String),
+    /// `warc-target-uri` header.
+    Url(String),
+}
+
+/// Hashes of the record ids and URLs of a corpus file, mapped to document ordinals.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct KeyIndex {
+    record_ids: Vec<(u64, u64)>,
+    urls: Vec<(u64, u64)>,
+}
+
+impl KeyIndex {
+    /// Build the key index of a reader. Blank lines are not documents.
+    pub fn build<R: BufRead>(r: R) -> Result<Self, Error> {
+        let mut index = Self::default();
+        let mut ordinal = 0;
+        for line in r.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let doc: Value = serde_json::from_str(&line)?;
+            let headers = &doc["warc_headers"];
+            if let Some(record_id) = headers[RECORD_ID_HEADER].as_str() {
+                index
+                    .record_ids
+                    .push((DigestSet::hash(record_id.as_bytes()), ordinal));
+            }
+            if let Some(url) = headers[URL_HEADER].as_str() {
+                index.urls.push((DigestSet::hash(url.as_bytes()), ordinal));
+            }
+            ordinal += 1;
+        }
+        index.record_ids.sort_unstable();
+        index.urls.sort_unstable();
+        Ok(index)
+    }
+
+    /// Ordinals of the documents that may match `key` (hashes can collide).
+    fn candidates(&self, key: &DocKey) -> Vec<usize> {
+        let (table, value) = match key {
+            DocKey::Ordinal(ordinal) => return vec![*ordinal],
+            DocKey::RecordId(record_id) => (&self.record_ids, record_id),
+            DocKey::Url(url) => (&self.urls, url),
+        };
+        let hash = DigestSet::hash(value.as_bytes());
+        let start = table.partition_point(|(h, _)| *h < hash);
+        table[start..]
+            .iter()
+            .take_while(|(h, _)| *h == hash)
+            .map(|(_, ordinal)| *ordinal as usize)
+            .collect()
+    }
+
+    pub fn save<W: Write>(&self, w: &mut W) -> Result<(), Error> {
+        w.write_all(KEYS_MAGIC)?;
+        for table in [&self.record_ids, &self.urls] {
+            w.write_all(&(table.len() as u64).to_le_bytes())?;
+            for (hash, ordinal) in table {
+                w.write_all(&hash.to_le_bytes())?;
+                w.write_all(&ordinal.to_le_bytes())?;
+            }
+        }
+        w.flush()?;
+        Ok(())
+    }
+
+    pub fn load<R: Read>(r: &mut R) -> Result<Self, Error> {
+        let mut magic = [0; 8];
+        r.read_exact(&mut magic)?;
+        if &magic != KEYS_MAGIC {
+            return Err(Error::Custom("not a key index file".to_string()));
+        }
+        let mut read_u64 = || -> Result<u64, Error> {
+            let mut buf = [0; 8];
+            r.read_exact(&mut buf)?;
+            Ok(u64::from_le_bytes(buf))
+        };
+        let mut tables = [Vec::new(), Vec::new()];
+        for table in tables.iter_mut() {
+            let nb_entries = read_u64()? as usize;
+            table.reserve(nb_entries);
+            for _ in 0..nb_entries {
+                table.push((read_u64()?, read_u64()?));
+            }
+        }
+        let [record_ids, urls] = tables;
+        Ok(Self { record_ids, urls })
+    }
+
+    /// Path of the key index of `corpus`.
+    pub fn path(corpus: &Path) -> PathBuf {
+        let mut path = corpus.as_os_str().to_owned();
+        path.push(".");
+        path.push(KEYS_EXTENSION);
+        path.into()
+    }
+
+    /// Load the key index of `corpus` if it exists and is newer than `corpus`.
+    pub fn load_fresh(corpus: &Path) -> Result<Option<Self>, Error> {
+        let path = Self::path(corpus);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        if path.metadata()?.modified()? < corpus.metadata()?.modified()? {
+            warn!("{:?} is older than {:?}: ignoring", path, corpus);
+            return Ok(None);
+        }
+        debug!("using key index {:?}", path);
+        let mut r = BufReader::new(File::open(path)?);
+        Self::load(&mut r).map(Some)
+    }
+
+    /// Build the key index of `corpus` and write it next to it.
+    pub fn index_file(corpus: &Path) -> Result<Self, Error> {
+        info!("indexing keys of {:?}", corpus);
+        let index = Self::build(BufReader::new(File::open(corpus)?))?;
+        let mut w = BufWriter::new(File::create(Self::path(corpus))?);
+        index.save(&mut w)?;
+        Ok(index)
+    }
+}
+
+/// Random access to the documents of a corpus file or a folder of corpus parts.
+///
+/// Parts are read in part order, and ordinals span over parts.
+pub(crate) struct DocLookup {
+    files: Vec<PathBuf>,
+    readers: Vec<IndexedFile>,
+    keys: Vec<Option<KeyIndex>>,
+    /// Ordinal of the first document of each file.
+    starts: Vec<usize>,
+    nb_docs: usize,
+}
+
+impl DocLookup {
+    /// Open a corpus file or folder. Compressed files can't be accessed randomly.
+    pub fn open(src: &Path) -> Result<Self, Error> {
+        let files = corpus_files(src)?;
+        if files.is_empty() {
+            return Err(Error::Custom(format!("No files found in {:?}", src)));
+        }
+        if let Some(file) = files.iter().find(|file| is_compressed(file)) {
+            return Err(Error::Custom(format!(
+                "{:?} is compressed, and can't be accessed randomly",
+                file
+            )));
+        }
+
+        let mut readers = Vec::with_capacity(files.len());
+        let mut starts = Vec::with_capacity(files.len());
+        let mut nb_docs = 0;
+        for file in &files {
+            let reader = IndexedFile::open(file, true)?;
+            starts.push(nb_docs);
+            nb_docs += reader.index().len();
+            readers.push(reader);
+        }
+        Ok(Self {
+            keys: vec![None; files.len()],
+            files,
+            readers,
+            starts,
+            nb_docs,
+        })
+    }
+
+    /// Number of documents.
+    pub fn len(&self) -> usize {
+        self.nb_docs
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nb_docs == 0
+    }
+
+    /// Get the documents matching `key`, as JSON lines.
+    /// At most one document matches an ordinal, but several documents can share a record id or a URL.
+    pub fn get_raw(&mut self, key: &DocKey) -> Result<Vec<String>, Error> {
+        if let DocKey::Ordinal(ordinal) = key {
+            if *ordinal >= self.nb_docs {
+                return Ok(Vec::new());
+            }
+            let file_idx = self.starts.partition_point(|start| start <= ordinal) - 1;
+            let doc = self.readers[file_idx].get(ordinal - self.starts[file_idx])?;
+            return Ok(doc.into_iter().collect());
+        }
+
+        let (header, value) = match key {
+            DocKey::RecordId(record_id) => (RECORD_ID_HEADER, record_id),
+            DocKey::Url(url) => (URL_HEADER, url),
+            DocKey::Ordinal(_) => unreachable!(),
+        };
+        let mut docs = Vec::new();
+        for file_idx in 0..self.files.len() {
+            for ordinal in self.keys(file_idx)?.candidates(key) {
+                if let Some(doc) = self.readers[file_idx].get(ordinal)? {
+                    let parsed: Value = serde_json::from_str(&doc)?;
+                    if parsed["warc_headers"][header].as_str() == Some(value) {
+                        docs.push(doc);
+                    }
+                }
+            }
+        }
+        Ok(docs)
+    }
+
+    /// Get the documents matching `key`.
+    pub fn get(&mut self, key: &DocKey) -> Result<Vec<Document>, Error> {
+        self.get_raw(key)?
+            .iter()
+            .map(|doc| Ok(serde_json::from_str(doc)?))
+            .collect()
+    }
+
+    /// Key index of a file, loaded or built on first use.
+    fn keys(&mut self, file_idx: usize) -> Result<&KeyIndex, Error> {
+        if self.keys[file_idx].is_none() {
+            let file = &self.files[file_idx];
+            let keys = match KeyIndex::load_fresh(file)? {
+                Some(keys) => keys,
+                None => {
+                    info!("no key index for {:?}, indexing...", file);
+                    KeyIndex::build(BufReader::new(File::open(file)?))?
+                }
+            };
+            self.keys[file_idx] = Some(keys);
+        }
+        Ok(self.keys[file_idx].as_ref().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, fs::File};
+
+    use oscar_io::oscar_doc::{Document, Metadata, Writer};
+    use tempfile::tempdir;
+
+    use super::{DocKey, DocLookup, KeyIndex};
+
+    fn doc(i: usize, url: &str) -> Document {
+        let headers = HashMap::from([
+            (
+                "warc-record-id".to_string(),
+                format!("<urn:uuid:00000000-0000-0000-0000-{i:012}>"),
+            ),
+            ("warc-target-uri".to_string(), url.to_string()),
+        ]);
+        Document::new(format!("document {i}"), headers, Metadata::default())
+    }
+
+    /// Two parts, the URL of the 2nd document being shared by the 5th one.
+    fn write_corpus(dir: &std::path::Path) {
+        let docs: Vec<Document> = (0..6)
+            .map(|i| match i {
+                4 => doc(i, "https://example.com/1"),
+                i => doc(i, &format!("https://example.com/{i}")),
+            })
+            .collect();
+        for (part, docs) in docs.chunks(3).enumerate() {
+            let path = dir.join(format!("fr_meta_part_{}.jsonl", part + 1));
+            let mut wr = Writer::new(File::create(path).unwrap());
+            wr.write_multiple(docs).unwrap();
+            wr.flush().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_key_index_roundtrip() {
+        let dir = tempdir().unwrap();
+        write_corpus(dir.path());
+        let corpus = dir.path().join("fr_meta_part_1.jsonl");
+        let keys = KeyIndex::index_file(&corpus).unwrap();
+        assert_eq!(keys.record_ids.len(), 3);
+        assert_eq!(KeyIndex::load_fresh(&corpus).unwrap(), Some(keys));
+    }
+
+    #[test]
+    fn test_get_ordinal() {
+        let dir = tempdir().unwrap();
+        write_corpus(dir.path());
+        let mut lookup = DocLookup::open(dir.path()).unwrap();
+        assert_eq!(lookup.len(), 6);
+
+        for i in 0..6 {
+            let docs = lookup.get(&DocKey::Ordinal(i)).unwrap();
+            assert_eq!(docs.len(), 1);
+            assert_eq!(docs[0].content(), &format!("document {i}"));
+        }
+        assert!(lookup.get(&DocKey::Ordinal(6)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_get_keys() {
+        let dir = tempdir().unwrap();
+        write_corpus(dir.path());
+        // key index of the first part only
+        KeyIndex::index_file(&dir.path().join("fr_meta_part_1.jsonl")).unwrap();
+        let mut lookup = DocLookup::open(dir.path()).unwrap();
+
+        let docs = lookup
+            .get(&DocKey::RecordId(
+                "<urn:uuid:00000000-0000-0000-0000-000000000005>".to_string(),
+            ))
+            .unwrap();
+        assert_eq!(docs.len(), 1);
+        assert_eq!(docs[0].content(), "document 5");
+
+        let docs = lookup
+            .get(&DocKey::Url("https://example.com/1".to_string()))
+            .unwrap();
+        let contents: Vec<&String> = docs.iter().map(|doc| doc.content()).collect();
+        assert_eq!(contents, ["document 1", "document 4"]);
+
+        assert!(lookup
+            .get(&DocKey::Url("https://example.com/missing".to_string()))
+            .unwrap()
+            .is_empty());
+    }
+}
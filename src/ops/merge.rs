@@ -0,0 +1,380 @@
+/*! Merging and resharding of split corpora.
+
+ Parts (`fr_part_1.jsonl`, `fr_part_2.jsonl`...) are always read in part order, so that `fr_part_10.jsonl` comes after `fr_part_9.jsonl`.
+!*/
+use std::{
+    fs::File,
+    io::{BufRead, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use log::info;
+
+use crate::{
+    error::Error,
+    ops::{
+        compress, corpus_files, decompressed, for_each_language, is_compressed, language_parts,
+        open_decompressed, part_number, write_atomically, Rotation, SplitWriter,
+    },
+};
+
+/// Languages along with their parts.
+type LanguageParts = Vec<(String, Vec<PathBuf>)>;
+
+/// Languages of `src` along with their parts.
+///
+/// `src` is either a corpus folder (see [language_parts]) or the folder of a single language, holding parts only.
+/// Returns whether `src` is a single language.
+fn merge_units(src: &Path) -> Result<(bool, LanguageParts), Error> {
+    if !src.is_dir() {
+        return Err(Error::Custom(format!(
+            "{:?} is not a folder of parts or a corpus folder",
+            src
+        )));
+    }
+    let files = corpus_files(src)?;
+    let has_subfolders =
+        std::fs::read_dir(src)?.any(|entry| entry.is_ok_and(|e| e.path().is_dir()));
+    if !has_subfolders && !files.is_empty() && files.iter().all(|f| part_number(f).is_some()) {
+        let lang = src
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| Error::Custom(format!("invalid folder name: {:?}", src)))?;
+        return Ok((true, vec![(lang.to_string(), files)]));
+    }
+    Ok((false, language_parts(src)?))
+}
+
+/// Name of the merged file of `lang`, keeping the extension(s) of `part`.
+/// Compression extensions are dropped if `decompress` is set.
+fn merged_name(lang: &str, part: &Path, decompress: bool) -> String {
+    let file_name = part
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    let extension = match file_name.split_once('.') {
+        Some((_, extension)) => extension,
+        None => return lang.to_string(),
+    };
+    let extension = if decompress && is_compressed(part) {
+        extension
+            .rsplit_once('.')
+            .map_or("", |(extension, _)| extension)
+    } else {
+        extension
+    };
+    if extension.is_empty() {
+        lang.to_string()
+    } else {
+        format!("{lang}.{extension}")
+    }
+}
+
+/// Copy `r` into `w`. Returns whether `r` is empty or ends with a newline.
+fn copy_lines<R: BufRead + ?Sized, W: Write + ?Sized>(r: &mut R, w: &mut W) -> Result<bool, Error> {
+    let mut ends_with_newline = true;
+    loop {
+        let buf = r.fill_buf()?;
+        if buf.is_empty() {
+            break;
+        }
+        ends_with_newline = buf.last() == Some(&b'\n');
+        w.write_all(buf)?;
+        let length = buf.len();
+        r.consume(length);
+    }
+    Ok(ends_with_newline)
+}
+
+/// Reader writing the bytes it reads into `w`.
+struct TeeReader<R, W> {
+    r: R,
+    w: W,
+}
+
+impl<R: Read, W: Write> Read for TeeReader<R, W> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let length = self.r.read(buf)?;
+        self.w.write_all(&buf[..length])?;
+        Ok(length)
+    }
+}
+
+pub trait Merge {
+    /// Concatenate `files` in `w`.
+    ///
+    /// If `decompress` is set, files are decompressed. Otherwise they are copied as is,
+    /// which is valid for compressed files too (gzip members and zstd frames can be concatenated),
+    /// as long as files are either all compressed or all uncompressed.
+    /// Parts that don't end with a newline are followed by one (compressed like the part, if needed),
+    /// so that their last line is not merged with the first one of the next part.
+    fn merge_files<W: Write>(files: &[PathBuf], w: &mut W, decompress: bool) -> Result<(), Error> {
+        if !decompress
+            && files.iter().any(|f| is_compressed(f))
+            && !files.iter().all(|f| is_compressed(f))
+        {
+            return Err(Error::Custom(
+                "can't merge compressed and uncompressed files without decompressing".to_string(),
+            ));
+        }
+        let mut ends_with_newline = true;
+        let mut previous: Option<&PathBuf> = None;
+        for file in files {
+            if !ends_with_newline {
+                match previous {
+                    Some(previous) if !decompress && is_compressed(previous) => {
+                        let compression = match previous.extension().and_then(|ext| ext.to_str()) {
+                            Some("gz") => "gzip",
+                            _ => "zstd",
+                        };
+                        compress(w, &b"\n"[..], compression)?;
+                    }
+                    _ => w.write_all(b"\n")?,
+                }
+            }
+            previous = Some(file);
+
+            if decompress || !is_compressed(file) {
+                ends_with_newline = copy_lines(&mut open_decompressed(file)?, w)?;
+            } else {
+                // copied as is, and decompressed on the way to find out how it ends
+                let mut tee = TeeReader {
+                    r: File::open(file)?,
+                    w: &mut *w,
+                };
+                ends_with_newline =
+                    copy_lines(&mut decompressed(&mut tee, file)?, &mut std::io::sink())?;
+                // bytes left after the compressed stream
+                std::io::copy(&mut tee, &mut std::io::sink())?;
+            }
+        }
+        w.flush()?;
+        Ok(())
+    }
+
+    /// Concatenate `files` (see [Merge::merge_files]) into the `dst` file, that must not exist.
+    ///
+    /// `dst` is written atomically (see [write_atomically]).
+    fn merge_into(files: &[PathBuf], dst: &Path, decompress: bool) -> Result<(), Error> {
+        if dst.exists() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("{:?}", dst),
+            )
+            .into());
+        }
+        write_atomically(dst, |temp| {
+            let mut w = BufWriter::new(File::create(temp)?);
+            Self::merge_files(files, &mut w, decompress)
+        })
+    }
+
+    /// Write the documents (non blank lines) of `files` in a [SplitWriter] rotating according to `rotation`.
+    fn reshard_files(files: &[PathBuf], dst: &Path, rotation: Rotation) -> Result<(), Error> {
+        let mut w = SplitWriter::new(dst, rotation);
+        let mut line = Vec::new();
+        for file in files {
+            let mut r = open_decompressed(file)?;
+            loop {
+                line.clear();
+                if r.read_until(b'\n', &mut line)? == 0 {
+                    break;
+                }
+                if line.iter().all(u8::is_ascii_whitespace) {
+                    continue;
+                }
+                if line.last() != Some(&b'\n') {
+                    line.push(b'\n');
+                }
+                w.write_all(&line)?;
+            }
+        }
+        w.flush()?;
+        Ok(())
+    }
+
+    /// Merge the parts of a language folder into the `dst` file,
+    /// or the parts of each language of a corpus folder into `dst/<lang>.<ext>`.
+    fn merge(src: &Path, dst: &Path, decompress: bool, num_threads: usize) -> Result<(), Error> {
+        let (single, languages) = merge_units(src)?;
+        if single {
+            let (lang, files) = &languages[0];
+            info!("merging {} parts of {lang} into {:?}", files.len(), dst);
+            return Self::merge_into(files, dst, decompress);
+        }
+
+        std::fs::create_dir_all(dst)?;
+        for_each_language(&languages, num_threads, "merging", |lang, files| {
+            let lang_dst = dst.join(merged_name(lang, &files[0], decompress));
+            info!(
+                "merging {} parts of {lang} into {:?}",
+                files.len(),
+                lang_dst
+            );
+            Self::merge_into(files, &lang_dst, decompress)
+        })?;
+        Ok(())
+    }
+
+    /// Split the parts of a language folder again into `dst` (see [SplitWriter]),
+    /// or the parts of each language of a corpus folder into `dst/<lang>/<lang>.<ext>`.
+    ///
+    /// Parts are decompressed.
+    fn reshard(
+        src: &Path,
+        dst: &Path,
+        rotation: Rotation,
+        num_threads: usize,
+    ) -> Result<(), Error> {
+        let (single, languages) = merge_units(src)?;
+        if dst.is_file() || (dst.is_dir() && dst.read_dir()?.next().is_some()) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("{:?}", dst),
+            )
+            .into());
+        }
+        if single {
+            let (lang, files) = &languages[0];
+            info!("resharding {lang} into {:?} ({rotation:?})", dst);
+            return Self::reshard_files(files, dst, rotation);
+        }
+
+        for_each_language(&languages, num_threads, "resharding", |lang, files| {
+            let lang_dst = dst.join(lang);
+            std::fs::create_dir_all(&lang_dst)?;
+            let lang_dst = lang_dst.join(merged_name(lang, &files[0], true));
+            info!("resharding {lang} into {:?} ({rotation:?})", lang_dst);
+            Self::reshard_files(files, &lang_dst, rotation)
+        })?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::File,
+        io::{Read, Write},
+        path::Path,
+    };
+
+    use flate2::{write::GzEncoder, Compression};
+    use tempfile::tempdir;
+
+    use crate::ops::Rotation;
+
+    use super::{merged_name, Merge};
+
+    struct MergeTest;
+    impl Merge for MergeTest {}
+
+    /// Write 12 parts of a language, the 10th one lacking a trailing newline.
+    fn write_parts(dir: &Path, compressed: bool) {
+        std::fs::create_dir_all(dir).unwrap();
+        for part in 1..=12 {
+            let content = if part == 10 {
+                format!("{part}")
+            } else {
+                format!("{part}\n")
+            };
+            if compressed {
+                let f = File::create(dir.join(format!("fr_part_{part}.jsonl.gz"))).unwrap();
+                let mut enc = GzEncoder::new(f, Compression::default());
+                enc.write_all(content.as_bytes()).unwrap();
+                enc.finish().unwrap();
+            } else {
+                std::fs::write(dir.join(format!("fr_part_{part}.jsonl")), content).unwrap();
+            }
+        }
+    }
+
+    fn expected() -> String {
+        (1..=12).map(|part| format!("{part}\n")).collect()
+    }
+
+    #[test]
+    fn test_merged_name() {
+        let part = Path::new("fr/fr_meta_part_1.jsonl.gz");
+        assert_eq!(merged_name("fr", part, false), "fr.jsonl.gz");
+        assert_eq!(merged_name("fr", part, true), "fr.jsonl");
+        assert_eq!(merged_name("fr", Path::new("fr_part_1"), true), "fr");
+    }
+
+    #[test]
+    fn test_merge_language() {
+        let dir = tempdir().unwrap();
+        write_parts(&dir.path().join("fr"), false);
+        let dst = dir.path().join("fr.jsonl");
+        MergeTest::merge(&dir.path().join("fr"), &dst, false, 1).unwrap();
+        assert_eq!(std::fs::read_to_string(&dst).unwrap(), expected());
+
+        // no overwriting
+        assert!(MergeTest::merge(&dir.path().join("fr"), &dst, false, 1).is_err());
+    }
+
+    #[test]
+    fn test_merge_failed() {
+        let dir = tempdir().unwrap();
+        let src = dir.path().join("fr");
+        write_parts(&src, false);
+        std::fs::remove_file(src.join("fr_part_3.jsonl")).unwrap();
+        write_parts(&dir.path().join("gz"), true);
+        std::fs::rename(
+            dir.path().join("gz/fr_part_3.jsonl.gz"),
+            src.join("fr_part_3.jsonl.gz"),
+        )
+        .unwrap();
+
+        // compressed and uncompressed parts can't be copied as is, and nothing is left behind
+        let dst = dir.path().join("fr.jsonl");
+        assert!(MergeTest::merge(&src, &dst, false, 1).is_err());
+        assert!(!dst.exists());
+        assert!(!crate::ops::temp_path(&dst).exists());
+
+        MergeTest::merge(&src, &dst, true, 1).unwrap();
+        assert_eq!(std::fs::read_to_string(&dst).unwrap(), expected());
+    }
+
+    #[test]
+    fn test_merge_compressed() {
+        let dir = tempdir().unwrap();
+        write_parts(&dir.path().join("src/fr"), true);
+        write_parts(&dir.path().join("src/de"), false);
+
+        let dst = dir.path().join("raw");
+        MergeTest::merge(&dir.path().join("src"), &dst, false, 1).unwrap();
+        let mut r = crate::ops::open_decompressed(&dst.join("fr.jsonl.gz")).unwrap();
+        let mut merged = String::new();
+        r.read_to_string(&mut merged).unwrap();
+        // compressed parts are copied as is
+        assert_eq!(merged, expected());
+        assert_eq!(
+            std::fs::read_to_string(dst.join("de.jsonl")).unwrap(),
+            expected()
+        );
+
+        let dst = dir.path().join("decompressed");
+        MergeTest::merge(&dir.path().join("src"), &dst, true, 1).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dst.join("fr.jsonl")).unwrap(),
+            expected()
+        );
+    }
+
+    #[test]
+    fn test_reshard() {
+        let dir = tempdir().unwrap();
+        write_parts(&dir.path().join("src/fr"), true);
+        let dst = dir.path().join("dst");
+        MergeTest::reshard(&dir.path().join("src"), &dst, Rotation::Docs(5), 1).unwrap();
+
+        let parts: Vec<String> = (1..=3)
+            .map(|part| {
+                std::fs::read_to_string(dst.join(format!("fr/fr_part_{part}.jsonl"))).unwrap()
+            })
+            .collect();
+        assert_eq!(parts.concat(), expected());
+        assert_eq!(parts[2], "11\n12\n");
+    }
+}
@@ -1,19 +1,77 @@
 //! Operation traits and basic implementations for some of them.
 //!
 //! A subset of these should be implemented for different corpus versions.
+mod audit_lang;
 mod checksum;
 mod compress;
+mod corpus;
+mod decontaminate;
 mod dedup;
+mod export;
+mod extract_fields;
 mod extract_text;
 mod filter_tags;
+mod hf;
+mod import_wet;
+mod index;
+mod lookup;
+mod merge;
+mod normalize;
+mod partition;
+mod progress;
+mod reservoir;
 mod sampling;
 mod split;
+mod stats;
+mod stratified;
+mod to_doc;
+mod validate;
+mod webdataset;
 
+pub(crate) use audit_lang::AuditLang;
 pub use checksum::Checksum;
-pub(crate) use compress::Compress;
+pub(crate) use compress::{compress, decompressed, is_compressed, open_decompressed, Compress};
+pub(crate) use corpus::{
+    build_thread_pool, corpus_files, for_each_file, for_each_language, is_corpus_file,
+    language_parts, mirrored_path, source_languages, CorpusReader,
+};
+pub(crate) use decontaminate::{Decontaminate, ReferenceSet};
 pub(crate) use dedup::Dedup;
+pub(crate) use dedup::DigestSet;
+pub use dedup::{DedupScope, TextUnit};
+pub(crate) use export::Export;
+pub use export::ExportFormat;
+pub(crate) use extract_fields::ExtractFields;
+pub use extract_fields::FieldsFormat;
 pub(crate) use extract_text::ExtractText;
+pub use extract_text::{TextFormat, DEFAULT_EOS};
 pub(crate) use filter_tags::FilterTags;
+pub(crate) use hf::HfDataset;
+pub use hf::ShardFormat;
+pub(crate) use import_wet::ImportWet;
+pub use index::INDEX_EXTENSION;
+pub(crate) use index::{IndexCorpus, IndexedFile, LineIndex};
+pub use lookup::{DocKey, KEYS_EXTENSION};
+pub(crate) use lookup::{DocLookup, KeyIndex};
+pub(crate) use merge::Merge;
+pub use normalize::Normalization;
+pub(crate) use partition::{host, Partition};
+pub use partition::{partition_args, PartitionKey, Ratios};
+pub use progress::PROGRESS_FILE;
+pub(crate) use progress::{is_progress_file, temp_path, write_atomically, Progress};
+pub(crate) use reservoir::{open_stream, Reservoir};
 pub(crate) use sampling::SampleText;
-pub use sampling::SamplingKind;
-pub(crate) use split::Split;
+pub use sampling::{
+    rng_from_matches, sampling_rng, seed_arg, SampleBudget, SamplingKind, SamplingRng,
+};
+pub use split::Rotation;
+pub(crate) use split::{part_number, sort_parts};
+pub(crate) use split::{Split, SplitWriter};
+pub(crate) use stats::Stats;
+pub use stats::STATS_REPORT;
+pub(crate) use stratified::StratifiedSample;
+pub use stratified::{Mixing, MANIFEST_FILE};
+pub(crate) use to_doc::ToDoc;
+pub(crate) use validate::Validate;
+pub(crate) use webdataset::WebDataset;
+pub use webdataset::{webdataset_args, SHARD_INDEX};
@@ -0,0 +1,198 @@
+//! Text normalization applied before hashing.
+//!
+//! Normalization only changes what is hashed: ops using it still write the original text.
+use std::borrow::Cow;
+
+use clap::{arg, Arg, ArgMatches};
+use unicode_normalization::UnicodeNormalization;
+
+/// Normalization steps, applied in the following order:
+/// NFKC, lowercasing, punctuation stripping, digit masking and whitespace collapsing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Normalization {
+    /// Unicode NFKC normalization.
+    pub nfkc: bool,
+    /// Unicode lowercasing (not full case folding: `ß` stays `ß`).
+    pub lowercase: bool,
+    /// Removal of characters that are neither alphanumeric nor whitespace.
+    pub strip_punctuation: bool,
+    /// Replacement of every digit by `0`.
+    pub mask_digits: bool,
+    /// Replacement of whitespace runs by a single space, and trimming.
+    pub collapse_whitespace: bool,
+}
+
+impl Normalization {
+    /// Normalization with every step enabled.
+    pub fn all() -> Self {
+        Self {
+            nfkc: true,
+            lowercase: true,
+            strip_punctuation: true,
+            mask_digits: true,
+            collapse_whitespace: true,
+        }
+    }
+
+    /// `true` if no step is enabled.
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Enabled steps as a bitmask, in application order (NFKC is the lowest bit).
+    pub fn to_bits(self) -> u8 {
+        [
+            self.nfkc,
+            self.lowercase,
+            self.strip_punctuation,
+            self.mask_digits,
+            self.collapse_whitespace,
+        ]
+        .iter()
+        .enumerate()
+        .fold(0, |bits, (i, enabled)| bits | (*enabled as u8) << i)
+    }
+
+    /// Normalization from a bitmask built by [Normalization::to_bits].
+    pub fn from_bits(bits: u8) -> Self {
+        Self {
+            nfkc: bits & 1 != 0,
+            lowercase: bits & 1 << 1 != 0,
+            strip_punctuation: bits & 1 << 2 != 0,
+            mask_digits: bits & 1 << 3 != 0,
+            collapse_whitespace: bits & 1 << 4 != 0,
+        }
+    }
+
+    /// Normalize `text`. Borrows `text` if no step is enabled.
+    pub fn normalize<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if self.is_identity() {
+            return Cow::Borrowed(text);
+        }
+
+        let mut normalized: String = if self.nfkc {
+            text.nfkc().collect()
+        } else {
+            text.to_string()
+        };
+        if self.lowercase {
+            normalized = normalized.to_lowercase();
+        }
+        if self.strip_punctuation {
+            normalized.retain(|c| c.is_alphanumeric() || c.is_whitespace());
+        }
+        if self.mask_digits {
+            normalized = normalized
+                .chars()
+                .map(|c| if c.is_numeric() { '0' } else { c })
+                .collect();
+        }
+        if self.collapse_whitespace {
+            normalized = normalized.split_whitespace().collect::<Vec<_>>().join(" ");
+        }
+
+        Cow::Owned(normalized)
+    }
+
+    /// Command line arguments enabling normalization steps, to be added to ops that hash text.
+    pub fn args() -> [Arg<'static>; 6] {
+        [
+            arg!(--nfkc "Apply Unicode NFKC normalization before hashing.").required(false),
+            arg!(--lowercase "Lowercase before hashing.").required(false),
+            arg!(--strip_punct "Remove punctuation and symbols before hashing.").required(false),
+            arg!(--mask_digits "Replace digits by 0 before hashing.").required(false),
+            arg!(--collapse_ws "Collapse whitespace runs into a single space before hashing.")
+                .required(false),
+            arg!(--normalize "Enable all normalization steps.").required(false),
+        ]
+    }
+
+    /// Build normalization from arguments added with [Normalization::args].
+    pub fn from_matches(matches: &ArgMatches) -> Self {
+        if matches.is_present("normalize") {
+            Self::all()
+        } else {
+            Self {
+                nfkc: matches.is_present("nfkc"),
+                lowercase: matches.is_present("lowercase"),
+                strip_punctuation: matches.is_present("strip_punct"),
+                mask_digits: matches.is_present("mask_digits"),
+                collapse_whitespace: matches.is_present("collapse_ws"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use super::Normalization;
+
+    #[test]
+    fn test_identity() {
+        let n = Normalization::default();
+        assert!(n.is_identity());
+        assert!(matches!(n.normalize("Foo  Bar"), Cow::Borrowed("Foo  Bar")));
+    }
+
+    #[test]
+    fn test_nfkc() {
+        let n = Normalization {
+            nfkc: true,
+            ..Default::default()
+        };
+        // fullwidth letters and ligature
+        assert_eq!(n.normalize("ＡＢＣ ﬁne"), "ABC fine");
+        // composed and decomposed é
+        assert_eq!(n.normalize("e\u{301}"), n.normalize("\u{e9}"));
+    }
+
+    #[test]
+    fn test_steps() {
+        let lowercase = Normalization {
+            lowercase: true,
+            ..Default::default()
+        };
+        assert_eq!(lowercase.normalize("ÉCOLE School"), "école school");
+
+        let punct = Normalization {
+            strip_punctuation: true,
+            ..Default::default()
+        };
+        assert_eq!(punct.normalize("Hello, world! (ok)"), "Hello world ok");
+
+        let digits = Normalization {
+            mask_digits: true,
+            ..Default::default()
+        };
+        assert_eq!(digits.normalize("Tel: 06 12 34"), "Tel: 00 00 00");
+
+        let ws = Normalization {
+            collapse_whitespace: true,
+            ..Default::default()
+        };
+        assert_eq!(ws.normalize("  foo \t bar  "), "foo bar");
+    }
+
+    #[test]
+    fn test_bits() {
+        let n = Normalization {
+            lowercase: true,
+            collapse_whitespace: true,
+            ..Default::default()
+        };
+        assert_eq!(n.to_bits(), 0b10010);
+        assert_eq!(Normalization::from_bits(n.to_bits()), n);
+        assert_eq!(Normalization::all().to_bits(), 0b11111);
+    }
+
+    #[test]
+    fn test_all() {
+        let n = Normalization::all();
+        assert_eq!(
+            n.normalize("Call us: ０６ 12 34 !"),
+            n.normalize("call   US 99 00 11")
+        );
+    }
+}
@@ -0,0 +1,336 @@
+//! Deterministic train/validation/test partitioning.
+//!
+//! Each document is assigned to a split by hashing a key (its URL, record id, URL host or content),
+//! so that assignments are stable across runs and machines, and don't depend on document order.
+//! Keying by URL host keeps every document of a domain in the same split, so that no domain leaks between splits.
+use std::{
+    borrow::Cow,
+    fs::File,
+    hash::Hasher,
+    io::{BufRead, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use clap::{arg, Arg, ArgMatches};
+use log::info;
+use twox_hash::XxHash64;
+
+use crate::{
+    error::Error,
+    ops::{for_each_language, open_decompressed, source_languages, Rotation, SplitWriter},
+};
+
+/// Named splits and their proportions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ratios {
+    names: Vec<String>,
+    /// Cumulative proportions, the last one being 1.
+    bounds: Vec<f64>,
+}
+
+impl Ratios {
+    /// Parse `name=ratio` pairs separated by commas (e.g. `train=0.8,validation=0.1,test=0.1`).
+    /// Ratios are normalized, so `train=8,test=2` is valid too.
+    pub fn parse(ratios: &str) -> Result<Self, Error> {
+        let mut names: Vec<String> = Vec::new();
+        let mut weights = Vec::new();
+        for pair in ratios.split(',') {
+            let parsed = pair
+                .split_once('=')
+                .and_then(|(name, ratio)| Some((name.trim(), ratio.trim().parse::<f64>().ok()?)));
+            match parsed {
+                Some((name, ratio)) if !name.is_empty() && ratio >= 0.0 && ratio.is_finite() => {
+                    if names.iter().any(|n| n == name) {
+                        return Err(Error::Custom(format!("split {name:?} is given twice")));
+                    }
+                    names.push(name.to_string());
+                    weights.push(ratio);
+                }
+                _ => {
+                    return Err(Error::Custom(format!(
+                        "expected '<split>=<ratio>', got {pair:?}"
+                    )))
+                }
+            }
+        }
+
+        let total: f64 = weights.iter().sum();
+        if total <= 0.0 {
+            return Err(Error::Custom("ratios can't all be 0".to_string()));
+        }
+        let mut cumulated = 0.0;
+        let mut bounds: Vec<f64> = weights
+            .iter()
+            .map(|weight| {
+                cumulated += weight;
+                cumulated / total
+            })
+            .collect();
+        // avoid rounding errors on the last bound
+        if let Some(last) = bounds.last_mut() {
+            *last = 1.0;
+        }
+        Ok(Self { names, bounds })
+    }
+
+    /// Split names, in the order they have been given.
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Index of the split of a document whose key hashes to `hash`.
+    pub fn assign(&self, hash: u64) -> usize {
+        // 53 high bits make a uniform float in [0, 1[
+        let u = (hash >> 11) as f64 / (1u64 << 53) as f64;
+        self.bounds
+            .iter()
+            .position(|bound| u < *bound)
+            .unwrap_or(self.bounds.len() - 1)
+    }
+
+    /// Command line argument setting the splits.
+    pub fn arg() -> Arg<'static> {
+        arg!(-r --ratios <RATIOS> "Comma-separated '<split>=<ratio>' pairs. Ratios are normalized.")
+            .default_value("train=0.8,validation=0.1,test=0.1")
+            .required(false)
+    }
+
+    /// Build ratios from the argument added with [Ratios::arg].
+    pub fn from_matches(matches: &ArgMatches) -> Result<Self, Error> {
+        Self::parse(matches.value_of("ratios").unwrap())
+    }
+}
+
+/// What documents are assigned by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartitionKey {
+    /// Document text.
+    Content,
+    /// `warc-target-uri` header.
+    Url,
+    /// `warc-record-id` header.
+    RecordId,
+    /// Host of the `warc-target-uri` header, so that no domain is shared between splits.
+    Domain,
+}
+
+impl PartitionKey {
+    /// Command line arguments choosing the key, for corpora holding WARC headers.
+    pub fn args() -> [Arg<'static>; 2] {
+        [
+            arg!(--by <KEY> "Document field to hash.")
+                .possible_values(["url", "record_id"])
+                .default_value("url")
+                .required(false),
+            arg!(--no_domain_leakage "Hash URL hosts, keeping every document of a domain in the same split. Overrides --by.")
+                .required(false),
+        ]
+    }
+
+    /// Build the key from the arguments added with [PartitionKey::args].
+    pub fn from_matches(matches: &ArgMatches) -> Self {
+        if matches.is_present("no_domain_leakage") {
+            return Self::Domain;
+        }
+        match matches.value_of("by") {
+            Some("record_id") => Self::RecordId,
+            _ => Self::Url,
+        }
+    }
+}
+
+/// Host of `url`, lowercased and without user info or port.
+pub(crate) fn host(url: &str) -> Option<String> {
+    let (_, rest) = url.split_once("://")?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+    // keep IPv6 hosts whole
+    let host = match host.strip_prefix('[') {
+        Some(ipv6) => ipv6.split(']').next()?,
+        None => host.split(':').next()?,
+    };
+    if host.is_empty() {
+        None
+    } else {
+        Some(host.to_lowercase())
+    }
+}
+
+/// Command line arguments common to every partitioning.
+/// Outputs are only rotated if one of the [Rotation::args] is given.
+pub fn partition_args() -> Vec<Arg<'static>> {
+    let mut args = vec![
+        arg!(--salt <SALT> "Salt of the hash function. Changing it gives another (stable) assignment.")
+            .default_value("0")
+            .required(false),
+        arg!(-J --num_threads <NUM_THREADS> "Number of threads to use (iif source is a folder). If 0, take all available")
+            .default_value("0")
+            .required(false),
+        Ratios::arg(),
+    ];
+    args.extend(Rotation::args(None));
+    args
+}
+
+pub trait Partition {
+    /// Extension of the output files.
+    const EXTENSION: &'static str;
+
+    /// Read the next document of `r` in `doc`, as it has to be written.
+    /// Returns `false` when there are no documents left.
+    fn next_doc<R: BufRead>(r: &mut R, doc: &mut String) -> Result<bool, Error>;
+
+    /// Key of `doc`, hashed to assign it to a split.
+    fn doc_key(doc: &str, key: PartitionKey) -> Result<Cow<'_, str>, Error>;
+
+    /// Assign the documents of `files` (read in order) to splits, writing them in `dsts` (one per split).
+    /// If `rotation` is set, outputs are rotated accordingly (see [SplitWriter]).
+    ///
+    /// Returns the number of documents of each split.
+    fn partition_files(
+        files: &[PathBuf],
+        dsts: &[PathBuf],
+        ratios: &Ratios,
+        key: PartitionKey,
+        salt: u64,
+        rotation: Option<Rotation>,
+    ) -> Result<Vec<usize>, Error> {
+        for dst in dsts {
+            if dst.exists() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::AlreadyExists,
+                    format!("{:?}", dst),
+                )
+                .into());
+            }
+        }
+        let mut writers: Vec<Box<dyn Write>> = dsts
+            .iter()
+            .map(|dst| -> Result<Box<dyn Write>, Error> {
+                Ok(match rotation {
+                    // unbuffered, so that documents are not split between files
+                    Some(rotation) => Box::new(SplitWriter::new(dst, rotation)),
+                    None => Box::new(BufWriter::new(File::create(dst)?)),
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        let mut counts = vec![0; dsts.len()];
+        let mut doc = String::new();
+        for file in files {
+            let mut r = open_decompressed(file)?;
+            while Self::next_doc(&mut r, &mut doc)? {
+                let mut hasher = XxHash64::with_seed(salt);
+                hasher.write(Self::doc_key(&doc, key)?.as_bytes());
+                let split = ratios.assign(hasher.finish());
+                writers[split].write_all(doc.as_bytes())?;
+                counts[split] += 1;
+            }
+        }
+        for w in writers.iter_mut() {
+            w.flush()?;
+        }
+        Ok(counts)
+    }
+
+    /// Partition a corpus file, or each language of a corpus folder, into `dst/<split>/<lang>.<ext>`.
+    ///
+    /// Parts of a language are read in part order, and languages are partitioned in parallel.
+    fn partition(
+        src: &Path,
+        dst: &Path,
+        ratios: &Ratios,
+        key: PartitionKey,
+        salt: u64,
+        rotation: Option<Rotation>,
+        num_threads: usize,
+    ) -> Result<(), Error> {
+        let languages = source_languages(src)?;
+        if languages.is_empty() {
+            return Err(Error::Custom(format!("No languages found in {:?}", src)));
+        }
+
+        for name in ratios.names() {
+            std::fs::create_dir_all(dst.join(name))?;
+        }
+        for_each_language(&languages, num_threads, "partitioning", |lang, files| {
+            let dsts: Vec<PathBuf> = ratios
+                .names()
+                .iter()
+                .map(|name| dst.join(name).join(format!("{lang}.{}", Self::EXTENSION)))
+                .collect();
+            let counts = Self::partition_files(files, &dsts, ratios, key, salt, rotation)?;
+            let counts: Vec<String> = ratios
+                .names()
+                .iter()
+                .zip(counts)
+                .map(|(name, count)| format!("{name}={count}"))
+                .collect();
+            info!("{lang}: {}", counts.join(", "));
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    /// Run [Partition::partition] with the `SOURCE` and `DESTINATION` arguments and the ones of [partition_args].
+    fn partition_from_matches(matches: &ArgMatches, key: PartitionKey) -> Result<(), Error> {
+        let src: PathBuf = matches
+            .value_of("SOURCE")
+            .expect("Value of 'SOURCE' is required.")
+            .into();
+        let dst: PathBuf = matches
+            .value_of("DESTINATION")
+            .expect("Value of 'DESTINATION' is required.")
+            .into();
+        let ratios = Ratios::from_matches(matches)?;
+        let salt: u64 = matches
+            .value_of("salt")
+            .unwrap()
+            .parse()
+            .expect("'salt' has to be a number.");
+        let rotation = Rotation::from_matches(matches)?;
+        let num_threads: usize = matches
+            .value_of("num_threads")
+            .unwrap()
+            .parse()
+            .expect("'num_threads' has to be a number.");
+
+        Self::partition(&src, &dst, &ratios, key, salt, rotation, num_threads)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{host, Ratios};
+
+    #[test]
+    fn test_ratios() {
+        let ratios = Ratios::parse("train=8, test=2").unwrap();
+        assert_eq!(ratios.names(), ["train", "test"]);
+        assert_eq!(ratios.assign(0), 0);
+        assert_eq!(ratios.assign(u64::MAX), 1);
+        assert_eq!(ratios.assign((u64::MAX / 10) * 7), 0);
+        assert_eq!(ratios.assign((u64::MAX / 10) * 9), 1);
+
+        // empty splits never get documents
+        let ratios = Ratios::parse("train=1,validation=0").unwrap();
+        assert_eq!(ratios.assign(u64::MAX), 0);
+
+        for invalid in ["train", "train=-1", "train=0", "train=1,train=2", "=1"] {
+            assert!(Ratios::parse(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_host() {
+        assert_eq!(
+            host("https://user@Example.com:8080/a/b?c#d").as_deref(),
+            Some("example.com")
+        );
+        assert_eq!(host("http://[::1]:80/").as_deref(), Some("::1"));
+        assert_eq!(host("http://foo.org").as_deref(), Some("foo.org"));
+        assert_eq!(host("foo.org/bar"), None);
+    }
+}
@@ -0,0 +1,200 @@
+//! Progress tracking of folder-wide operations, so that interrupted runs can be resumed.
+//!
+//! Outputs are first written under a temporary name (see [temp_path]), and renamed once complete,
+//! so that a destination never holds partly written files under their final name.
+//! Completed sources are then appended to a progress manifest at the root of the destination,
+//! which is removed once the whole operation has succeeded.
+use std::{
+    collections::HashSet,
+    ffi::OsStr,
+    fs::File,
+    io::{BufRead, BufReader, ErrorKind, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use log::{debug, info, warn};
+
+use crate::error::Error;
+
+/// Name of the progress manifest, written at the root of destination folders.
+pub const PROGRESS_FILE: &str = ".oscar-tools-progress";
+
+/// Extension of outputs being written.
+const TEMP_EXTENSION: &str = "tmp";
+
+/// Temporary name of `path` while it is being written (`foo.jsonl.tmp` for `foo.jsonl`).
+///
+/// Outputs are written under this name and renamed once complete (see [write_atomically]),
+/// so that they are never found partly written under their final name.
+pub(crate) fn temp_path(path: &Path) -> PathBuf {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".");
+    temp.push(TEMP_EXTENSION);
+    temp.into()
+}
+
+/// Write `dst` with `write`, given the temporary name of `dst` (see [temp_path]) to write to.
+///
+/// The temporary file is renamed to `dst` if `write` succeeds, and removed if it fails.
+pub(crate) fn write_atomically<T>(
+    dst: &Path,
+    write: impl FnOnce(&Path) -> Result<T, Error>,
+) -> Result<T, Error> {
+    let temp = temp_path(dst);
+    match write(&temp) {
+        Ok(output) => {
+            std::fs::rename(&temp, dst)?;
+            Ok(output)
+        }
+        Err(e) => {
+            if let Err(remove_error) = std::fs::remove_file(&temp) {
+                if remove_error.kind() != ErrorKind::NotFound {
+                    warn!("could not remove {:?}: {:?}", temp, remove_error);
+                }
+            }
+            Err(e)
+        }
+    }
+}
+
+/// `true` if `path` is a temporary output or a progress manifest, which are not corpus files.
+pub(crate) fn is_progress_file(path: &Path) -> bool {
+    path.extension() == Some(OsStr::new(TEMP_EXTENSION))
+        || path.file_name() == Some(OsStr::new(PROGRESS_FILE))
+}
+
+/// Completed sources of an operation, identified by their path relative to the source folder.
+pub(crate) struct Progress {
+    path: PathBuf,
+    manifest: Mutex<File>,
+    completed: HashSet<String>,
+}
+
+impl Progress {
+    /// Open the progress manifest of `dst`.
+    ///
+    /// If `resume` is set, sources completed by previous runs are loaded.
+    /// Otherwise, the manifest is started anew.
+    pub fn open(dst: &Path, resume: bool) -> Result<Self, Error> {
+        let path = dst.join(PROGRESS_FILE);
+        let mut completed = HashSet::new();
+        if resume && path.is_file() {
+            for line in BufReader::new(File::open(&path)?).lines() {
+                completed.insert(line?);
+            }
+            info!(
+                "resuming: {} completed files in {:?}",
+                completed.len(),
+                path
+            );
+        }
+        let manifest = File::options()
+            .create(true)
+            .append(true)
+            .truncate(false)
+            .open(&path)?;
+        if !resume {
+            manifest.set_len(0)?;
+        }
+        Ok(Self {
+            path,
+            manifest: Mutex::new(manifest),
+            completed,
+        })
+    }
+
+    /// Key of `src` (relative to `root`) in the manifest.
+    fn key(src: &Path, root: &Path) -> String {
+        src.strip_prefix(root)
+            .unwrap_or(src)
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// `true` if `src` (relative to `root`) has been completed by a previous run.
+    pub fn is_completed(&self, src: &Path, root: &Path) -> bool {
+        self.completed.contains(&Self::key(src, root))
+    }
+
+    /// Record `src` (relative to `root`) as completed.
+    /// The manifest is synced, so that completion survives crashes.
+    pub fn complete(&self, src: &Path, root: &Path) -> Result<(), Error> {
+        let key = Self::key(src, root);
+        debug!("completed {key}");
+        let mut manifest = self
+            .manifest
+            .lock()
+            .map_err(|_| Error::Custom("progress manifest lock is poisoned".to_string()))?;
+        writeln!(manifest, "{key}")?;
+        manifest.sync_data()?;
+        Ok(())
+    }
+
+    /// Remove the manifest, once every source has been completed.
+    pub fn finish(self) -> Result<(), Error> {
+        drop(self.manifest);
+        std::fs::remove_file(&self.path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use tempfile::tempdir;
+
+    use crate::error::Error;
+
+    use super::{is_progress_file, temp_path, write_atomically, Progress, PROGRESS_FILE};
+
+    #[test]
+    fn test_progress() {
+        let dir = tempdir().unwrap();
+        let root = Path::new("/corpus");
+        let progress = Progress::open(dir.path(), false).unwrap();
+        progress.complete(&root.join("fr.jsonl"), root).unwrap();
+        progress.complete(&root.join("de/de.jsonl"), root).unwrap();
+
+        let progress = Progress::open(dir.path(), true).unwrap();
+        assert!(progress.is_completed(&root.join("fr.jsonl"), root));
+        assert!(progress.is_completed(&root.join("de/de.jsonl"), root));
+        assert!(!progress.is_completed(&root.join("en.jsonl"), root));
+
+        // not resuming forgets previous runs
+        let progress = Progress::open(dir.path(), false).unwrap();
+        assert!(!progress.is_completed(&root.join("fr.jsonl"), root));
+        let progress = Progress::open(dir.path(), true).unwrap();
+        assert!(!progress.is_completed(&root.join("fr.jsonl"), root));
+
+        progress.finish().unwrap();
+        assert!(!dir.path().join(PROGRESS_FILE).exists());
+    }
+
+    #[test]
+    fn test_progress_files() {
+        let temp = temp_path(Path::new("fr/fr.jsonl.gz"));
+        assert_eq!(temp, Path::new("fr/fr.jsonl.gz.tmp"));
+        assert!(is_progress_file(&temp));
+        assert!(is_progress_file(&Path::new("out").join(PROGRESS_FILE)));
+        assert!(!is_progress_file(Path::new("fr/fr.jsonl.gz")));
+    }
+
+    #[test]
+    fn test_write_atomically() {
+        let dir = tempdir().unwrap();
+        let dst = dir.path().join("fr.jsonl");
+        write_atomically(&dst, |temp| Ok(std::fs::write(temp, "foo")?)).unwrap();
+        assert_eq!(std::fs::read_to_string(&dst).unwrap(), "foo");
+
+        let dst = dir.path().join("de.jsonl");
+        let result: Result<(), Error> = write_atomically(&dst, |temp| {
+            std::fs::write(temp, "partial")?;
+            Err(Error::Custom("failed".to_string()))
+        });
+        assert!(result.is_err());
+        assert!(!dst.exists());
+        assert!(!temp_path(&dst).exists());
+    }
+}
@@ -0,0 +1,266 @@
+//! Single-pass sampling, for sources that can't be indexed (compressed files, stdin).
+//!
+//! Each document gets a random key, and the sample is made of the documents with the highest keys that fit
+//! in the budget, which is equivalent to shuffling the corpus and taking documents until the budget is reached.
+//! Weighted sampling uses the keys of Efraimidis and Spirakis (`u^(1/w)`), so that heavier documents are more
+//! likely to be kept.
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::BinaryHeap,
+    io::{BufRead, BufReader, Write},
+    path::Path,
+};
+
+use clap::{arg, Arg};
+use rand::Rng;
+
+use crate::{
+    error::Error,
+    ops::{open_decompressed, SampleBudget, SamplingRng},
+};
+
+/// Open `path` for a single-pass read: `-` is stdin, and compressed files are decompressed.
+pub(crate) fn open_stream(path: &Path) -> Result<Box<dyn BufRead>, Error> {
+    if path == Path::new("-") {
+        Ok(Box::new(BufReader::new(std::io::stdin())))
+    } else {
+        open_decompressed(path)
+    }
+}
+
+/// A sampled document.
+#[derive(Debug)]
+struct Entry {
+    key: f64,
+    ordinal: usize,
+    size: usize,
+    doc: Vec<u8>,
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Entry {}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Entry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key.total_cmp(&other.key)
+    }
+}
+
+/// Reservoir sampler over documents (lines).
+///
+/// The sample is kept in memory, and is written in corpus order by [Reservoir::finish].
+/// [SampleBudget::Fraction] budgets can't be known in advance, so they are sampled by keeping every document
+/// with the given probability, writing them right away.
+#[derive(Debug)]
+pub(crate) struct Reservoir {
+    budget: SampleBudget,
+    weighted: bool,
+    heap: BinaryHeap<Reverse<Entry>>,
+    /// Highest key of the evicted documents. Documents with lower keys are not sampled.
+    threshold: f64,
+    total_size: usize,
+    nb_read: usize,
+    nb_written: usize,
+}
+
+impl Reservoir {
+    /// Create a reservoir for `budget`.
+    /// If `weighted` is set, documents are weighted by their size in bytes.
+    pub fn new(budget: SampleBudget, weighted: bool) -> Self {
+        Self {
+            budget,
+            weighted,
+            heap: BinaryHeap::new(),
+            threshold: f64::NEG_INFINITY,
+            total_size: 0,
+            nb_read: 0,
+            nb_written: 0,
+        }
+    }
+
+    /// Command line arguments enabling (weighted) reservoir sampling.
+    pub fn args() -> [Arg<'static>; 2] {
+        [
+            arg!(--reservoir "Sample in a single pass, keeping the sample in memory. Allows sampling from stdin ('-').")
+                .required(false),
+            arg!(--weighted "With reservoir sampling, make larger documents more likely to be sampled.")
+                .requires("reservoir")
+                .required(false),
+        ]
+    }
+
+    /// Read documents of `r`. Blank lines are skipped.
+    ///
+    /// `measure` gives the size of a document in the unit of the budget (see [SampleBudget::measure]).
+    /// Documents sampled right away are written in `w`.
+    pub fn read<R, W, F>(
+        &mut self,
+        r: &mut R,
+        w: &mut W,
+        rng: &mut SamplingRng,
+        mut measure: F,
+    ) -> Result<(), Error>
+    where
+        R: BufRead,
+        W: Write,
+        F: FnMut(&[u8]) -> Result<usize, Error>,
+    {
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            if r.read_until(b'\n', &mut line)? == 0 {
+                break;
+            }
+            if line.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+            let ordinal = self.nb_read;
+            self.nb_read += 1;
+
+            if let SampleBudget::Fraction(fraction) = self.budget {
+                if rng.gen::<f64>() < fraction {
+                    Self::write_doc(w, &line)?;
+                    self.nb_written += 1;
+                }
+                continue;
+            }
+
+            // documents that don't fit in the budget are never sampled
+            let size = measure(&line)?;
+            if size > self.budget.size() {
+                continue;
+            }
+
+            // ln(u^(1/w)) has the same order as u^(1/w) and doesn't underflow
+            let weight = if self.weighted {
+                line.len() as f64
+            } else {
+                1.0
+            };
+            let key = (1.0 - rng.gen::<f64>()).ln() / weight;
+            if key <= self.threshold {
+                continue;
+            }
+
+            self.heap.push(Reverse(Entry {
+                key,
+                ordinal,
+                size,
+                doc: line.clone(),
+            }));
+            self.total_size += size;
+            while self.total_size > self.budget.size() {
+                if let Some(Reverse(evicted)) = self.heap.pop() {
+                    self.total_size -= evicted.size;
+                    self.threshold = evicted.key;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the sampled documents in `w`, in corpus order.
+    /// Returns the number of sampled and read documents.
+    pub fn finish<W: Write>(self, w: &mut W) -> Result<(usize, usize), Error> {
+        let mut entries: Vec<Entry> = self.heap.into_iter().map(|Reverse(e)| e).collect();
+        entries.sort_unstable_by_key(|e| e.ordinal);
+        for entry in &entries {
+            Self::write_doc(w, &entry.doc)?;
+        }
+        w.flush()?;
+        Ok((self.nb_written + entries.len(), self.nb_read))
+    }
+
+    #[inline]
+    fn write_doc<W: Write>(w: &mut W, doc: &[u8]) -> Result<(), Error> {
+        w.write_all(doc)?;
+        if doc.last() != Some(&b'\n') {
+            w.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::ops::{sampling_rng, SampleBudget};
+
+    use super::Reservoir;
+
+    fn corpus() -> String {
+        (0..100).map(|i| format!("doc {i}\n\n")).collect()
+    }
+
+    fn sample(budget: SampleBudget, weighted: bool, seed: u64) -> String {
+        let mut reservoir = Reservoir::new(budget, weighted);
+        let mut rng = sampling_rng(seed);
+        let mut w = Vec::new();
+        reservoir
+            .read(&mut Cursor::new(corpus()), &mut w, &mut rng, |doc| {
+                Ok(budget.measure(&String::from_utf8_lossy(doc)))
+            })
+            .unwrap();
+        let (nb_sampled, nb_read) = reservoir.finish(&mut w).unwrap();
+        assert_eq!(nb_read, 100);
+        let w = String::from_utf8(w).unwrap();
+        assert_eq!(w.lines().count(), nb_sampled);
+        w
+    }
+
+    #[test]
+    fn test_docs() {
+        let sample = sample(SampleBudget::Docs(10), false, 0);
+        let ordinals: Vec<usize> = sample
+            .lines()
+            .map(|line| line.strip_prefix("doc ").unwrap().parse().unwrap())
+            .collect();
+        assert_eq!(ordinals.len(), 10);
+
+        // corpus order, no duplicates
+        let mut sorted = ordinals.clone();
+        sorted.sort_unstable();
+        sorted.dedup();
+        assert_eq!(ordinals, sorted);
+    }
+
+    #[test]
+    fn test_bytes() {
+        for weighted in [false, true] {
+            let sample = sample(SampleBudget::Bytes(100), weighted, 0);
+            assert!(!sample.is_empty());
+            assert!(sample.len() <= 100);
+        }
+    }
+
+    #[test]
+    fn test_fraction() {
+        let sample = sample(SampleBudget::Fraction(0.5), false, 0);
+        let nb_sampled = sample.lines().count();
+        assert!(nb_sampled > 20 && nb_sampled < 80);
+    }
+
+    #[test]
+    fn test_seeded() {
+        assert_eq!(
+            sample(SampleBudget::Docs(10), true, 1),
+            sample(SampleBudget::Docs(10), true, 1)
+        );
+        assert_ne!(
+            sample(SampleBudget::Docs(10), false, 1),
+            sample(SampleBudget::Docs(10), false, 2)
+        );
+    }
+}
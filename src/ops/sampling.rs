@@ -1,16 +1,188 @@
 use std::path::Path;
 
+use clap::{arg, Arg, ArgGroup, ArgMatches};
+use log::info;
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+
 use crate::error::Error;
 
+#[derive(Debug, Clone, Copy)]
 pub enum SamplingKind {
     WithReplacement,
     WithoutReplacement,
 }
+
+impl SamplingKind {
+    /// Command line argument selecting sampling with replacement.
+    pub fn arg() -> Arg<'static> {
+        arg!(--replacement "Sample with replacement: documents drawn several times are written as many times.").required(false)
+    }
+
+    /// Build sampling kind from the argument added with [SamplingKind::arg].
+    pub fn from_matches(matches: &ArgMatches) -> Self {
+        if matches.is_present("replacement") {
+            Self::WithReplacement
+        } else {
+            Self::WithoutReplacement
+        }
+    }
+}
+
+/// Maximum size of a sample.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SampleBudget {
+    /// Total size of the sampled documents, in bytes.
+    Bytes(usize),
+    /// Number of sampled documents.
+    Docs(usize),
+    /// Total number of (whitespace-separated) tokens of the sampled documents.
+    Tokens(usize),
+    /// Fraction of the documents of the corpus, in `]0, 1]`.
+    /// Has to be resolved into a number of documents (see [SampleBudget::resolve]) before sampling.
+    Fraction(f64),
+}
+
+impl SampleBudget {
+    /// Part of the budget used by `text`.
+    ///
+    /// Text is measured in documents for [SampleBudget::Fraction] budgets.
+    pub fn measure(&self, text: &str) -> usize {
+        match self {
+            Self::Bytes(_) => text.len(),
+            Self::Tokens(_) => text.split_whitespace().count(),
+            Self::Docs(_) | Self::Fraction(_) => 1,
+        }
+    }
+
+    /// Resolve fractions of a corpus of `nb_docs` documents into a number of documents.
+    /// Other budgets are left as is.
+    pub fn resolve(self, nb_docs: usize) -> Self {
+        match self {
+            Self::Fraction(fraction) => Self::Docs((nb_docs as f64 * fraction).round() as usize),
+            budget => budget,
+        }
+    }
+
+    /// Total budget.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the budget is an unresolved fraction.
+    pub fn size(&self) -> usize {
+        match self {
+            Self::Bytes(size) | Self::Docs(size) | Self::Tokens(size) => *size,
+            Self::Fraction(_) => panic!("fraction budgets have to be resolved before sampling"),
+        }
+    }
+
+    /// Command line arguments setting the budget. Exactly one of them has to be provided, see [SampleBudget::group].
+    pub fn args() -> [Arg<'static>; 4] {
+        [
+            arg!(--bytes <BYTES> "Sample size, in bytes.").required(false),
+            arg!(--docs <N> "Sample size, in documents.").required(false),
+            arg!(--tokens <N> "Sample size, in whitespace-separated tokens.").required(false),
+            arg!(--fraction <FRACTION> "Sample size, as a fraction of the corpus documents (e.g. 0.01).")
+                .required(false),
+        ]
+    }
+
+    /// Group of the arguments added with [SampleBudget::args].
+    pub fn group() -> ArgGroup<'static> {
+        ArgGroup::new("budget")
+            .args(&["bytes", "docs", "tokens", "fraction"])
+            .required(true)
+    }
+
+    /// Build budget from arguments added with [SampleBudget::args].
+    pub fn from_matches(matches: &ArgMatches) -> Result<Self, Error> {
+        let parse = |name: &str| {
+            matches.value_of(name).map(|v| {
+                v.parse::<usize>()
+                    .map_err(|_| Error::Custom(format!("'{name}' has to be a number.")))
+            })
+        };
+        if let Some(bytes) = parse("bytes") {
+            Ok(Self::Bytes(bytes?))
+        } else if let Some(docs) = parse("docs") {
+            Ok(Self::Docs(docs?))
+        } else if let Some(tokens) = parse("tokens") {
+            Ok(Self::Tokens(tokens?))
+        } else if let Some(fraction) = matches.value_of("fraction") {
+            match fraction.parse::<f64>() {
+                Ok(fraction) if fraction > 0.0 && fraction <= 1.0 => Ok(Self::Fraction(fraction)),
+                _ => Err(Error::Custom(
+                    "'fraction' has to be a number in ]0, 1].".to_string(),
+                )),
+            }
+        } else {
+            Err(Error::Custom("No sample size provided.".to_string()))
+        }
+    }
+}
+
+/// Random number generator used by sampling ops.
+///
+/// ChaCha20 is used rather than [rand::rngs::StdRng], whose algorithm may change between `rand` versions:
+/// a given seed always yields the same sample.
+pub type SamplingRng = ChaCha20Rng;
+
+/// Build a sampling RNG from `seed`.
+pub fn sampling_rng(seed: u64) -> SamplingRng {
+    SamplingRng::seed_from_u64(seed)
+}
+
+/// Command line argument setting the sampling seed.
+pub fn seed_arg() -> Arg<'static> {
+    arg!(--seed <SEED> "Seed of the random number generator. Random if not provided.")
+        .required(false)
+}
+
+/// Build a sampling RNG from the argument added with [seed_arg].
+/// The seed is logged, so that random samples can be reproduced.
+pub fn rng_from_matches(matches: &ArgMatches) -> Result<SamplingRng, Error> {
+    let seed: u64 = match matches.value_of("seed") {
+        Some(seed) => seed
+            .parse()
+            .map_err(|_| Error::Custom("'seed' has to be a number.".to_string()))?,
+        None => rand::random(),
+    };
+    info!("sampling with seed {seed}");
+    Ok(sampling_rng(seed))
+}
+
 pub trait SampleText {
+    /// Sample `src` into `dst`, up to `budget`. Samples are reproducible: the same `rng` state yields the same output.
     fn sample(
         src: &Path,
         dst: &Path,
-        sample_size: usize,
+        budget: SampleBudget,
         sampling: SamplingKind,
+        rng: &mut SamplingRng,
     ) -> Result<(), Error>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::SampleBudget;
+
+    #[test]
+    fn test_measure() {
+        let text = "foo bar\tbaz\n";
+        assert_eq!(SampleBudget::Bytes(0).measure(text), 12);
+        assert_eq!(SampleBudget::Docs(0).measure(text), 1);
+        assert_eq!(SampleBudget::Tokens(0).measure(text), 3);
+    }
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(
+            SampleBudget::Fraction(0.01).resolve(1234),
+            SampleBudget::Docs(12)
+        );
+        assert_eq!(
+            SampleBudget::Bytes(10).resolve(1234),
+            SampleBudget::Bytes(10)
+        );
+    }
+}
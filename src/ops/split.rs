@@ -1,6 +1,6 @@
 /*! Splitting operations.
 
- These operations split the corpus into smaller files of a defined max size.
+ These operations split the corpus into smaller files of a defined max size, number of documents or number of lines.
 !*/
 use log::{debug, error, info, warn};
 use std::{
@@ -10,29 +10,98 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use crate::error::Error;
+use crate::{
+    error::Error,
+    ops::{is_corpus_file, temp_path, Progress},
+};
+use clap::{arg, Arg, ArgMatches};
 use rayon::iter::{ParallelBridge, ParallelIterator};
 
+/// When [SplitWriter] moves on to a new file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    /// Files hold at most this many bytes.
+    Bytes(usize),
+    /// Files hold at most this many documents.
+    Docs(usize),
+    /// Files hold at most this many lines.
+    Lines(usize),
+}
+
+impl Rotation {
+    /// Command line arguments setting the rotation. `--size` is in MBytes.
+    ///
+    /// If `default_size` is set, it is the default of `--size`.
+    pub fn args(default_size: Option<&'static str>) -> [Arg<'static>; 3] {
+        let size = arg!(-s --size <SIZE_MB> "Split size (in MBytes)").required(false);
+        let size = match default_size {
+            Some(default_size) => size.default_value(default_size),
+            None => size,
+        };
+        [
+            size,
+            arg!(--docs_per_file <NB_DOCS> "Split every NB_DOCS documents.")
+                .alias("docs-per-file")
+                .required(false)
+                .conflicts_with_all(&["size", "lines_per_file"]),
+            arg!(--lines_per_file <NB_LINES> "Split every NB_LINES lines.")
+                .alias("lines-per-file")
+                .required(false)
+                .conflicts_with_all(&["size", "docs_per_file"]),
+        ]
+    }
+
+    /// Build the rotation from the arguments added with [Rotation::args].
+    /// Returns `None` if no rotation has been set and `--size` has no default.
+    pub fn from_matches(matches: &ArgMatches) -> Result<Option<Self>, Error> {
+        let parse = |name: &str| -> Result<Option<usize>, Error> {
+            matches
+                .value_of(name)
+                .map(|value| match value.parse::<usize>() {
+                    Ok(value) if value > 0 => Ok(value),
+                    _ => Err(Error::Custom(format!(
+                        "'{name}' has to be a positive number, got {value:?}"
+                    ))),
+                })
+                .transpose()
+        };
+        if let Some(nb_docs) = parse("docs_per_file")? {
+            Ok(Some(Self::Docs(nb_docs)))
+        } else if let Some(nb_lines) = parse("lines_per_file")? {
+            Ok(Some(Self::Lines(nb_lines)))
+        } else {
+            // convert from MBytes into Bytes
+            Ok(parse("size")?.map(|size| Self::Bytes(size * 1_000_000)))
+        }
+    }
+}
+
 /// Rotating file writer.
 ///
 /// Files are named `foo.bar`, and if there is a need of more than one file,
 /// `foo.bar` is renamed `foo_part_1.bar`, and so on.
-struct SplitWriter {
+///
+/// Each `write` call is a document, and files are only rotated between documents.
+pub(crate) struct SplitWriter {
     dst: PathBuf,
     fp: Option<File>,
-    max_size: usize,
+    rotation: Rotation,
     current_size: usize,
+    current_docs: usize,
+    current_lines: usize,
     nb_files: u32,
 }
 
 impl SplitWriter {
-    /// Create a new writer. `max_size` is in bytes.
-    pub fn new(dst: &Path, max_size: usize) -> Self {
+    /// Create a new writer, rotating files according to `rotation`.
+    pub fn new(dst: &Path, rotation: Rotation) -> Self {
         Self {
             dst: dst.to_path_buf(),
             fp: None,
-            max_size,
+            rotation,
             current_size: 0,
+            current_docs: 0,
+            current_lines: 0,
             nb_files: 0,
         }
     }
@@ -116,6 +185,8 @@ impl SplitWriter {
             debug!("Rotating: creating {:?}", filename);
             self.fp = Some(File::create(&filename)?);
             self.current_size = 0;
+            self.current_docs = 0;
+            self.current_lines = 0;
             Ok(())
         }
     }
@@ -128,20 +199,37 @@ impl Write for SplitWriter {
             self.rotate_file()?;
         }
 
+        let nb_lines = buf.iter().filter(|b| **b == b'\n').count();
+
         // create new split if current is full
-        if self.current_size + buf.len() > self.max_size {
+        let is_full = match self.rotation {
+            Rotation::Bytes(max_size) => self.current_size + buf.len() > max_size,
+            Rotation::Docs(max_docs) => self.current_docs >= max_docs,
+            Rotation::Lines(max_lines) => self.current_lines + nb_lines > max_lines,
+        };
+        // don't leave an empty file behind an oversized entry
+        if is_full && (self.current_docs > 0) {
             self.rotate_file()?;
         }
 
         // print warning if buf very large
-        if buf.len() > self.max_size {
-            warn!("Current entry is too large: Split size limits won't be enforced (entry size: {}, max size:{}", buf.len(), self.max_size);
+        match self.rotation {
+            Rotation::Bytes(max_size) if buf.len() > max_size => {
+                warn!("Current entry is too large: Split size limits won't be enforced (entry size: {}, max size:{}", buf.len(), max_size);
+            }
+            Rotation::Lines(max_lines) if nb_lines > max_lines => {
+                warn!("Current entry is too large: Split line limits won't be enforced (entry lines: {}, max lines:{}", nb_lines, max_lines);
+            }
+            _ => (),
         }
 
         if let Some(fp) = &mut self.fp {
-            let bytes_written = fp.write(buf)?;
-            self.current_size += bytes_written;
-            Ok(bytes_written)
+            // documents are written whole, so that rotations don't split them
+            fp.write_all(buf)?;
+            self.current_size += buf.len();
+            self.current_docs += 1;
+            self.current_lines += nb_lines;
+            Ok(buf.len())
         } else {
             Err(std::io::Error::new(
                 std::io::ErrorKind::NotFound,
@@ -160,16 +248,23 @@ impl Write for SplitWriter {
 }
 pub trait Split {
     /// Assumes all corpus files to be in the same dict (not in separate folders)
+    ///
+    /// Each file is split into a temporary folder, renamed once complete and recorded in a progress manifest
+    /// (see [crate::ops::Progress]). If `resume` is set, files completed by a previous run are skipped,
+    /// and `dst` doesn't have to be empty.
     fn split_all(
         src: &Path,
         dst: &Path,
-        split_size: usize,
+        rotation: Rotation,
         num_threads: usize,
+        resume: bool,
     ) -> Result<(), Error> {
-        rayon::ThreadPoolBuilder::new()
-            .num_threads(num_threads)
-            .build_global()?;
-        debug!("Built rayon threadpool with num_threads={num_threads}");
+        if num_threads != 1 {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(num_threads)
+                .build_global()?;
+            debug!("Built rayon threadpool with num_threads={num_threads}");
+        }
 
         //check existence of folder and/or its emptyness
         // if dst.exists() {
@@ -188,17 +283,18 @@ pub trait Split {
             std::fs::create_dir(dst)?;
         }
 
-        if dst.read_dir()?.count() != 0 {
-            error!("Destination directory is not empty!");
+        if !resume && dst.read_dir()?.count() != 0 {
+            error!("Destination directory is not empty! Use --resume to resume a previous run.");
             return Err(std::io::Error::new(ErrorKind::AlreadyExists, format!("{:?}", dst)).into());
         }
+        let progress = Progress::open(dst, resume)?;
 
         // filter out folders and errors (printing then discarding them)
         let files = files
             .filter_map(|p| match p {
                 Ok(path) => {
                     let path = path.path();
-                    if path.is_file() {
+                    if is_corpus_file(&path) {
                         Some(path)
                     } else {
                         None
@@ -215,20 +311,34 @@ pub trait Split {
 
         let r: Vec<Result<(), Error>> = files
             .map(|file| {
+                if progress.is_completed(&file, src) {
+                    info!("Skipping  {:?}: already split", file);
+                    return Ok(());
+                }
+
                 // extract filename
                 // send to a split file
                 if let Some(filename) = file.file_stem() {
-                    // Create folder for file
+                    // Create folder for file, under a temporary name
                     let dest_folder: PathBuf = [dst.as_os_str(), filename].iter().collect();
-                    std::fs::create_dir(&dest_folder)?;
+                    let temp_folder = temp_path(&dest_folder);
+                    for stale in [&temp_folder, &dest_folder] {
+                        if resume && stale.exists() {
+                            warn!("removing {:?}, left by an interrupted run", stale);
+                            std::fs::remove_dir_all(stale)?;
+                        }
+                    }
+                    std::fs::create_dir(&temp_folder)?;
 
                     // create base file name
-                    let mut dest_file = dest_folder.clone();
+                    let mut dest_file = temp_folder.clone();
                     let file_name = file.file_name().unwrap();
                     dest_file.push(file_name);
 
                     info!("Splitting {:?} in {:?}", file, dest_folder);
-                    Self::split_file(&file, &dest_file, split_size)?;
+                    Self::split_file(&file, &dest_file, rotation)?;
+                    std::fs::rename(&temp_folder, &dest_folder)?;
+                    progress.complete(&file, src)?;
                     info!("Done      {:?} in {:?}", file, dest_folder);
                 };
                 Ok(())
@@ -239,7 +349,7 @@ pub trait Split {
         let errors: Vec<Error> = r.into_iter().filter_map(|result| result.err()).collect();
 
         if errors.is_empty() {
-            Ok(())
+            progress.finish()
         } else {
             // print errors
             for e in errors {
@@ -251,34 +361,51 @@ pub trait Split {
         }
     }
 
-    /// Split a single file into multiple fixed size ones
-    fn split_file(src: &Path, dst: &Path, split_size: usize) -> Result<(), Error> {
-        debug!("Using default splitter with size {split_size}");
+    /// Split a single file into multiple ones, according to `rotation`
+    fn split_file(src: &Path, dst: &Path, rotation: Rotation) -> Result<(), Error> {
+        debug!("Using default splitter with rotation {rotation:?}");
         let corpus = File::open(&src)?;
         let corpus_buf = BufReader::new(corpus);
         let documents = corpus_buf.lines();
 
-        let mut split_writer = SplitWriter::new(dst, split_size);
+        let mut split_writer = SplitWriter::new(dst, rotation);
         for document in documents {
             let mut document = document?;
             document.push('\n');
             split_writer.write_all(document.as_bytes())?;
         }
+        split_writer.flush()?;
 
         Ok(())
     }
 }
 
+/// Get the part number of a split file (`foo_part_12.bar` is part `12`).
+///
+/// Files that have not been split (`foo.bar`) have no part number.
+pub(crate) fn part_number(path: &Path) -> Option<u64> {
+    let filename = path.file_name()?.to_str()?;
+    let stem = filename.split('.').next()?;
+    let (_, part) = stem.rsplit_once("_part_")?;
+    part.parse().ok()
+}
+
+/// Sort split files in part order (`foo_part_2.bar` comes before `foo_part_10.bar`)
+/// rather than in lexicographic order.
+pub(crate) fn sort_parts(files: &mut [PathBuf]) {
+    files.sort_by(|a, b| (part_number(a), a).cmp(&(part_number(b), b)));
+}
+
 #[cfg(test)]
 mod tests {
-    use std::path::PathBuf;
+    use std::{fs::File, io::Write, path::PathBuf};
 
-    use super::SplitWriter;
+    use super::{part_number, sort_parts, Rotation, SplitWriter};
 
     #[test]
     fn file_name_single() {
         let p = PathBuf::from("foo.txt");
-        let mut s = SplitWriter::new(&p, 100);
+        let mut s = SplitWriter::new(&p, Rotation::Bytes(100));
         assert_eq!(s.next_filename().unwrap(), p);
     }
 
@@ -286,11 +413,64 @@ mod tests {
     fn file_name_multiple() {
         let p = PathBuf::from("foo.txt");
         let expected = PathBuf::from("foo_part_3.txt");
-        let mut s = SplitWriter::new(&p, 100);
+        let mut s = SplitWriter::new(&p, Rotation::Bytes(100));
         s.next_filename();
         s.next_filename();
         s.next_filename();
         let res = s.next_filename();
         assert_eq!(res.unwrap(), expected);
     }
+
+    #[test]
+    fn test_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let docs = ["a\n", "b\nb\n", "c\n", "d\nd\nd\n", "e\n"];
+        for (rotation, expected) in [
+            (Rotation::Docs(2), vec!["a\nb\nb\n", "c\nd\nd\nd\n", "e\n"]),
+            (
+                Rotation::Lines(3),
+                vec!["a\nb\nb\n", "c\n", "d\nd\nd\n", "e\n"],
+            ),
+            (
+                Rotation::Bytes(4),
+                vec!["a\n", "b\nb\n", "c\n", "d\nd\nd\n", "e\n"],
+            ),
+        ] {
+            let dst = dir.path().join(format!("{rotation:?}.txt"));
+            let mut w = SplitWriter::new(&dst, rotation);
+            for doc in docs {
+                w.write_all(doc.as_bytes()).unwrap();
+            }
+            w.flush().unwrap();
+
+            let files: Vec<String> = (1..=expected.len())
+                .map(|part| {
+                    let file = SplitWriter::format_filename(&dst, part as u64).unwrap();
+                    std::fs::read_to_string(file).unwrap()
+                })
+                .collect();
+            assert_eq!(files, expected, "{rotation:?}");
+        }
+    }
+
+    #[test]
+    fn test_part_number() {
+        assert_eq!(part_number(&PathBuf::from("fr/fr_part_12.jsonl")), Some(12));
+        assert_eq!(part_number(&PathBuf::from("fr_part_3.jsonl.gz")), Some(3));
+        assert_eq!(part_number(&PathBuf::from("fr.jsonl")), None);
+    }
+
+    #[test]
+    fn test_sort_parts() {
+        let mut files: Vec<PathBuf> = ["fr_part_10.txt", "fr_part_2.txt", "fr_part_1.txt"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        sort_parts(&mut files);
+        let expected: Vec<PathBuf> = ["fr_part_1.txt", "fr_part_2.txt", "fr_part_10.txt"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        assert_eq!(files, expected);
+    }
 }
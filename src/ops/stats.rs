@@ -0,0 +1,411 @@
+//! Corpus statistics, written as JSON and Markdown reports (e.g. to fill dataset cards).
+//!
+//! Documents are read as JSON values rather than through [oscar_io::oscar_doc::SplitFolderReader],
+//! which can't read compressed parts, reads index and checksum files as documents,
+//! and panics on languages unknown to oscar-io.
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    fs::File,
+    io::{BufRead, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use log::info;
+use serde_json::{json, Value};
+
+use crate::{
+    error::Error,
+    ops::{for_each_language, host, open_decompressed, source_languages},
+};
+
+/// Number of bins of the identification probability histogram.
+const PROB_BINS: usize = 10;
+
+/// Name of the overall reports (`stats.json`, `stats.md`).
+pub const STATS_REPORT: &str = "stats";
+
+/// Statistics of a set of documents.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(crate) struct CorpusStats {
+    docs: u64,
+    lines: u64,
+    words: u64,
+    bytes: u64,
+    /// Documents without annotations.
+    clean_docs: u64,
+    /// Documents per annotation tag.
+    tags: HashMap<String, u64>,
+    /// Documents per identification probability bin (`[0, 0.1[`, `[0.1, 0.2[`, ..., `[0.9, 1]`).
+    probs: [u64; PROB_BINS],
+    /// Documents without identification.
+    unidentified_docs: u64,
+    /// Documents per URL host.
+    domains: HashMap<String, u64>,
+}
+
+impl CorpusStats {
+    /// Account for a document.
+    pub fn add_doc(&mut self, doc: &Value) -> Result<(), Error> {
+        let content = match doc["content"].as_str() {
+            Some(content) => content,
+            None => return Err(Error::MissingContent(doc.clone())),
+        };
+        self.docs += 1;
+        self.lines += content.lines().count() as u64;
+        self.words += content.split_whitespace().count() as u64;
+        self.bytes += content.len() as u64;
+
+        match doc["metadata"]["annotation"].as_array() {
+            Some(tags) if !tags.is_empty() => {
+                for tag in tags.iter().filter_map(Value::as_str) {
+                    *self.tags.entry(tag.to_string()).or_default() += 1;
+                }
+            }
+            _ => self.clean_docs += 1,
+        }
+
+        match doc["metadata"]["identification"]["prob"].as_f64() {
+            Some(prob) => {
+                let bin = ((prob * PROB_BINS as f64) as usize).min(PROB_BINS - 1);
+                self.probs[bin] += 1;
+            }
+            None => self.unidentified_docs += 1,
+        }
+
+        if let Some(domain) = doc["warc_headers"]["warc-target-uri"]
+            .as_str()
+            .and_then(host)
+        {
+            *self.domains.entry(domain).or_default() += 1;
+        }
+        Ok(())
+    }
+
+    /// Account for the documents (non blank lines) of a reader.
+    pub fn add_docs<R: BufRead>(&mut self, r: R) -> Result<(), Error> {
+        for line in r.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            self.add_doc(&serde_json::from_str(&line)?)?;
+        }
+        Ok(())
+    }
+
+    /// Add the statistics of `other`.
+    pub fn merge(&mut self, other: &Self) {
+        self.docs += other.docs;
+        self.lines += other.lines;
+        self.words += other.words;
+        self.bytes += other.bytes;
+        self.clean_docs += other.clean_docs;
+        for (tag, count) in &other.tags {
+            *self.tags.entry(tag.clone()).or_default() += count;
+        }
+        for (bin, count) in self.probs.iter_mut().zip(other.probs) {
+            *bin += count;
+        }
+        self.unidentified_docs += other.unidentified_docs;
+        for (domain, count) in &other.domains {
+            *self.domains.entry(domain.clone()).or_default() += count;
+        }
+    }
+
+    /// Entries of `counts` by decreasing count (then by name), at most `top` of them.
+    fn sorted(counts: &HashMap<String, u64>, top: Option<usize>) -> Vec<(&str, u64)> {
+        let mut sorted: Vec<(&str, u64)> = counts.iter().map(|(k, v)| (k.as_str(), *v)).collect();
+        sorted.sort_unstable_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+        if let Some(top) = top {
+            sorted.truncate(top);
+        }
+        sorted
+    }
+
+    /// Bounds of the `bin`-th probability bin.
+    fn prob_bin(bin: usize) -> (f64, f64) {
+        (
+            bin as f64 / PROB_BINS as f64,
+            (bin + 1) as f64 / PROB_BINS as f64,
+        )
+    }
+
+    /// JSON report, with the `top_domains` most frequent domains.
+    pub fn to_json(&self, top_domains: usize) -> Value {
+        let probs: Vec<Value> = self
+            .probs
+            .iter()
+            .enumerate()
+            .map(|(bin, count)| {
+                let (min, max) = Self::prob_bin(bin);
+                json!({"min": min, "max": max, "docs": count})
+            })
+            .collect();
+        let domains: Vec<Value> = Self::sorted(&self.domains, Some(top_domains))
+            .into_iter()
+            .map(|(domain, count)| json!({"domain": domain, "docs": count}))
+            .collect();
+        json!({
+            "docs": self.docs,
+            "lines": self.lines,
+            "words": self.words,
+            "bytes": self.bytes,
+            "clean_docs": self.clean_docs,
+            "tags": self.tags,
+            "identification_prob": probs,
+            "unidentified_docs": self.unidentified_docs,
+            "distinct_domains": self.domains.len(),
+            "top_domains": domains,
+        })
+    }
+
+    /// Share of the documents, in percent.
+    fn share(&self, count: u64) -> f64 {
+        if self.docs == 0 {
+            0.0
+        } else {
+            100.0 * count as f64 / self.docs as f64
+        }
+    }
+
+    /// Markdown report, with the `top_domains` most frequent domains.
+    /// Sections start at `##`, so that reports can be embedded under a title.
+    pub fn to_markdown(&self, top_domains: usize) -> String {
+        // writing in a String can't fail
+        let mut md = String::new();
+        md.push_str("| | count |\n|---|---:|\n");
+        for (name, count) in [
+            ("documents", self.docs),
+            ("lines", self.lines),
+            ("words", self.words),
+            ("bytes", self.bytes),
+        ] {
+            writeln!(md, "| {name} | {count} |").unwrap();
+        }
+
+        md.push_str("\n## Annotations\n\n| tag | documents | share |\n|---|---:|---:|\n");
+        writeln!(
+            md,
+            "| *(none)* | {} | {:.2}% |",
+            self.clean_docs,
+            self.share(self.clean_docs)
+        )
+        .unwrap();
+        for (tag, count) in Self::sorted(&self.tags, None) {
+            writeln!(md, "| {tag} | {count} | {:.2}% |", self.share(count)).unwrap();
+        }
+
+        md.push_str(
+            "\n## Identification probability\n\n| probability | documents | share |\n|---|---:|---:|\n",
+        );
+        for (bin, count) in self.probs.iter().enumerate() {
+            let (min, max) = Self::prob_bin(bin);
+            let closing = if bin == PROB_BINS - 1 { ']' } else { '[' };
+            writeln!(
+                md,
+                "| [{min:.1}, {max:.1}{closing} | {count} | {:.2}% |",
+                self.share(*count)
+            )
+            .unwrap();
+        }
+        if self.unidentified_docs > 0 {
+            writeln!(
+                md,
+                "| *(none)* | {} | {:.2}% |",
+                self.unidentified_docs,
+                self.share(self.unidentified_docs)
+            )
+            .unwrap();
+        }
+
+        writeln!(
+            md,
+            "\n## Top domains\n\n{} distinct domains.\n\n| domain | documents | share |\n|---|---:|---:|",
+            self.domains.len()
+        )
+        .unwrap();
+        for (domain, count) in Self::sorted(&self.domains, Some(top_domains)) {
+            writeln!(md, "| {domain} | {count} | {:.2}% |", self.share(count)).unwrap();
+        }
+        md
+    }
+}
+
+pub trait Stats {
+    /// Statistics of the documents of `files`.
+    fn files_stats(files: &[PathBuf]) -> Result<CorpusStats, Error> {
+        let mut stats = CorpusStats::default();
+        for file in files {
+            stats.add_docs(open_decompressed(file)?)?;
+        }
+        Ok(stats)
+    }
+
+    /// Compute statistics of each language of the corpus at `src` in parallel,
+    /// writing `<lang>.json` and `<lang>.md` reports in `dst`, along with overall `stats.json` and `stats.md` ones.
+    fn stats(src: &Path, dst: &Path, top_domains: usize, num_threads: usize) -> Result<(), Error> {
+        let languages = source_languages(src)?;
+        std::fs::create_dir_all(dst)?;
+        let results = for_each_language(
+            &languages,
+            num_threads,
+            "computing statistics of",
+            |lang, files| {
+                info!("computing statistics of {lang}");
+                let stats = Self::files_stats(files)?;
+                write_json(
+                    &dst.join(format!("{lang}.json")),
+                    &stats.to_json(top_domains),
+                )?;
+                let md = format!("# {lang}\n\n{}", stats.to_markdown(top_domains));
+                std::fs::write(dst.join(format!("{lang}.md")), md)?;
+                Ok(stats)
+            },
+        )?;
+
+        let mut total = CorpusStats::default();
+        let mut lang_stats = Vec::with_capacity(languages.len());
+        for ((lang, _), stats) in languages.iter().zip(results) {
+            total.merge(&stats);
+            lang_stats.push((lang.as_str(), stats));
+        }
+
+        let mut report = total.to_json(top_domains);
+        report["languages"] = lang_stats
+            .iter()
+            .map(|(lang, stats)| {
+                json!({
+                    "lang": lang,
+                    "docs": stats.docs,
+                    "lines": stats.lines,
+                    "words": stats.words,
+                    "bytes": stats.bytes,
+                })
+            })
+            .collect();
+        write_json(&dst.join(format!("{STATS_REPORT}.json")), &report)?;
+
+        let mut md = String::from("# Overall\n\n## Languages\n\n| language | documents | lines | words | bytes |\n|---|---:|---:|---:|---:|\n");
+        for (lang, stats) in &lang_stats {
+            writeln!(
+                md,
+                "| {lang} | {} | {} | {} | {} |",
+                stats.docs, stats.lines, stats.words, stats.bytes
+            )
+            .unwrap();
+        }
+        writeln!(
+            md,
+            "| **total** | {} | {} | {} | {} |\n\n## Totals\n",
+            total.docs, total.lines, total.words, total.bytes
+        )
+        .unwrap();
+        md.push_str(&total.to_markdown(top_domains));
+        std::fs::write(dst.join(format!("{STATS_REPORT}.md")), md)?;
+        Ok(())
+    }
+}
+
+/// Write a pretty-printed JSON value at `path`.
+fn write_json(path: &Path, value: &Value) -> Result<(), Error> {
+    let mut w = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(&mut w, value)?;
+    writeln!(w)?;
+    w.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    use super::{CorpusStats, Stats};
+
+    struct StatsTest;
+    impl Stats for StatsTest {}
+
+    fn doc(content: &str, tags: Option<Vec<&str>>, prob: f64, url: &str) -> String {
+        json!({
+            "content": content,
+            "warc_headers": {"warc-target-uri": url},
+            "metadata": {
+                "identification": {"label": "fr", "prob": prob},
+                "annotation": tags,
+                "sentence_identifications": [],
+            },
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn test_add_docs() {
+        let corpus = [
+            doc("foo bar\nbaz", None, 0.95, "https://a.com/1"),
+            doc("quux", Some(vec!["tiny", "noisy"]), 0.42, "https://a.com/2"),
+            doc("é", Some(vec!["tiny"]), 1.0, "http://B.org"),
+        ]
+        .join("\n\n");
+        let mut stats = CorpusStats::default();
+        stats.add_docs(corpus.as_bytes()).unwrap();
+
+        assert_eq!(stats.docs, 3);
+        assert_eq!(stats.lines, 4);
+        assert_eq!(stats.words, 5);
+        assert_eq!(stats.bytes, 17);
+        assert_eq!(stats.clean_docs, 1);
+        assert_eq!(stats.tags["tiny"], 2);
+        assert_eq!(stats.probs[9], 2);
+        assert_eq!(stats.probs[4], 1);
+        assert_eq!(CorpusStats::sorted(&stats.domains, Some(1)), [("a.com", 2)]);
+
+        let mut merged = stats.clone();
+        merged.merge(&stats);
+        assert_eq!(merged.docs, 6);
+        assert_eq!(merged.domains["b.org"], 2);
+
+        assert!(stats.add_docs("{}".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_stats() {
+        let dir = tempdir().unwrap();
+        let src = dir.path().join("src");
+        std::fs::create_dir_all(src.join("fr")).unwrap();
+        std::fs::write(
+            src.join("fr/fr_part_1.jsonl"),
+            doc("un deux", None, 0.9, "https://a.fr"),
+        )
+        .unwrap();
+        std::fs::write(
+            src.join("fr/fr_part_2.jsonl"),
+            doc("trois", Some(vec!["tiny"]), 0.8, "https://b.fr"),
+        )
+        .unwrap();
+        std::fs::write(src.join("de.jsonl"), doc("eins", None, 0.7, "https://a.de")).unwrap();
+
+        let dst = dir.path().join("dst");
+        StatsTest::stats(&src, &dst, 10, 1).unwrap();
+
+        let fr: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(dst.join("fr.json")).unwrap()).unwrap();
+        assert_eq!(fr["docs"], 2);
+        assert_eq!(fr["words"], 3);
+        assert_eq!(fr["tags"]["tiny"], 1);
+
+        let overall: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(dst.join("stats.json")).unwrap())
+                .unwrap();
+        assert_eq!(overall["docs"], 3);
+        assert_eq!(overall["languages"][0]["lang"], "de");
+        assert_eq!(overall["distinct_domains"], 3);
+
+        let md = std::fs::read_to_string(dst.join("stats.md")).unwrap();
+        assert!(md.contains("| fr | 2 | 2 | 3 | 12 |"));
+        assert!(md.contains("| **total** | 3 | 3 | 4 | 16 |"));
+        assert!(std::fs::read_to_string(dst.join("de.md"))
+            .unwrap()
+            .starts_with("# de\n"));
+    }
+}
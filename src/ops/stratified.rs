@@ -0,0 +1,380 @@
+//! Stratified sampling of a multilingual corpus.
+//!
+//! The budget is split into per-language quotas, either proportionally to `n^alpha`
+//! (`n` being the language size, and `alpha < 1` favouring smaller languages)
+//! or to explicit weights. Each language is then sampled to its quota with [SampleText].
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use clap::{arg, ArgGroup, ArgMatches};
+use log::{info, warn};
+use rand::Rng;
+use rayon::prelude::*;
+use serde_json::json;
+
+use crate::{
+    error::Error,
+    ops::{
+        build_thread_pool, is_compressed, language_parts, open_decompressed, rng_from_matches,
+        sampling_rng, seed_arg, SampleBudget, SampleText, SamplingKind, SamplingRng,
+    },
+};
+
+/// Name of the manifest written along with samples.
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// How the sample budget is split between languages.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Mixing {
+    /// Language proportions are proportional to `n^alpha`.
+    Temperature(f64),
+    /// Language proportions are proportional to the weights. Missing languages are not sampled.
+    Weights(HashMap<String, f64>),
+}
+
+impl Mixing {
+    /// Load weights from a file holding a `<lang> <weight>` pair per line.
+    /// Blank lines and lines starting with `#` are ignored.
+    pub fn from_weight_file(path: &Path) -> Result<Self, Error> {
+        let r = BufReader::new(File::open(path)?);
+        let mut weights = HashMap::new();
+        for (line_number, line) in r.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let parsed = line
+                .split_once(char::is_whitespace)
+                .and_then(|(lang, weight)| Some((lang, weight.trim().parse::<f64>().ok()?)));
+            match parsed {
+                Some((lang, weight)) if weight >= 0.0 => {
+                    weights.insert(lang.to_string(), weight);
+                }
+                _ => {
+                    return Err(Error::Custom(format!(
+                        "{:?}:{}: expected '<lang> <weight>', got {:?}",
+                        path,
+                        line_number + 1,
+                        line
+                    )))
+                }
+            }
+        }
+        Ok(Self::Weights(weights))
+    }
+
+    /// Weight of a language of size `size`.
+    fn weight(&self, lang: &str, size: usize) -> f64 {
+        match self {
+            Self::Temperature(alpha) => (size as f64).powf(*alpha),
+            Self::Weights(weights) => weights.get(lang).copied().unwrap_or(0.0),
+        }
+    }
+}
+
+/// Split `total` between languages proportionally to `weights`.
+///
+/// If `capped` is set, quotas can't exceed language sizes, and the excess is redistributed to other languages.
+pub(crate) fn quotas(sizes: &[usize], weights: &[f64], total: usize, capped: bool) -> Vec<usize> {
+    let mut quotas = vec![0.0; sizes.len()];
+    let mut fixed = vec![false; sizes.len()];
+    loop {
+        let fixed_total: f64 = (0..sizes.len())
+            .filter(|i| fixed[*i])
+            .map(|i| quotas[i])
+            .sum();
+        let remaining = (total as f64 - fixed_total).max(0.0);
+        let weight_total: f64 = (0..sizes.len())
+            .filter(|i| !fixed[*i])
+            .map(|i| weights[i])
+            .sum();
+        if weight_total <= 0.0 {
+            break;
+        }
+
+        let mut new_caps = false;
+        let unfixed: Vec<usize> = (0..sizes.len()).filter(|i| !fixed[*i]).collect();
+        for i in unfixed {
+            quotas[i] = remaining * weights[i] / weight_total;
+            if capped && quotas[i] >= sizes[i] as f64 {
+                quotas[i] = sizes[i] as f64;
+                fixed[i] = true;
+                new_caps = true;
+            }
+        }
+        if !new_caps {
+            break;
+        }
+    }
+    quotas.into_iter().map(|quota| quota as usize).collect()
+}
+
+/// Stratified sampling, on top of [SampleText].
+pub trait StratifiedSample: SampleText {
+    /// Extension of the sample files.
+    const EXTENSION: &'static str;
+
+    /// Size of a document (a raw line) in the unit of `budget`.
+    fn doc_size(doc: &[u8], budget: SampleBudget) -> Result<usize, Error>;
+
+    /// Sample the documents of `files` (the parts of a language, in order) into `dst`.
+    ///
+    /// Defaults to [SampleText::sample] on the file, or on the folder of split languages.
+    fn sample_language(
+        files: &[PathBuf],
+        dst: &Path,
+        budget: SampleBudget,
+        sampling: SamplingKind,
+        rng: &mut SamplingRng,
+    ) -> Result<(), Error> {
+        let src = match files {
+            [file] => file.as_path(),
+            _ => files[0].parent().unwrap_or(&files[0]),
+        };
+        Self::sample(src, dst, budget, sampling, rng)
+    }
+
+    /// Size of the documents of `files` in the unit of `budget`.
+    ///
+    /// The byte size of an uncompressed file is its length (separators included), without reading it.
+    fn files_size(files: &[PathBuf], budget: SampleBudget) -> Result<usize, Error> {
+        let mut size = 0;
+        let mut line = Vec::new();
+        for file in files {
+            if matches!(budget, SampleBudget::Bytes(_)) && !is_compressed(file) {
+                size += file.metadata()?.len() as usize;
+                continue;
+            }
+            let mut r = open_decompressed(file)?;
+            loop {
+                line.clear();
+                if r.read_until(b'\n', &mut line)? == 0 {
+                    break;
+                }
+                if !line.iter().all(u8::is_ascii_whitespace) {
+                    size += Self::doc_size(&line, budget)?;
+                }
+            }
+        }
+        Ok(size)
+    }
+
+    /// Sample each language of the corpus at `src` to its quota (see [Mixing]) into `dst/<lang>.<ext>`,
+    /// and write a manifest of the target and achieved proportions in `dst`.
+    ///
+    /// Languages are sampled in parallel, each one with a seed drawn from `rng`.
+    fn sample_languages(
+        src: &Path,
+        dst: &Path,
+        budget: SampleBudget,
+        mixing: &Mixing,
+        sampling: SamplingKind,
+        rng: &mut SamplingRng,
+    ) -> Result<(), Error> {
+        let languages = language_parts(src)?;
+        if languages.is_empty() {
+            return Err(Error::Custom(format!("No languages found in {:?}", src)));
+        }
+
+        info!("measuring {} languages...", languages.len());
+        let sizes: Result<Vec<usize>, Error> = languages
+            .par_iter()
+            .map(|(_, files)| Self::files_size(files, budget))
+            .collect();
+        let sizes = sizes?;
+        let budget = budget.resolve(sizes.iter().sum());
+
+        let weights: Vec<f64> = languages
+            .iter()
+            .zip(&sizes)
+            .map(|((lang, _), size)| mixing.weight(lang, *size))
+            .collect();
+        let capped = matches!(sampling, SamplingKind::WithoutReplacement);
+        let quotas = quotas(&sizes, &weights, budget.size(), capped);
+
+        std::fs::create_dir_all(dst)?;
+        let seeds: Vec<u64> = languages.iter().map(|_| rng.gen()).collect();
+        let results: Vec<Result<usize, Error>> = languages
+            .par_iter()
+            .zip(&quotas)
+            .zip(&seeds)
+            .map(|(((lang, files), quota), seed)| {
+                if *quota == 0 {
+                    return Ok(0);
+                }
+                let lang_dst = dst.join(format!("{lang}.{}", Self::EXTENSION));
+                let lang_budget = match budget {
+                    SampleBudget::Bytes(_) => SampleBudget::Bytes(*quota),
+                    SampleBudget::Tokens(_) => SampleBudget::Tokens(*quota),
+                    SampleBudget::Docs(_) | SampleBudget::Fraction(_) => SampleBudget::Docs(*quota),
+                };
+                info!("sampling {lang} ({lang_budget:?})");
+                Self::sample_language(
+                    files,
+                    &lang_dst,
+                    lang_budget,
+                    sampling,
+                    &mut sampling_rng(*seed),
+                )?;
+                Self::files_size(&[lang_dst], budget)
+            })
+            .collect();
+
+        let mut sampled = Vec::with_capacity(results.len());
+        let mut nb_errors = 0;
+        for ((lang, _), result) in languages.iter().zip(results) {
+            match result {
+                Ok(size) => sampled.push(size),
+                Err(e) => {
+                    warn!("could not sample {lang}: {:?}", e);
+                    sampled.push(0);
+                    nb_errors += 1;
+                }
+            }
+        }
+
+        let weight_total: f64 = weights.iter().sum();
+        let sampled_total: usize = sampled.iter().sum();
+        let manifest_languages: Vec<_> = languages
+            .iter()
+            .enumerate()
+            .map(|(i, (lang, _))| {
+                json!({
+                    "lang": lang,
+                    "available": sizes[i],
+                    "target_proportion": if weight_total > 0.0 { weights[i] / weight_total } else { 0.0 },
+                    "quota": quotas[i],
+                    "sampled": sampled[i],
+                    "proportion": if sampled_total > 0 { sampled[i] as f64 / sampled_total as f64 } else { 0.0 },
+                })
+            })
+            .collect();
+        let manifest = json!({
+            "budget": format!("{budget:?}"),
+            "mixing": match mixing {
+                Mixing::Temperature(alpha) => json!({"alpha": alpha}),
+                Mixing::Weights(weights) => json!({"weights": weights}),
+            },
+            "sampled": sampled_total,
+            "languages": manifest_languages,
+        });
+        let mut w = BufWriter::new(File::create(dst.join(MANIFEST_FILE))?);
+        serde_json::to_writer_pretty(&mut w, &manifest)?;
+        writeln!(w)?;
+        w.flush()?;
+
+        if nb_errors > 0 {
+            return Err(Error::Custom(
+                "Error(s) during stratified sampling. Check logs.".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// `sample-languages` subcommand, run with [StratifiedSample::sample_languages_from_matches].
+    fn sample_languages_subcommand() -> clap::App<'static> {
+        clap::App::new("sample-languages")
+            .about("Sample every language of a corpus to a quota, proportional to n^alpha (n being the language size) or to weights.")
+            .long_about("Stratified multilingual sampling.
+
+The sample size is split into per-language quotas, proportional either to n^alpha (--alpha, n being the size of the language in the sample size unit), or to weights (--weights, a file of '<lang> <weight>' lines).
+alpha=1 keeps the corpus proportions, and alpha<1 favours smaller languages.
+Without replacement, quotas can't exceed language sizes, and the excess is given to the other languages.
+
+Each language is sampled into DESTINATION/<lang>.txt (v1) or DESTINATION/<lang>.jsonl (v2), and a manifest of the target and achieved proportions is written in DESTINATION/manifest.json.")
+            .arg(arg!([SOURCE] "Corpus root folder. Subfolders and files are languages.").required(true))
+            .arg(arg!([DESTINATION] "Destination folder.").required(true))
+            .args(SampleBudget::args())
+            .group(SampleBudget::group())
+            .arg(arg!(--alpha <ALPHA> "Exponent of the language sizes.").required(false))
+            .arg(arg!(--weights <FILE> "File of language weights.").required(false))
+            .group(ArgGroup::new("mixing").args(&["alpha", "weights"]).required(true))
+            .arg(SamplingKind::arg())
+            .arg(seed_arg())
+            .arg(arg!(-J --num_threads <NUM_THREADS> "Number of threads to use. If 0, take all available").default_value("0").required(false))
+    }
+
+    /// Run [StratifiedSample::sample_languages] with the arguments of [StratifiedSample::sample_languages_subcommand].
+    fn sample_languages_from_matches(matches: &ArgMatches) -> Result<(), Error> {
+        let src: PathBuf = matches.value_of("SOURCE").unwrap().into();
+        let dst: PathBuf = matches.value_of("DESTINATION").unwrap().into();
+        let budget = SampleBudget::from_matches(matches)?;
+        let mixing = match matches.value_of("weights") {
+            Some(weights) => Mixing::from_weight_file(Path::new(weights))?,
+            None => Mixing::Temperature(
+                matches
+                    .value_of("alpha")
+                    .unwrap()
+                    .parse()
+                    .expect("'alpha' has to be a number."),
+            ),
+        };
+        let sampling = SamplingKind::from_matches(matches);
+        let mut rng = rng_from_matches(matches)?;
+        let num_threads: usize = matches
+            .value_of("num_threads")
+            .unwrap()
+            .parse()
+            .expect("'num_threads' has to be a number.");
+        build_thread_pool(num_threads);
+
+        Self::sample_languages(&src, &dst, budget, &mixing, sampling, &mut rng)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use tempfile::NamedTempFile;
+
+    use super::{quotas, Mixing};
+
+    #[test]
+    fn test_quotas_proportional() {
+        let q = quotas(&[100, 300], &[1.0, 3.0], 40, true);
+        assert_eq!(q, [10, 30]);
+    }
+
+    #[test]
+    fn test_quotas_capped() {
+        // first language can only give 5, the rest goes to the second one
+        let q = quotas(&[5, 1000], &[1.0, 1.0], 100, true);
+        assert_eq!(q, [5, 95]);
+
+        // uncapped
+        let q = quotas(&[5, 1000], &[1.0, 1.0], 100, false);
+        assert_eq!(q, [50, 50]);
+
+        // not enough data
+        let q = quotas(&[5, 10], &[1.0, 1.0], 100, true);
+        assert_eq!(q, [5, 10]);
+    }
+
+    #[test]
+    fn test_temperature() {
+        let mixing = Mixing::Temperature(0.5);
+        assert_eq!(mixing.weight("fr", 100), 10.0);
+        let mixing = Mixing::Temperature(0.0);
+        assert_eq!(mixing.weight("fr", 100), 1.0);
+    }
+
+    #[test]
+    fn test_weight_file() {
+        let mut f = NamedTempFile::new().unwrap();
+        writeln!(f, "# weights\nfr 0.5\n\nen\t2").unwrap();
+        let mixing = Mixing::from_weight_file(f.path()).unwrap();
+        assert_eq!(mixing.weight("fr", 1), 0.5);
+        assert_eq!(mixing.weight("en", 1), 2.0);
+        assert_eq!(mixing.weight("de", 1), 0.0);
+
+        let mut f = NamedTempFile::new().unwrap();
+        writeln!(f, "fr").unwrap();
+        assert!(Mixing::from_weight_file(f.path()).is_err());
+    }
+}
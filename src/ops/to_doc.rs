@@ -0,0 +1,136 @@
+/*! Conversion of OSCAR v1 (text) corpora into minimal OSCAR v2 documents.
+
+ Each blank-line separated document becomes a v2 document holding its content, no WARC headers,
+ and the language of its file (`fr.txt`, `fr_dedup.txt.gz` and `fr/fr_part_1.txt` are all `fr`) with a probability of `1`.
+ Lines have no identifications and documents have no annotations.
+
+ Folders keep their layout: `fr/fr_part_1.txt` is converted into `fr/fr_part_1.jsonl`.
+!*/
+use std::{
+    fs::File,
+    io::{BufRead, BufWriter, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use log::info;
+use oscar_io::{
+    common::Identification,
+    lang::Lang,
+    oscar_doc::{Document, Metadata, Writer},
+};
+
+use crate::{
+    error::Error,
+    ops::{for_each_file, language_parts, mirrored_path, open_decompressed, write_atomically},
+};
+
+/// Language of a v1 corpus file, inferred from its name.
+///
+/// Extensions, part numbers (`_part_<n>`) and deduplication suffixes (`_dedup`) are ignored.
+pub(crate) fn lang_from_path(path: &Path) -> Result<Lang, Error> {
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.split('.').next())
+        .ok_or_else(|| Error::Custom(format!("invalid file name: {:?}", path)))?;
+    let name = match name.rsplit_once("_part_") {
+        Some((name, part)) if part.parse::<u64>().is_ok() => name,
+        _ => name,
+    };
+    let name = name.strip_suffix("_dedup").unwrap_or(name);
+    Lang::from_str(name).map_err(|_| {
+        Error::Custom(format!(
+            "could not infer a language from {:?}: {name:?} is not a known language",
+            path
+        ))
+    })
+}
+
+pub trait ToDoc {
+    /// Read the next document of `r` in `doc`, without its separator.
+    /// Returns `false` when there are no documents left.
+    fn next_doc<R: BufRead>(r: &mut R, doc: &mut String) -> Result<bool, Error>;
+
+    /// Convert the documents of `r` into `lang` documents, written in `w`.
+    /// Returns the number of converted documents.
+    fn convert_docs<R: BufRead, W: Write>(
+        mut r: R,
+        w: &mut Writer<W>,
+        lang: Lang,
+    ) -> Result<usize, Error> {
+        let identification = Identification::new(lang, 1.0);
+        let mut doc = String::new();
+        let mut nb_docs = 0;
+        while Self::next_doc(&mut r, &mut doc)? {
+            let content = doc.trim_end().to_string();
+            let sentence_identifications = vec![None; content.lines().count()];
+            let metadata = Metadata::new(&identification, &None, &sentence_identifications);
+            w.write(&Document::new(content, Default::default(), metadata))?;
+            nb_docs += 1;
+        }
+        Ok(nb_docs)
+    }
+
+    /// Convert the v1 file `src` into the `dst` file (see [write_atomically]).
+    fn convert_file(src: &Path, dst: &Path) -> Result<usize, Error> {
+        let lang = lang_from_path(src)?;
+        info!("converting {:?} ({lang}) into {:?}", src, dst);
+        let r = open_decompressed(src)?;
+        write_atomically(dst, |temp| {
+            let mut w = Writer::new(BufWriter::new(File::create(temp)?));
+            let nb_docs = Self::convert_docs(r, &mut w, lang)?;
+            w.flush()?;
+            Ok(nb_docs)
+        })
+    }
+
+    /// Convert the v1 file `src` into the `dst` file, or the v1 corpus folder `src` into the `dst` folder
+    /// (files in parallel), keeping its layout.
+    fn to_doc(src: &Path, dst: &Path, num_threads: usize) -> Result<(), Error> {
+        if src.is_file() {
+            let nb_docs = Self::convert_file(src, dst)?;
+            info!("converted {nb_docs} documents");
+            return Ok(());
+        }
+
+        let files: Vec<PathBuf> = language_parts(src)?
+            .into_iter()
+            .flat_map(|(_, files)| files)
+            .collect();
+        let nb_docs: usize = for_each_file(&files, num_threads, "converting", |file| {
+            let file_dst = mirrored_path(file, src, dst, "jsonl");
+            if let Some(parent) = file_dst.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            Self::convert_file(file, &file_dst)
+        })?
+        .into_iter()
+        .sum();
+        info!("converted {nb_docs} documents from {} files", files.len());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use oscar_io::lang::Lang;
+
+    use super::lang_from_path;
+
+    #[test]
+    fn test_lang_from_path() {
+        assert_eq!(lang_from_path(Path::new("fr.txt")).unwrap(), Lang::Fr);
+        assert_eq!(
+            lang_from_path(Path::new("corpus/de_dedup.txt.gz")).unwrap(),
+            Lang::De
+        );
+        assert_eq!(
+            lang_from_path(Path::new("en/en_part_12.txt")).unwrap(),
+            Lang::En
+        );
+        assert!(lang_from_path(Path::new("foo.txt")).is_err());
+    }
+}
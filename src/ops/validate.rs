@@ -0,0 +1,256 @@
+//! Schema validation of OSCAR v2 corpora.
+//!
+//! Documents are checked as JSON values rather than deserialized into [oscar_io::oscar_doc::Document],
+//! which panics on unknown language labels and stops at the first error of a document.
+use std::{
+    fmt::Display,
+    io::{BufRead, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use log::info;
+use oscar_io::lang::Lang;
+use serde_json::Value;
+
+use crate::{
+    error::Error,
+    ops::{for_each_file, open_decompressed, source_languages},
+};
+
+/// Violation of the schema, located at a line of a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Violation {
+    file: PathBuf,
+    line: usize,
+    message: String,
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.file.display(), self.line, self.message)
+    }
+}
+
+/// Check an identification (`{"label": <lang>, "prob": <[0, 1]>}`) found at `field`.
+fn check_identification(id: &Value, field: &str, violations: &mut Vec<String>) {
+    match id["label"].as_str() {
+        Some(label) if Lang::from_str(label).is_err() => {
+            violations.push(format!("{field}.label: unknown language {label:?}"))
+        }
+        Some(_) => (),
+        None => violations.push(format!("{field}.label: missing or not a string")),
+    }
+    match id["prob"].as_f64() {
+        Some(prob) if !(0.0..=1.0).contains(&prob) => {
+            violations.push(format!("{field}.prob: {prob} is not in [0, 1]"))
+        }
+        Some(_) => (),
+        None => violations.push(format!("{field}.prob: missing or not a number")),
+    }
+}
+
+/// Schema violations of a document.
+///
+/// `content-length` is the byte length of the original record,
+/// which may hold a trailing newline that is not part of `content`.
+pub(crate) fn check_doc(doc: &str) -> Vec<String> {
+    let doc: Value = match serde_json::from_str(doc) {
+        Ok(doc) => doc,
+        Err(e) => return vec![format!("invalid JSON: {e}")],
+    };
+    if !doc.is_object() {
+        return vec!["document is not an object".to_string()];
+    }
+    let mut violations = Vec::new();
+
+    let content = doc["content"].as_str();
+    if content.is_none() {
+        violations.push("content: missing or not a string".to_string());
+    }
+
+    match doc["warc_headers"].as_object() {
+        Some(headers) => {
+            for (name, value) in headers {
+                if !value.is_string() {
+                    violations.push(format!("warc_headers.{name}: not a string"));
+                }
+            }
+            if let (Some(content), Some(length)) = (content, headers.get("content-length")) {
+                match length.as_str().map(str::parse::<usize>) {
+                    Some(Ok(length)) if length != content.len() && length != content.len() + 1 => {
+                        violations.push(format!(
+                            "warc_headers.content-length: {length} does not match content length ({})",
+                            content.len()
+                        ))
+                    }
+                    Some(Ok(_)) => (),
+                    Some(Err(_)) => violations.push(format!(
+                        "warc_headers.content-length: {length} is not a number"
+                    )),
+                    // not a string, already reported
+                    None => (),
+                }
+            }
+        }
+        None => violations.push("warc_headers: missing or not an object".to_string()),
+    }
+
+    let metadata = &doc["metadata"];
+    if !metadata.is_object() {
+        violations.push("metadata: missing or not an object".to_string());
+        return violations;
+    }
+    check_identification(
+        &metadata["identification"],
+        "metadata.identification",
+        &mut violations,
+    );
+    match &metadata["annotation"] {
+        Value::Null => (),
+        Value::Array(annotations) => {
+            if !annotations.iter().all(Value::is_string) {
+                violations.push("metadata.annotation: not an array of strings".to_string());
+            }
+        }
+        _ => violations.push("metadata.annotation: not an array or null".to_string()),
+    }
+    match metadata["sentence_identifications"].as_array() {
+        Some(ids) => {
+            for (i, id) in ids.iter().enumerate() {
+                if !id.is_null() {
+                    check_identification(
+                        id,
+                        &format!("metadata.sentence_identifications[{i}]"),
+                        &mut violations,
+                    );
+                }
+            }
+            if let Some(content) = content {
+                let nb_lines = content.lines().count();
+                if ids.len() != nb_lines {
+                    violations.push(format!(
+                        "metadata.sentence_identifications: {} identifications for {nb_lines} lines",
+                        ids.len()
+                    ));
+                }
+            }
+        }
+        None => violations
+            .push("metadata.sentence_identifications: missing or not an array".to_string()),
+    }
+    violations
+}
+
+/// Schema violations of the documents (non blank lines) of `r`, located in `file`.
+pub(crate) fn check_docs<R: BufRead>(r: R, file: &Path) -> Result<Vec<Violation>, Error> {
+    let mut violations = Vec::new();
+    for (line_number, line) in r.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        violations.extend(check_doc(&line).into_iter().map(|message| Violation {
+            file: file.to_path_buf(),
+            line: line_number + 1,
+            message,
+        }));
+    }
+    Ok(violations)
+}
+
+pub trait Validate {
+    /// Check every document of `src` (files in parallel), writing violations into `w` as `file:line: message`.
+    ///
+    /// Fails if any violation is found.
+    fn validate<W: Write>(src: &Path, w: &mut W, num_threads: usize) -> Result<(), Error> {
+        let files: Vec<PathBuf> = source_languages(src)?
+            .into_iter()
+            .flat_map(|(_, files)| files)
+            .collect();
+        let results = for_each_file(&files, num_threads, "validating", |file| {
+            info!("validating {:?}", file);
+            check_docs(open_decompressed(file)?, file)
+        })?;
+
+        let mut nb_violations = 0;
+        for violations in results {
+            nb_violations += violations.len();
+            for violation in violations {
+                writeln!(w, "{violation}")?;
+            }
+        }
+        w.flush()?;
+
+        if nb_violations > 0 {
+            return Err(Error::Custom(format!(
+                "{nb_violations} schema violation(s) found in {:?}",
+                src
+            )));
+        }
+        info!("{} files are valid", files.len());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use serde_json::json;
+
+    use super::{check_doc, check_docs};
+
+    fn doc() -> serde_json::Value {
+        json!({
+            "content": "foo\nbar",
+            "warc_headers": {"content-length": "8", "warc-record-id": "<urn:uuid:1>"},
+            "metadata": {
+                "identification": {"label": "en", "prob": 0.9},
+                "annotation": null,
+                "sentence_identifications": [{"label": "en", "prob": 0.9}, null],
+            },
+        })
+    }
+
+    #[test]
+    fn test_check_doc() {
+        assert!(check_doc(&doc().to_string()).is_empty());
+
+        let mut invalid = doc();
+        invalid["warc_headers"]["content-length"] = json!("42");
+        invalid["metadata"]["identification"] = json!({"label": "xx", "prob": 1.5});
+        invalid["metadata"]["sentence_identifications"] = json!([null]);
+        let violations = check_doc(&invalid.to_string());
+        assert_eq!(violations.len(), 4, "{violations:?}");
+        assert!(violations[0].starts_with("warc_headers.content-length"));
+        assert!(violations[1].contains("unknown language \"xx\""));
+        assert!(violations[2].contains("1.5 is not in [0, 1]"));
+        assert!(violations[3].contains("1 identifications for 2 lines"));
+
+        assert_eq!(
+            check_doc("{"),
+            vec!["invalid JSON: EOF while parsing an object at line 1 column 1"]
+        );
+        assert_eq!(
+            check_doc(r#"{"content": "foo"}"#),
+            vec![
+                "warc_headers: missing or not an object",
+                "metadata: missing or not an object"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_docs() {
+        let mut invalid = doc();
+        invalid["content"] = json!(1);
+        let corpus = format!("{}\n\n{}\n", doc(), invalid);
+        let violations = check_docs(corpus.as_bytes(), Path::new("fr.jsonl")).unwrap();
+        assert_eq!(violations.len(), 1);
+        assert_eq!(
+            violations[0].to_string(),
+            "fr.jsonl:3: content: missing or not a string"
+        );
+    }
+}
@@ -0,0 +1,337 @@
+/*! Export into WebDataset-style tar shards.
+
+ Each language is written into sequentially numbered POSIX tar shards (`<lang>/<lang>-000000.tar`, `<lang>/<lang>-000001.tar`...),
+ rotated like [crate::ops::SplitWriter] (see [Rotation]).
+ Each document is a sample `<lang>-<ordinal>`, made of a `.txt` member (its content) and a `.json` member (its metadata).
+
+ Each language folder also holds a [SHARD_INDEX] listing its shards along with their sizes and number of samples.
+!*/
+use std::{
+    fs::File,
+    io::{BufRead, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use clap::{arg, Arg, ArgMatches};
+use log::{debug, info, warn};
+use serde_json::{json, Value};
+use tar::{Builder, EntryType, Header};
+
+use crate::{
+    error::Error,
+    ops::{for_each_language, open_decompressed, source_languages, temp_path, Rotation},
+};
+
+/// Name of the shard index of each language, following the `wids` shard index format.
+pub const SHARD_INDEX: &str = "shardindex.json";
+
+/// Size of tar headers and blocks.
+const BLOCK_SIZE: usize = 512;
+
+/// Written shard.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ShardInfo {
+    pub name: String,
+    pub size: u64,
+    pub samples: usize,
+}
+
+/// Rotating tar shard writer.
+///
+/// Each [ShardWriter::append] call is a sample, and shards are only rotated between samples.
+/// Shards are written under their [temp_path] until complete, and an unfinished one is removed if the writer is dropped.
+pub(crate) struct ShardWriter {
+    dir: PathBuf,
+    prefix: String,
+    rotation: Rotation,
+    builder: Option<Builder<BufWriter<File>>>,
+    current_size: usize,
+    current_docs: usize,
+    current_lines: usize,
+    shards: Vec<ShardInfo>,
+}
+
+impl ShardWriter {
+    /// Create a new writer of `dir/<prefix>-<shard number>.tar` shards, rotating according to `rotation`.
+    pub fn new(dir: &Path, prefix: &str, rotation: Rotation) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            prefix: prefix.to_string(),
+            rotation,
+            builder: None,
+            current_size: 0,
+            current_docs: 0,
+            current_lines: 0,
+            shards: Vec::new(),
+        }
+    }
+
+    /// Size of a tar member holding `len` bytes.
+    fn member_size(len: usize) -> usize {
+        BLOCK_SIZE + len.div_ceil(BLOCK_SIZE) * BLOCK_SIZE
+    }
+
+    fn shard_name(&self, number: usize) -> String {
+        format!("{}-{number:06}.tar", self.prefix)
+    }
+
+    fn append_member(
+        builder: &mut Builder<BufWriter<File>>,
+        name: &str,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        let mut header = Header::new_ustar();
+        header.set_entry_type(EntryType::Regular);
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(0);
+        builder.append_data(&mut header, name, data)?;
+        Ok(())
+    }
+
+    /// Write the sample `key`, made of `key.txt` and `key.json` members.
+    pub fn append(&mut self, key: &str, txt: &[u8], json: &[u8]) -> Result<(), Error> {
+        let size = Self::member_size(txt.len()) + Self::member_size(json.len());
+        let nb_lines = txt.iter().filter(|b| **b == b'\n').count();
+        let is_full = match self.rotation {
+            Rotation::Bytes(max_size) => self.current_size + size > max_size,
+            Rotation::Docs(max_docs) => self.current_docs >= max_docs,
+            Rotation::Lines(max_lines) => self.current_lines + nb_lines > max_lines,
+        };
+        // don't leave an empty shard behind an oversized sample
+        if is_full && self.current_docs > 0 {
+            self.close_shard()?;
+        }
+        if self.builder.is_none() {
+            let shard = self.dir.join(self.shard_name(self.shards.len()));
+            debug!("creating {:?}", shard);
+            self.builder = Some(Builder::new(BufWriter::new(File::create(temp_path(
+                &shard,
+            ))?)));
+        }
+
+        if let Some(builder) = &mut self.builder {
+            Self::append_member(builder, &format!("{key}.txt"), txt)?;
+            Self::append_member(builder, &format!("{key}.json"), json)?;
+        }
+        self.current_size += size;
+        self.current_docs += 1;
+        self.current_lines += nb_lines;
+        Ok(())
+    }
+
+    /// Finish the current shard, if any.
+    fn close_shard(&mut self) -> Result<(), Error> {
+        if let Some(builder) = self.builder.take() {
+            let mut w = builder.into_inner()?;
+            w.flush()?;
+            drop(w);
+
+            let name = self.shard_name(self.shards.len());
+            let shard = self.dir.join(&name);
+            std::fs::rename(temp_path(&shard), &shard)?;
+            self.shards.push(ShardInfo {
+                name,
+                size: shard.metadata()?.len(),
+                samples: self.current_docs,
+            });
+            self.current_size = 0;
+            self.current_docs = 0;
+            self.current_lines = 0;
+        }
+        Ok(())
+    }
+
+    /// Finish the last shard, returning the written shards.
+    pub fn finish(mut self) -> Result<Vec<ShardInfo>, Error> {
+        self.close_shard()?;
+        Ok(std::mem::take(&mut self.shards))
+    }
+}
+
+impl Drop for ShardWriter {
+    fn drop(&mut self) {
+        if self.builder.take().is_some() {
+            let shard = self.dir.join(self.shard_name(self.shards.len()));
+            if let Err(e) = std::fs::remove_file(temp_path(&shard)) {
+                warn!("could not remove unfinished shard {:?}: {:?}", shard, e);
+            }
+        }
+    }
+}
+
+/// Shard index of `shards`.
+fn shard_index(shards: &[ShardInfo]) -> Value {
+    let shardlist: Vec<Value> = shards
+        .iter()
+        .map(|shard| {
+            json!({
+                "url": shard.name,
+                "nsamples": shard.samples,
+                "filesize": shard.size,
+            })
+        })
+        .collect();
+    json!({
+        "__kind__": "wids-shard-index-v1",
+        "wids_version": 1,
+        "shardlist": shardlist,
+    })
+}
+
+/// Command line arguments of [WebDataset::webdataset_from_matches].
+pub fn webdataset_args() -> Vec<Arg<'static>> {
+    let mut args = vec![
+        arg!([SOURCE] "Corpus source file/folder.").required(true),
+        arg!([DESTINATION] "Destination folder.").required(true),
+    ];
+    args.extend(Rotation::args(Some("500")));
+    args.push(
+        arg!(-J --num_threads <NUM_THREADS> "Number of threads to use (iif source is a folder). If 0, take all available")
+            .default_value("0")
+            .required(false),
+    );
+    args
+}
+
+pub trait WebDataset {
+    /// Read the next document of `r` (of language `lang`), as its text and its metadata.
+    /// Returns `None` when there are no documents left.
+    fn next_sample<R: BufRead>(r: &mut R, lang: &str) -> Result<Option<(String, Value)>, Error>;
+
+    /// Write the documents of `files` (read in order) into shards of `dst`, along with their [SHARD_INDEX].
+    fn write_shards(
+        lang: &str,
+        files: &[PathBuf],
+        dst: &Path,
+        rotation: Rotation,
+    ) -> Result<Vec<ShardInfo>, Error> {
+        std::fs::create_dir_all(dst)?;
+        let mut w = ShardWriter::new(dst, lang, rotation);
+        let mut ordinal = 0;
+        for file in files {
+            let mut r = open_decompressed(file)?;
+            while let Some((txt, metadata)) = Self::next_sample(&mut r, lang)? {
+                let key = format!("{lang}-{ordinal:09}");
+                w.append(&key, txt.as_bytes(), metadata.to_string().as_bytes())?;
+                ordinal += 1;
+            }
+        }
+        let shards = w.finish()?;
+
+        let mut index = BufWriter::new(File::create(dst.join(SHARD_INDEX))?);
+        serde_json::to_writer_pretty(&mut index, &shard_index(&shards))?;
+        writeln!(index)?;
+        index.flush()?;
+        Ok(shards)
+    }
+
+    /// Write the corpus file/folder `src` into `dst/<lang>/` shards (languages in parallel).
+    fn webdataset(
+        src: &Path,
+        dst: &Path,
+        rotation: Rotation,
+        num_threads: usize,
+    ) -> Result<(), Error> {
+        let languages = source_languages(src)?;
+        for_each_language(
+            &languages,
+            num_threads,
+            "writing shards of",
+            |lang, files| {
+                info!("writing {lang} shards ({rotation:?})");
+                let shards = Self::write_shards(lang, files, &dst.join(lang), rotation)?;
+                info!("{lang}: {} shards", shards.len());
+                Ok(())
+            },
+        )?;
+        Ok(())
+    }
+
+    /// Run [WebDataset::webdataset] with the arguments of [webdataset_args].
+    fn webdataset_from_matches(matches: &ArgMatches) -> Result<(), Error> {
+        let src: PathBuf = matches
+            .value_of("SOURCE")
+            .expect("Value of 'SOURCE' is required.")
+            .into();
+        let dst: PathBuf = matches
+            .value_of("DESTINATION")
+            .expect("Value of 'DESTINATION' is required.")
+            .into();
+        let rotation = Rotation::from_matches(matches)?.expect("'size' has a default value.");
+        let num_threads: usize = matches
+            .value_of("num_threads")
+            .unwrap()
+            .parse()
+            .expect("'num_threads' has to be a number.");
+
+        Self::webdataset(&src, &dst, rotation, num_threads)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, io::Read};
+
+    use tar::Archive;
+    use tempfile::tempdir;
+
+    use crate::ops::Rotation;
+
+    use super::{ShardWriter, BLOCK_SIZE};
+
+    /// Members of a shard, along with their content.
+    fn read_shard(path: &std::path::Path) -> Vec<(String, String)> {
+        let mut archive = Archive::new(File::open(path).unwrap());
+        archive
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let mut entry = entry.unwrap();
+                let name = entry.path().unwrap().to_string_lossy().into_owned();
+                let mut content = String::new();
+                entry.read_to_string(&mut content).unwrap();
+                (name, content)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_shard_writer() {
+        let dir = tempdir().unwrap();
+        // each sample takes 4 blocks
+        let mut w = ShardWriter::new(dir.path(), "fr", Rotation::Bytes(9 * BLOCK_SIZE));
+        for i in 0..5 {
+            w.append(&format!("fr-{i}"), b"foo\nbar\n", b"{}").unwrap();
+        }
+        let shards = w.finish().unwrap();
+
+        assert_eq!(
+            shards.iter().map(|s| s.samples).collect::<Vec<_>>(),
+            vec![2, 2, 1]
+        );
+        assert_eq!(shards[2].name, "fr-000002.tar");
+        for shard in &shards {
+            let path = dir.path().join(&shard.name);
+            assert_eq!(path.metadata().unwrap().len(), shard.size);
+        }
+        assert_eq!(
+            read_shard(&dir.path().join("fr-000001.tar")),
+            vec![
+                ("fr-2.txt".to_string(), "foo\nbar\n".to_string()),
+                ("fr-2.json".to_string(), "{}".to_string()),
+                ("fr-3.txt".to_string(), "foo\nbar\n".to_string()),
+                ("fr-3.json".to_string(), "{}".to_string()),
+            ]
+        );
+        // no temporary leftovers
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 3);
+
+        // unfinished shards are removed
+        let dir = tempdir().unwrap();
+        let mut w = ShardWriter::new(dir.path(), "fr", Rotation::Docs(10));
+        w.append("fr-0", b"foo\n", b"{}").unwrap();
+        drop(w);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+}